@@ -36,30 +36,64 @@ pub fn from_u64(num: u64) -> String {
 
     let mut num = num;
 
-    let mut i = 64;
-
     // If the number is zero we don't need to do anything
     if num == 0 {
         return "0".to_string();
     }
 
     while num > 0 {
-        let j = num % i;
+        let j = num % 64;
 
         out.push(TABLE[j as usize]);
 
-        num /= i;
-        i *= 64;
+        num /= 64;
     }
 
     // Return the reversed string since we built it backwards(to be more effecient)
     out.chars().rev().collect::<String>()
 }
 
-/// Takes a pinv-style base64 string and converts it to a u64. Unwraps on
-/// error or invalid character, should be changed in an update.
+/// Width keys are padded to in tables and on labels, enough for every key
+/// below 64^6 to line up
+pub const KEY_WIDTH: usize = 6;
+
+/// Takes a u64 and converts it to a pinv-style base64 string, left-padded
+/// with zeros to at least `width` characters. Longer keys are not truncated.
+pub fn from_u64_padded(num: u64, width: usize) -> String {
+    format!("{:0>width$}", from_u64(num), width = width)
+}
+
+/// Check that a string is a pinv-style base64 key, ignoring surrounding
+/// whitespace. Empty strings are not valid keys.
+pub fn is_valid(string: &str) -> bool {
+    let string = string.trim();
+
+    !string.is_empty() && string.chars().all(|digit| TABLE.contains(&digit))
+}
+
+/// Convert a key typed in by the user, checking it with is_valid first so a
+/// mistyped key gets a clearer error than to_u64 gives
+pub fn parse_key(string: &str) -> Result<u64, Box<dyn Error>> {
+    if string.trim().is_empty() {
+        bail!("No key given!");
+    }
+
+    if !is_valid(string) {
+        bail!(
+            "\"{}\" isn't a key, keys only use 0-9, A-Z, a-z, + and -!",
+            string.trim()
+        );
+    }
+
+    to_u64(string)
+}
+
+/// Takes a pinv-style base64 string and converts it to a u64. Errors on an
+/// invalid character or if the key is too large to fit in a u64.
 pub fn to_u64(string: &str) -> Result<u64, Box<dyn Error>> {
-    let mut pow = 1;
+    // None once the place value no longer fits in a u64, which is only an
+    // error if a non-zero digit appears there
+    let mut pow = Some(1u64);
     let mut out: u64 = 0;
 
     for digit in string.trim().chars().rev() {
@@ -70,10 +104,86 @@ pub fn to_u64(string: &str) -> Result<u64, Box<dyn Error>> {
             }
         };
 
-        out += (digit_val as u64) * pow;
+        if digit_val != 0 {
+            out = match pow
+                .and_then(|pow| (digit_val as u64).checked_mul(pow))
+                .and_then(|val| out.checked_add(val))
+            {
+                Some(out) => out,
+                None => {
+                    bail!("Key {} is too large!", string.trim());
+                }
+            };
+        }
 
-        pow *= 64;
+        pow = pow.and_then(|pow| pow.checked_mul(64));
     }
 
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_b64_from_u64() {
+        assert_eq!(from_u64(0), "0");
+        assert_eq!(from_u64(63), "-");
+        assert_eq!(from_u64(64), "10");
+        assert_eq!(from_u64(4096), "100");
+        assert_eq!(from_u64(u64::MAX), "F----------");
+    }
+
+    #[test]
+    fn test_b64_from_u64_padded() {
+        assert_eq!(from_u64_padded(0, 6), "000000");
+        assert_eq!(from_u64_padded(64_u64.pow(5), 6), "100000");
+        assert_eq!(from_u64_padded(64_u64.pow(6), 6), "1000000");
+    }
+
+    #[test]
+    fn test_b64_to_u64() {
+        assert_eq!(to_u64("0").unwrap(), 0);
+        assert_eq!(to_u64("A").unwrap(), 10);
+        assert_eq!(to_u64("10").unwrap(), 64);
+        assert_eq!(to_u64(" 1- ").unwrap(), 127);
+
+        assert!(to_u64("1_").is_err());
+    }
+
+    #[test]
+    fn test_b64_is_valid() {
+        assert!(is_valid("0"));
+        assert!(is_valid("aZ+-9"));
+        assert!(is_valid(" 1F "));
+
+        assert!(!is_valid(""));
+        assert!(!is_valid("   "));
+        assert!(!is_valid("1 F"));
+        assert!(!is_valid("1_F"));
+        assert!(!is_valid("é"));
+    }
+
+    #[test]
+    fn test_b64_parse_key() {
+        assert_eq!(parse_key(" A ").unwrap(), 10);
+
+        assert_eq!(parse_key(" ").unwrap_err().to_string(), "No key given!");
+        assert_eq!(
+            parse_key("1 F").unwrap_err().to_string(),
+            "\"1 F\" isn't a key, keys only use 0-9, A-Z, a-z, + and -!"
+        );
+    }
+
+    #[test]
+    fn test_b64_to_u64_overflow() {
+        // Longest valid key
+        assert_eq!(to_u64("F----------").unwrap(), u64::MAX);
+        assert_eq!(to_u64("0000F----------").unwrap(), u64::MAX);
+
+        // One past the largest value, and one character beyond the longest key
+        assert!(to_u64("G0000000000").is_err());
+        assert!(to_u64("100000000000").is_err());
+    }
+}
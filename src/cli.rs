@@ -0,0 +1,2136 @@
+//! Command line interface, kept apart from main so the subcommands can be run
+//! with their output going anywhere, not just stdout
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+
+use crate::config::{Config, Confirmation};
+use crate::db::{
+    Catagory, CatagoryField, Condition, ConditionOperator, DataType, Db, Entry, EntryField, Sort,
+    SortOrder,
+};
+use crate::{b64, csv, table, templates};
+use chrono::Local;
+use clap::{arg, command, value_parser, ArgMatches, Command};
+use simple_error::bail;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Ask for confirmation, writing the prompt to out and reading the answer
+/// from input
+fn ask(input: &mut dyn BufRead, out: &mut dyn Write) -> Result<bool, Box<dyn Error>> {
+    writeln!(out, "Confirm?(y/n)")?;
+
+    let mut answer = String::new();
+
+    input.read_line(&mut answer)?;
+
+    if answer.trim() == "y" {
+        return Ok(true);
+    }
+    writeln!(out, "'y' not selected, aborted!")?;
+    Ok(false)
+}
+
+/// Ask for the number of entries a change affects to be typed in to confirm
+/// it, writing the prompt to out and reading the answer from input
+fn ask_count(
+    count: usize,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<bool, Box<dyn Error>> {
+    writeln!(out, "Type {} to confirm:", count)?;
+
+    let mut answer = String::new();
+
+    input.read_line(&mut answer)?;
+
+    if answer.trim() == count.to_string() {
+        return Ok(true);
+    }
+    writeln!(out, "{} not typed, aborted!", count)?;
+    Ok(false)
+}
+
+/// Confirm a change affecting count entries, without asking if --yes was
+/// given. If the config asks for a strong confirmation the count has to be
+/// typed in, otherwise y is enough.
+fn confirm_count(
+    yes: bool,
+    count: usize,
+    config: &Config,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<bool, Box<dyn Error>> {
+    match config.confirmation(count) {
+        Confirmation::Normal => confirm(yes, || ask(input, out)),
+        Confirmation::Strong => confirm(yes, || ask_count(count, input, out)),
+    }
+}
+
+/// Confirm a change, without asking if --yes was given
+fn confirm(
+    yes: bool,
+    ask: impl FnOnce() -> Result<bool, Box<dyn Error>>,
+) -> Result<bool, Box<dyn Error>> {
+    match yes {
+        true => Ok(true),
+        false => ask(),
+    }
+}
+
+fn split_field(field: &str) -> Result<(String, String), Box<dyn Error>> {
+    // Split at the first "=", everything before will be the
+    // field ID, everything after the field value
+    let splitpoint = match field.find('=') {
+        Some(splitpoint) => splitpoint,
+        None => {
+            bail!("Invalid field! No \"=\"!");
+        }
+    };
+
+    let field_id = field[..splitpoint].to_uppercase();
+
+    let field_value = field[splitpoint + 1..].to_owned();
+
+    Ok((field_id, field_value))
+}
+
+/// Delete an entry after printing it and a warning to out. The warning is
+/// printed even if no_confirm skips the confirmation, so it shows up in logs.
+/// If trash is set the entry is moved to the trash instead. Returns whether
+/// the entry was deleted.
+fn delete_entry(
+    db: &mut Db,
+    key: u64,
+    trash: bool,
+    no_confirm: bool,
+    config: &Config,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<bool, Box<dyn Error>> {
+    let entry = db.grab_entry(key)?;
+
+    let warning = match trash {
+        true => "THE ENTRY WILL BE MOVED TO THE TRASH",
+        false => "ONCE AN ENTRY IS DELETED, IT CANNOT BE UNDONE",
+    };
+
+    writeln!(out, "{}\n\n\t{}", entry.formatted(config), warning)?;
+
+    if !no_confirm && !ask(input, out)? {
+        return Ok(false);
+    }
+
+    match trash {
+        true => db.trash_entry(key)?,
+        false => db.delete_entry(key)?,
+    }
+
+    Ok(true)
+}
+
+/// Grab the uppercased field ids given to an option that can be repeated
+fn flagged_fields(matches: &ArgMatches, id: &str) -> Vec<String> {
+    matches
+        .get_many::<String>(id)
+        .unwrap_or_default()
+        .map(|field_id| field_id.to_uppercase())
+        .collect()
+}
+
+/// Convert entries into rows of a table with the given field headers
+fn entries_to_table(headers: &[String], entries: &[Entry], config: &Config) -> Vec<Vec<String>> {
+    entries
+        .iter()
+        .map(|entry| {
+            headers
+                .iter()
+                .map(|header| match header.as_str() {
+                    "KEY" => b64::from_u64(entry.key),
+                    "LOCATION" => entry.location.clone(),
+                    "QUANTITY" => entry.quantity.to_string(),
+                    "CREATED" => config.format_timestamp(entry.created),
+                    "MODIFIED" => config.format_timestamp(entry.modified),
+                    _ => entry
+                        .fields
+                        .iter()
+                        .find(|field| &field.id == header)
+                        .map(|field| field.value.clone())
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// What import_entries does with the entries it's given
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ImportMode {
+    /// Add the entries to the database
+    Import,
+    /// Write the first few entries to out, nothing is imported
+    Preview(usize),
+    /// Check every entry against the database, nothing is imported
+    DryRun,
+}
+
+/// Import, preview or check entries read from CSV into a catagory, see
+/// ImportMode. If decimal_comma is set, commas in REAL fields are read as
+/// decimal points. Returns the number of entries imported, previewed or
+/// checked.
+fn import_entries(
+    db: &mut Db,
+    mut entries: Vec<Entry>,
+    catagory_id: &str,
+    mode: ImportMode,
+    decimal_comma: bool,
+    config: &Config,
+    out: &mut dyn Write,
+) -> Result<usize, Box<dyn Error>> {
+    if decimal_comma {
+        let real_fields = db.fields_of_type(catagory_id, DataType::REAL)?;
+
+        csv::decimal_commas_to_dots(&mut entries, &real_fields);
+    }
+
+    match mode {
+        ImportMode::Import => db.add_entries(entries),
+        ImportMode::Preview(preview) => {
+            let entries = &entries[..preview.min(entries.len())];
+
+            for entry in entries {
+                writeln!(out, "{}\n\n", entry.formatted(config))?;
+            }
+
+            Ok(entries.len())
+        }
+        ImportMode::DryRun => db.check_entries(entries),
+    }
+}
+
+/// Check entries about to be imported for repeated keys if --dedupe-keys was
+/// given, keeping the last entry with a key if keep_last is set and failing
+/// otherwise. See Db::dedupe_keys.
+fn dedupe_keys(entries: Vec<Entry>, dedupe: Option<bool>) -> Result<Vec<Entry>, Box<dyn Error>> {
+    match dedupe {
+        Some(keep_last) => Db::dedupe_keys(entries, keep_last),
+        None => Ok(entries),
+    }
+}
+
+/// Make a catagory read from CSV data by csv_to_catagory and import the
+/// entries read with it, in one transaction so the catagory isn't kept if the
+/// entries can't be imported. If decimal_comma is set, commas in REAL fields are read as decimal
+/// points. Returns the id of the catagory and the number of entries imported.
+fn import_new_catagory(
+    db: &mut Db,
+    catagory: Catagory,
+    mut entries: Vec<Entry>,
+    decimal_comma: bool,
+) -> Result<(String, usize), Box<dyn Error>> {
+    if decimal_comma {
+        let real_fields: Vec<String> = catagory
+            .fields
+            .iter()
+            .filter(|field| field.datatype == DataType::REAL)
+            .map(|field| field.id.clone())
+            .collect();
+
+        csv::decimal_commas_to_dots(&mut entries, &real_fields);
+    }
+
+    let catagory_id = catagory.id.clone();
+    let count = db.add_catagory_with_entries(catagory, entries)?;
+
+    Ok((catagory_id, count))
+}
+
+/// Format a value as currency, e.g. 1234.5 becomes "$1,234.50"
+fn format_currency(value: f64) -> String {
+    let digits = format!("{:.2}", value.abs());
+    let (whole, fraction) = digits.split_at(digits.len() - 3);
+
+    // Group the whole part of the number by thousands
+    let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+
+        grouped.push(digit);
+    }
+
+    let sign = match value < 0.0 {
+        true => "-",
+        false => "",
+    };
+
+    format!("{}${}{}", sign, grouped, fraction)
+}
+
+/// Build the command line interface. Probably going to redo this in the near
+/// future, but it sorta works for now
+pub fn command() -> Command<'static> {
+    // To be re-written...
+    command!()
+        .propagate_version(true)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .args([
+            arg!(-d --database <PATH> "Database file to use instead of the default one, made if it doesn't exist")
+                .required(false)
+                .global(true),
+            arg!(--demo "Fill an empty database with sample catagories and entries to explore"),
+            arg!(-y --yes "Don't ask for confirmation, for scripts").global(true),
+        ])
+        .subcommand(
+            // TUI Subcommand
+            Command::new("tui").about("Enter TUI mode"),
+        )
+        .subcommand(
+            // Add subcommand
+            Command::new("add")
+                .about("Add an entry to a catagory.")
+                .args(&[
+                    arg!(-c --catagory <CATAGORY> "The catagory to add the entry to.")
+                        .required(true),
+                    arg!(-k --key <KEY> "The key of the entry to add.").required(true),
+                    arg!(-l --location <LOCATION> "The physical location of the entry.")
+                        .required(true),
+                    arg!(-q --quantity <QUANTITY> "The quantity of the entry.")
+                        .required(true)
+                        .value_parser(value_parser!(u64)),
+                    arg!([FIELD] ... "A field to apply to the entry.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Add catagory subcommand
+            Command::new("add_catagory")
+                .about("Add a new catagory.")
+                .args(&[
+                    arg!(-c --catagory <CATAGORY> "The name of the catagory.").required(true),
+                    arg!(-u --unique <FIELD> "A field no two entries may share a value in.")
+                        .required(false)
+                        .multiple_occurrences(true),
+                    arg!(-i --immutable <FIELD> "A field that can't be changed once it's set.")
+                        .required(false)
+                        .multiple_occurrences(true),
+                    arg!([FIELD] ... "A field to apply to the catagory.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Field order subcommand
+            Command::new("field_order")
+                .about("Show or set the order the fields of a catagory are displayed in.")
+                .args(&[
+                    arg!([CATAGORY] "The catagory to order the fields of.").required(true),
+                    arg!([FIELDS] ... "The fields to show first, in order."),
+                    arg!(--reset "Go back to the order the fields were added in."),
+                ]),
+        )
+        .subcommand(
+            // Find subcommand
+            Command::new("find")
+                .about("Find an entry given a key.")
+                .args(&[
+                    arg!([KEY] "The key of the entry to look up.").required(true),
+                    arg!(-c --"assume-catagory" <CATAGORY> "The catagory the entry is probably in, checked first.")
+                        .required(false),
+                ]),
+        )
+        .subcommand(
+            // Search subcommand
+            Command::new("search")
+                .about("Search every catagory for entries with the given field values.")
+                .args(&[arg!([FIELD] ... "A field value to match, like location=bazville.")
+                    .required(true)]),
+        )
+        .subcommand(
+            // Delete subcommand
+            Command::new("delete")
+                .about("Delete an entry given a key.")
+                .args(&[
+                    arg!([KEY] "The key of the entry to delete.").required(true),
+                    arg!(-t --trash "Move the entry to the trash so it can be restored later."),
+                    arg!(--"no-confirm-delete" "Delete without asking for confirmation."),
+                ]),
+        )
+        .subcommand(
+            // Untrash subcommand
+            Command::new("untrash")
+                .about("Restore an entry from the trash given a key.")
+                .args(&[arg!([KEY] "The key of the entry to restore.").required(true)]),
+        )
+        .subcommand(
+            // Empty trash subcommand
+            Command::new("empty_trash")
+                .alias("empty-trash")
+                .about("Permanently delete every entry in the trash."),
+        )
+        .subcommand(
+            // Backup subcommand
+            Command::new("backup")
+                .about("Copy the whole database into a single file.")
+                .args(&[arg!([OUT] "File to write the backup to.").required(true)]),
+        )
+        .subcommand(
+            // Restore subcommand
+            Command::new("restore")
+                .about("Replace everything in the database with a backup.")
+                .args(&[arg!([IN] "Backup file to restore from.").required(true)]),
+        )
+        .subcommand(
+            // Doctor subcommand
+            Command::new("doctor")
+                .about("Check the database for keys left behind by dropped catagories.")
+                .args(&[arg!(--fix "Remove the keys that were found.")]),
+        )
+        .subcommand(
+            // Rekey subcommand
+            Command::new("rekey")
+                .about("Change the key of an entry.")
+                .args(&[
+                    arg!([OLD_KEY] "The current key of the entry.").required(true),
+                    arg!([NEW_KEY] "The key to move the entry to.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Move subcommand
+            Command::new("move")
+                .about("Move an entry to another catagory, keeping its key.")
+                .args(&[
+                    arg!([KEY] "The key of the entry to move.").required(true),
+                    arg!([CATAGORY] "The catagory to move the entry to.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Rename field subcommand
+            Command::new("rename_field")
+                .about("Rename a field in a catagory, keeping its values.")
+                .args(&[
+                    arg!(-c --catagory <CATAGORY> "The catagory the field is in.").required(true),
+                    arg!([OLD_FIELD] "The current name of the field.").required(true),
+                    arg!([NEW_FIELD] "The new name of the field.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Give subcommand
+            Command::new("give")
+                .about("Add to the quantity of an entry.")
+                .args(&[
+                    arg!(-k --key <KEY> "The key of the entry to give to."),
+                    arg!([QUANTITY] "The quantity to add to the entry.")
+                        .required(true)
+                        .value_parser(value_parser!(u64)),
+                ]),
+        )
+        .subcommand(
+            // Take subcommand
+            Command::new("take")
+                .about("Take from the quantity of an entry.")
+                .args(&[
+                    arg!(-k --key <KEY> "The key of the entry to take from."),
+                    arg!([QUANTITY] "The quantity to take from the entry.")
+                        .required(true)
+                        .value_parser(value_parser!(u64)),
+                ]),
+        )
+        .subcommand(
+            // Add subcommand
+            Command::new("modify")
+                .about("Modify an entry given a key")
+                .args(&[
+                    arg!(-k --key <KEY> "The key of the entry to modify.").required(true),
+                    arg!([FIELD] ... "A field to modify in the entry.").required(true),
+                ]),
+        )
+        .subcommand(
+            // List command
+            Command::new("list")
+                .about("Lists the contents of a catagory.")
+                .args(&[
+                    arg!(-c --catagory <CATAOGRY> "The catagory to list the contents of.")
+                        .required(true),
+                    arg!(-s --sort <FIELD> "Field to sort the entries by.").required(false),
+                    arg!(-r --reverse "Sort from largest to smallest."),
+                    arg!(-t --table "List the entries as a table, one per line."),
+                    arg!(--"max-width" <WIDTH> "Width to fit the table in, defaults to the terminal's.")
+                        .required(false)
+                        .value_parser(value_parser!(usize)),
+                ]),
+        )
+        .subcommand(
+            // History command
+            Command::new("history")
+                .about("Show the most recent changes made to entries, newest first.")
+                .args(&[arg!(-l --limit <N> "Most changes to show.")
+                    .required(false)
+                    .value_parser(value_parser!(u64))]),
+        )
+        .subcommand(
+            // List command
+            Command::new("list_catagories")
+                .about("Lists all catagories.")
+                .args(&[arg!(-r --reverse "List from Z to A.")]),
+        )
+        .subcommand(
+            // Locations command
+            Command::new("locations")
+                .about("Show the number of entries and total quantity at each location."),
+        )
+        .subcommand(
+            // Low stock command
+            Command::new("low_stock")
+                .alias("low-stock")
+                .about("List the entries in every catagory that are running out.")
+                .args(&[arg!(-t --threshold <N> "Highest quantity counted as running out.")
+                    .required(true)
+                    .value_parser(value_parser!(u64))]),
+        )
+        .subcommand(
+            // Stats command
+            Command::new("stats")
+                .about("Show the number of entries and total quantity of each catagory.")
+                .args(&[arg!(--json "Print the stats as JSON.")]),
+        )
+        .subcommand(
+            // Value command
+            Command::new("value").about("Show the total value of catagories with a PRICE field."),
+        )
+        .subcommand(
+            // Export command
+            Command::new("export")
+                .about("Export the contents of a catagory.")
+                .args(&[
+                    arg!(-c --catagory <CATAGORY> "The catagory to export.").required(true),
+                    arg!(-t --template <TEMPLATE> "Format to export in.")
+                        .required(true)
+                        .alias("format")
+                        .value_parser(["csv", "csv-header", "json"]),
+                    arg!([OUT] "File to write to, prints to stdout if not given.").required(false),
+                ]),
+        )
+        .subcommand(
+            // Export entry command
+            Command::new("export_entry")
+                .alias("export-entry")
+                .about("Export a single entry as JSON, along with its catagory.")
+                .args(&[
+                    arg!([KEY] "The key of the entry to export.").required(true),
+                    arg!([OUT] "File to write to, prints to stdout if not given.").required(false),
+                ]),
+        )
+        .subcommand(
+            // Import command
+            Command::new("import")
+                .about("Import entries into a catagory from a CSV file with a header, or from JSON.")
+                .args(&[
+                    arg!(-c --catagory <CATAGORY> "The catagory to import into, not needed for JSON.")
+                        .required(false)
+                        .required_unless_present_any(["format", "new-catagory"])
+                        .conflicts_with("new-catagory"),
+                    arg!(-n --"new-catagory" "Make the catagory named on the first row of the CSV, with typed headers like OHMS:r."),
+                    arg!(-f --format <FORMAT> "Format to import from, defaults to csv.")
+                        .required(false)
+                        .value_parser(["csv", "json"]),
+                    arg!(--preview <N> "Print the first N entries without importing anything.")
+                        .required(false)
+                        .value_parser(value_parser!(usize)),
+                    arg!(--"dry-run" "Check every entry could be imported without importing anything."),
+                    arg!(--"decimal-comma" "Read commas in REAL fields as decimal points, like 1,2."),
+                    arg!(--delimiter <CHAR> "Character separating values in the CSV, a comma by default.")
+                        .required(false)
+                        .value_parser(value_parser!(char)),
+                    arg!(--"no-header" "The CSV has no header row, and its columns are in the order export writes them."),
+                    arg!(--"no-quotes" "Read quotes in the CSV as part of the values."),
+                    arg!(--"dedupe-keys" <MODE> "Check the file for repeated keys first, and either fail or keep the last entry with each key.")
+                        .required(false)
+                        .value_parser(["error", "keep-last"]),
+                    arg!([IN] "File to read from.").required(true),
+                ]),
+        )
+        .subcommand(
+            // Import entry command
+            Command::new("import_entry")
+                .alias("import-entry")
+                .about("Import a single entry exported with export_entry, making its catagory if needed.")
+                .args(&[arg!([IN] "File to read from.").required(true)]),
+        )
+        .subcommand(
+            // Fill template command
+            Command::new("fill_template")
+                .about("Fill out an svg template with the currently unused keys.")
+                .args(&[
+                    arg!([OUT] "File to write to, will be an SVG no matter what suffix unless --pdf is given.")
+                        .required(true),
+                    arg!(--pdf "Write a PDF the size of the sheet instead of an SVG."),
+                    arg!(-b --builtin <BUILTIN> "Use a builtin template.").required(false),
+                    arg!(-i --infile <IN> "GZ-SVG template to read and fill out.").required(false),
+                ]),
+        )
+        .subcommand(
+            // Add template command
+            Command::new("add_template")
+                .alias("add-template")
+                .about("Compress an SVG template into the template directory so it can be filled.")
+                .args(&[
+                    arg!(--id <ID> "Name to give the template.").required(true),
+                    arg!([IN] "SVG template to add.").required(true),
+                ]),
+        )
+        .subcommand(
+            // List templates command
+            Command::new("list_templates")
+                .alias("list-templates")
+                .about("List the builtin label templates and the ones in the template directory"),
+        )
+        .subcommand(
+            // List builtin templates command
+            Command::new("list_builtin_templates").about("List all builtin label templates"),
+        )
+}
+
+/// Run a subcommand other than tui, writing what it prints to out and reading
+/// answers to confirmations from input
+pub fn run(
+    db: &mut Db,
+    matches: &ArgMatches,
+    config: &Config,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    let yes = matches.contains_id("yes");
+
+    match matches.subcommand() {
+        // Add Subcommand
+        Some(("add", matches)) => {
+            let catagory: String = matches.get_one::<String>("catagory").unwrap().clone();
+            let key: String = matches.get_one::<String>("key").unwrap().clone();
+            let location: String = matches.get_one::<String>("location").unwrap().clone();
+            let quantity: u64 = *matches.get_one::<u64>("quantity").unwrap();
+
+            let fields: Vec<String> = matches
+                .get_many::<String>("FIELD")
+                .unwrap()
+                .cloned()
+                .collect();
+
+            let mut entry_fields: Vec<EntryField> = Vec::new();
+            // Parse all the fields
+            for field in fields {
+                let (field_id, field_value) = split_field(&field)?;
+
+                let entry_field = EntryField::new(&field_id, &field_value);
+
+                entry_fields.push(entry_field);
+            }
+
+            // Convert the key from base64 to u64
+            let key = b64::to_u64(&key)?;
+
+            // Create the created/modified timestamp
+            let created = Local::now().timestamp();
+            let modified = created;
+
+            let mut entry = Entry::new(&catagory, key, &location, quantity, created, modified);
+            entry.add_fields(&entry_fields);
+
+            writeln!(out, "{}", entry.formatted(config))?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            db.read_blob_files(&entry.catagory_id, &mut entry.fields)?;
+
+            db.add_entry(entry)?;
+        }
+        // Add catagory subcommand
+        Some(("add_catagory", matches)) => {
+            let catagory_id: String = matches.get_one::<String>("catagory").unwrap().clone();
+
+            let fields: Vec<String> = matches
+                .get_many::<String>("FIELD")
+                .unwrap()
+                .cloned()
+                .collect();
+
+            let unique = flagged_fields(matches, "unique");
+            let immutable = flagged_fields(matches, "immutable");
+
+            let mut catagory_fields: Vec<CatagoryField> = Vec::new();
+            // Parse all the fields
+            for field in fields {
+                let (field_id, field_value) = split_field(&field)?;
+
+                if field_value.len() != 1 {
+                    bail!(
+                        "Catagory field is supposed to be one character, not {}!",
+                        field_value
+                    );
+                }
+                // Get the type
+                let field_value = DataType::from_char(field_value.chars().next().unwrap())?;
+
+                let mut catagory_field = CatagoryField::new(&field_id, field_value);
+
+                if unique.contains(&field_id.to_uppercase()) {
+                    catagory_field = catagory_field.unique();
+                }
+
+                if immutable.contains(&field_id.to_uppercase()) {
+                    catagory_field = catagory_field.immutable();
+                }
+
+                catagory_fields.push(catagory_field);
+            }
+
+            for (kind, field_ids) in [("Unique", &unique), ("Immutable", &immutable)] {
+                for field_id in field_ids {
+                    if !catagory_fields
+                        .iter()
+                        .any(|field| field.id.to_uppercase() == *field_id)
+                    {
+                        bail!(
+                            "{} field {} isn't one of the catagory's fields!",
+                            kind,
+                            field_id
+                        );
+                    }
+                }
+            }
+
+            let catagory = Catagory::with_fields(&catagory_id, catagory_fields);
+
+            writeln!(out, "{}", catagory)?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            db.add_catagory(catagory)?;
+        }
+        // Field order subcommand
+        Some(("field_order", matches)) => {
+            let catagory_id = matches
+                .get_one::<String>("CATAGORY")
+                .unwrap()
+                .to_uppercase();
+
+            let fields: Vec<String> = matches
+                .get_many::<String>("FIELDS")
+                .unwrap_or_default()
+                .map(|field| field.to_uppercase())
+                .collect();
+
+            if matches.contains_id("reset") {
+                db.set_display_order(&catagory_id, &[])?;
+            } else if !fields.is_empty() {
+                db.set_display_order(&catagory_id, &fields)?;
+            }
+
+            writeln!(out, "{}", db.display_order(&catagory_id)?.join(" "))?;
+        }
+        // Find subcommand
+        Some(("find", matches)) => {
+            let key: String = matches.get_one::<String>("KEY").unwrap().clone();
+
+            // Convert the key from b64 to u64
+            let key = b64::parse_key(&key)?;
+
+            let entry = match matches.get_one::<String>("assume-catagory") {
+                Some(catagory_id) => db.grab_entry_in(key, &catagory_id.to_uppercase())?,
+                None => db.grab_entry(key)?,
+            };
+
+            writeln!(out, "{}", entry.formatted(config))?;
+        }
+        // Search subcommand
+        Some(("search", matches)) => {
+            let mut conditions = Vec::<Condition>::new();
+
+            for field in matches.get_many::<String>("FIELD").unwrap() {
+                let (field_id, field_value) = split_field(field)?;
+
+                conditions.push(Condition::new(
+                    &field_id,
+                    ConditionOperator::Equal,
+                    &field_value,
+                ));
+            }
+
+            for entry in db.search_all(&conditions)? {
+                writeln!(out, "{}\n\n", entry.formatted(config))?;
+            }
+        }
+        // Delete subcommand
+        Some(("delete", matches)) => {
+            let key: String = matches.get_one::<String>("KEY").unwrap().clone();
+
+            let trash = matches.contains_id("trash");
+            let no_confirm = matches.contains_id("no-confirm-delete");
+
+            // Convert the key from b64 to u64
+            let key = b64::parse_key(&key)?;
+
+            delete_entry(db, key, trash, no_confirm || yes, config, input, out)?;
+        }
+        // Untrash subcommand
+        Some(("untrash", matches)) => {
+            let key = b64::to_u64(matches.get_one::<String>("KEY").unwrap())?;
+
+            db.restore_trashed(key)?;
+
+            writeln!(out, "Restored entry {}", b64::from_u64(key))?;
+        }
+        // Empty trash subcommand
+        Some(("empty_trash", _)) => {
+            let count = db.empty_trash()?;
+
+            writeln!(out, "Deleted {} entries from the trash", count)?;
+        }
+        // Backup subcommand
+        Some(("backup", matches)) => {
+            let out_name = matches.get_one::<String>("OUT").unwrap();
+
+            db.backup_to(Path::new(out_name))?;
+        }
+        // Restore subcommand
+        Some(("restore", matches)) => {
+            let in_name = matches.get_one::<String>("IN").unwrap();
+
+            writeln!(
+                out,
+                "Restore from {}?\n\n\tEVERYTHING CURRENTLY IN THE DATABASE WILL BE REPLACED",
+                in_name
+            )?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            db.restore_from(Path::new(in_name))?;
+        }
+        // Doctor subcommand
+        Some(("doctor", matches)) => {
+            let orphans = match matches.contains_id("fix") {
+                true => db.remove_orphans()?,
+                false => db.find_orphans()?,
+            };
+
+            if orphans.is_empty() {
+                writeln!(out, "No problems found!")?;
+                return Ok(());
+            }
+
+            for (key, catagory) in &orphans {
+                writeln!(
+                    out,
+                    "Key {} points to missing catagory {}",
+                    b64::from_u64(*key),
+                    catagory
+                )?;
+            }
+
+            match matches.contains_id("fix") {
+                true => writeln!(out, "Removed {} orphaned keys", orphans.len())?,
+                false => writeln!(out, "Run with --fix to remove them")?,
+            }
+        }
+        // Rekey subcommand
+        Some(("rekey", matches)) => {
+            let old_key: String = matches.get_one::<String>("OLD_KEY").unwrap().clone();
+            let new_key: String = matches.get_one::<String>("NEW_KEY").unwrap().clone();
+
+            // Convert the keys from b64 to u64
+            let old_key = b64::to_u64(&old_key)?;
+            let new_key = b64::to_u64(&new_key)?;
+
+            let entry = db.grab_entry(old_key)?;
+
+            writeln!(
+                out,
+                "{}\n\nNew key: {}",
+                entry.formatted(config),
+                b64::from_u64(new_key)
+            )?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            db.rekey_entry(old_key, new_key)?;
+        }
+        // Move subcommand
+        Some(("move", matches)) => {
+            let key = b64::to_u64(matches.get_one::<String>("KEY").unwrap())?;
+            let catagory_id = matches
+                .get_one::<String>("CATAGORY")
+                .unwrap()
+                .to_uppercase();
+
+            let entry = db.grab_entry(key)?;
+
+            writeln!(
+                out,
+                "{}\n\nNew catagory: {}",
+                entry.formatted(config),
+                catagory_id
+            )?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            let dropped = db.move_entry(key, &catagory_id)?;
+
+            if !dropped.is_empty() {
+                writeln!(
+                    out,
+                    "Warning: {} doesn't have these fields, so they were left behind: {}",
+                    catagory_id,
+                    dropped.join(", ")
+                )?;
+            }
+        }
+        // Rename field subcommand
+        Some(("rename_field", matches)) => {
+            let catagory_id = matches
+                .get_one::<String>("catagory")
+                .unwrap()
+                .to_uppercase();
+            let old_field = matches
+                .get_one::<String>("OLD_FIELD")
+                .unwrap()
+                .to_uppercase();
+            let new_field = matches
+                .get_one::<String>("NEW_FIELD")
+                .unwrap()
+                .to_uppercase();
+
+            db.rename_catagory_field(&catagory_id, &old_field, &new_field)?;
+        }
+        // Give subcommand
+        Some(("give", matches)) => {
+            let key: String = matches.get_one::<String>("key").unwrap().clone();
+            let quantity: u64 = *matches.get_one::<u64>("QUANTITY").unwrap();
+
+            // Convert the key from b64 to u64
+            let key = b64::to_u64(&key)?;
+
+            let entry = db.grab_entry(key)?;
+
+            let new_quantity = entry.quantity + quantity;
+            writeln!(out, "{}", entry.formatted(config))?;
+
+            writeln!(out, "New quantity: {}", new_quantity)?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            // Convert the new quantity to an entry field and submit...
+            let field = EntryField::new("QUANTITY", &new_quantity.to_string());
+
+            db.mod_entry(key, vec![field])?;
+        }
+        // Take subcommand
+        Some(("take", matches)) => {
+            let key: String = matches.get_one::<String>("key").unwrap().clone();
+            let quantity: u64 = *matches.get_one::<u64>("QUANTITY").unwrap();
+
+            // Convert the key from b64 to u64
+            let key = b64::to_u64(&key)?;
+
+            let entry = db.grab_entry(key)?;
+
+            let new_quantity = match entry.quantity > quantity {
+                true => entry.quantity - quantity,
+                false => 0,
+            };
+
+            writeln!(out, "{}", entry.formatted(config))?;
+
+            writeln!(out, "New quantity: {}", new_quantity)?;
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            // Convert the new quantity to an entry field and submit...
+            let field = EntryField::new("QUANTITY", &new_quantity.to_string());
+
+            db.mod_entry(key, vec![field])?;
+        }
+        // Modify subcommand
+        Some(("modify", matches)) => {
+            let key: String = matches.get_one::<String>("key").unwrap().clone();
+            let fields: Vec<String> = matches
+                .get_many::<String>("FIELD")
+                .unwrap()
+                .cloned()
+                .collect();
+
+            // Convert the key from base64 to u64
+            let key = b64::to_u64(&key)?;
+
+            let mut entry_fields: Vec<EntryField> = Vec::new();
+            // Parse all the fields
+            for field in fields {
+                let (field_id, field_value) = split_field(&field)?;
+
+                let entry_field = EntryField::new(&field_id, &field_value);
+
+                entry_fields.push(entry_field);
+            }
+
+            // Grab the entry (to display)
+            let entry = db.grab_entry(key)?;
+
+            writeln!(
+                out,
+                "Old Entry:\n\n{}\n\nModified Fields:\n\n",
+                entry.formatted(config)
+            )?;
+            // Get the fields that have been modified
+            for field in &entry_fields {
+                // Make sure the field isn't one of the hard-coded fields
+                match field.id.as_str() {
+                    "KEY" => writeln!(
+                        out,
+                        "\tKEY: {} -> {}",
+                        b64::from_u64(entry.key),
+                        field.value
+                    )?,
+                    "LOCATION" => {
+                        writeln!(out, "\tLOCATION: {} -> {}", entry.location, field.value)?
+                    }
+                    "QUANTITY" => {
+                        writeln!(out, "\tQUANTITY: {} -> {}", entry.quantity, field.value)?
+                    }
+                    "CREATED" | "MODIFIED" => {
+                        bail!("Cannot alter the time of creation or modification!")
+                    }
+                    _ => {
+                        // Get the old field
+                        let old_field = match entry
+                            .fields
+                            .iter()
+                            .find(|old_field| old_field.id == field.id)
+                        {
+                            Some(old_field) => old_field,
+                            None => bail!(
+                                "No such field {} in catagory {}!",
+                                field.id,
+                                entry.catagory_id
+                            ),
+                        };
+
+                        writeln!(
+                            out,
+                            "\t{}: {} -> {}",
+                            old_field.id, old_field.value, field.value
+                        )?;
+                    }
+                };
+            }
+
+            match confirm(yes, || ask(input, out))? {
+                true => {}
+                false => {
+                    return Ok(());
+                }
+            }
+
+            db.read_blob_files(&entry.catagory_id, &mut entry_fields)?;
+
+            db.mod_entry(key, entry_fields)?;
+        }
+        // List subcommand
+        // !TODO! Make more useful
+        Some(("list", matches)) => {
+            let catagory_id = matches
+                .get_one::<String>("catagory")
+                .unwrap()
+                .to_uppercase();
+
+            let order = match matches.contains_id("reverse") {
+                true => SortOrder::Descending,
+                false => SortOrder::Ascending,
+            };
+
+            let sort = matches
+                .get_one::<String>("sort")
+                .map(|field_id| Sort::new(field_id, order));
+
+            let entries = db.search_catagory_sorted(&catagory_id, &[], None, sort.as_ref())?;
+
+            if matches.contains_id("table") {
+                let max_width = matches
+                    .get_one::<usize>("max-width")
+                    .copied()
+                    .or_else(table::terminal_width);
+
+                let headers = db.display_fields(&catagory_id)?;
+                let entry_table = entries_to_table(&headers, &entries, config);
+
+                for row in table::columnator(headers, entry_table, max_width) {
+                    writeln!(out, "{}", row)?;
+                }
+
+                return Ok(());
+            }
+
+            for entry in entries {
+                writeln!(out, "{}\n\n", entry.formatted(config))?;
+            }
+        }
+        // History subcommand
+        Some(("history", matches)) => {
+            let limit = matches.get_one::<u64>("limit").copied();
+
+            for record in db.history(limit)? {
+                writeln!(out, "{}", record)?;
+            }
+        }
+        // List catagories subcommand
+        // !TODO! Make more useful
+        Some(("list_catagories", matches)) => {
+            let order = match matches.contains_id("reverse") {
+                true => SortOrder::Descending,
+                false => SortOrder::Ascending,
+            };
+
+            let catagories = db.list_catagories_sorted(order)?;
+
+            for catagory in catagories {
+                writeln!(out, "{}", catagory)?;
+            }
+        }
+        // Low stock subcommand
+        Some(("low_stock", matches)) => {
+            let threshold = *matches.get_one::<u64>("threshold").unwrap();
+
+            let headers = ["CATAGORY", "KEY", "LOCATION", "QUANTITY"]
+                .iter()
+                .map(|header| header.to_string())
+                .collect();
+
+            let low_table = db
+                .low_stock(threshold)?
+                .into_iter()
+                .map(|entry| {
+                    vec![
+                        entry.catagory_id,
+                        b64::from_u64(entry.key),
+                        entry.location,
+                        entry.quantity.to_string(),
+                    ]
+                })
+                .collect();
+
+            for row in table::columnator(headers, low_table, table::terminal_width()) {
+                writeln!(out, "{}", row)?;
+            }
+        }
+        // Locations subcommand
+        Some(("locations", _)) => {
+            let headers = ["LOCATION", "ENTRIES", "QUANTITY"]
+                .iter()
+                .map(|header| header.to_string())
+                .collect();
+
+            let location_table = db
+                .count_by_location()?
+                .into_iter()
+                .map(|(location, entries, quantity)| {
+                    vec![location, entries.to_string(), quantity.to_string()]
+                })
+                .collect();
+
+            for row in table::columnator(headers, location_table, table::terminal_width()) {
+                writeln!(out, "{}", row)?;
+            }
+        }
+        // Stats subcommand
+        Some(("stats", matches)) => {
+            let stats = db.catagory_stats()?;
+
+            if matches.contains_id("json") {
+                writeln!(out, "{}", serde_json::to_string_pretty(&stats)?)?;
+                return Ok(());
+            }
+
+            for stats in stats {
+                writeln!(
+                    out,
+                    "{}: {} entries, {} total quantity",
+                    stats.catagory, stats.entries, stats.total_quantity
+                )?;
+            }
+        }
+        // Value subcommand
+        Some(("value", _)) => {
+            let values = db.total_value_all()?;
+
+            let mut total: f64 = 0.0;
+
+            for (catagory, value) in values {
+                match value {
+                    Some(value) => {
+                        writeln!(out, "{}: {}", catagory, format_currency(value))?;
+
+                        total += value;
+                    }
+                    None => writeln!(out, "{}: unpriced", catagory)?,
+                }
+            }
+
+            writeln!(out, "TOTAL: {}", format_currency(total))?;
+        }
+        // Export subcommand
+        Some(("export", matches)) => {
+            let catagory_id = matches
+                .get_one::<String>("catagory")
+                .unwrap()
+                .to_uppercase();
+
+            let exported = match matches.get_one::<String>("template").unwrap().as_str() {
+                "csv" | "csv-header" => db.export_catagory_csv(&catagory_id)?,
+                "json" => db.export_catagory_json(&catagory_id)?,
+                _ => unreachable!("clap only allows known templates"),
+            };
+
+            match matches.get_one::<String>("OUT") {
+                Some(out_name) => fs::write(out_name, exported)?,
+                None => write!(out, "{}", exported)?,
+            }
+        }
+        // Export entry subcommand
+        Some(("export_entry", matches)) => {
+            let key = b64::to_u64(matches.get_one::<String>("KEY").unwrap())?;
+
+            let exported = db.export_entry(key)?;
+
+            match matches.get_one::<String>("OUT") {
+                Some(out_name) => fs::write(out_name, exported)?,
+                None => writeln!(out, "{}", exported)?,
+            }
+        }
+        // Import subcommand
+        Some(("import", matches)) => {
+            let preview = matches.get_one::<usize>("preview").copied();
+            let dry_run = matches.contains_id("dry-run");
+            let decimal_comma = matches.contains_id("decimal-comma");
+            let new_catagory = matches.contains_id("new-catagory");
+            let delimiter = matches.get_one::<char>("delimiter").copied();
+            let no_header = matches.contains_id("no-header");
+            let no_quotes = matches.contains_id("no-quotes");
+            let dedupe = matches
+                .get_one::<String>("dedupe-keys")
+                .map(|mode| mode == "keep-last");
+
+            let data = fs::read_to_string(matches.get_one::<String>("IN").unwrap())?;
+
+            // JSON entries name their own catagories
+            if matches.get_one::<String>("format").map(String::as_str) == Some("json") {
+                if preview.is_some()
+                    || dry_run
+                    || decimal_comma
+                    || new_catagory
+                    || delimiter.is_some()
+                    || no_header
+                    || no_quotes
+                {
+                    bail!("--preview, --dry-run, --decimal-comma, --new-catagory, --delimiter, --no-header and --no-quotes only work when importing CSV!");
+                }
+
+                let entries = dedupe_keys(Db::json_to_entries(&data)?, dedupe)?;
+
+                let mut catagories: Vec<&str> = entries
+                    .iter()
+                    .map(|entry| entry.catagory_id.as_str())
+                    .collect();
+
+                catagories.sort_unstable();
+                catagories.dedup();
+
+                writeln!(
+                    out,
+                    "Import {} entries into {}?",
+                    entries.len(),
+                    catagories.join(", ")
+                )?;
+
+                if !confirm_count(yes, entries.len(), config, input, out)? {
+                    return Ok(());
+                }
+
+                let count = db.add_entries(entries)?;
+
+                writeln!(out, "Imported {} entries", count)?;
+
+                return Ok(());
+            }
+
+            if new_catagory {
+                if preview.is_some() || dry_run || no_header || no_quotes {
+                    bail!("--preview, --dry-run, --no-header and --no-quotes don't work with --new-catagory!");
+                }
+
+                let (catagory, entries) = csv::csv_to_catagory(&data, delimiter.unwrap_or(','))?;
+                let entries = dedupe_keys(entries, dedupe)?;
+
+                writeln!(
+                    out,
+                    "Import {} entries into new catagory {}?",
+                    entries.len(),
+                    catagory.id
+                )?;
+
+                if !confirm_count(yes, entries.len(), config, input, out)? {
+                    return Ok(());
+                }
+
+                let (catagory_id, count) =
+                    import_new_catagory(db, catagory, entries, decimal_comma)?;
+
+                writeln!(
+                    out,
+                    "Made catagory {} and imported {} entries",
+                    catagory_id, count
+                )?;
+
+                return Ok(());
+            }
+
+            let catagory_id = match matches.get_one::<String>("catagory") {
+                Some(catagory_id) => catagory_id.to_uppercase(),
+                None => {
+                    bail!("A catagory to import into is needed for CSV!");
+                }
+            };
+
+            let options = csv::CsvOptions {
+                delimiter: delimiter.unwrap_or(','),
+                has_headers: !no_header,
+                quoting: !no_quotes,
+            };
+
+            let columns = if no_header {
+                db.display_fields(&catagory_id)?
+            } else {
+                Vec::new()
+            };
+
+            let entries = csv::csv_to_entries_with(&data, &catagory_id, &options, &columns)?;
+            let entries = dedupe_keys(entries, dedupe)?;
+
+            let mode = match (preview, dry_run) {
+                (Some(preview), _) => ImportMode::Preview(preview),
+                (None, true) => ImportMode::DryRun,
+                (None, false) => ImportMode::Import,
+            };
+
+            // Previews and dry runs don't change anything
+            if mode == ImportMode::Import {
+                writeln!(
+                    out,
+                    "Import {} entries into {}?",
+                    entries.len(),
+                    catagory_id
+                )?;
+
+                if !confirm_count(yes, entries.len(), config, input, out)? {
+                    return Ok(());
+                }
+            }
+
+            let count =
+                import_entries(db, entries, &catagory_id, mode, decimal_comma, config, out)?;
+
+            match mode {
+                ImportMode::Import => writeln!(out, "Imported {} entries", count)?,
+                ImportMode::Preview(_) => {}
+                ImportMode::DryRun => writeln!(out, "Would import {} entries", count)?,
+            }
+        }
+        // Import entry subcommand
+        Some(("import_entry", matches)) => {
+            let data = fs::read_to_string(matches.get_one::<String>("IN").unwrap())?;
+
+            let key = db.import_entry(&data)?;
+
+            writeln!(out, "Imported entry {}", b64::from_u64(key))?;
+        }
+        // Fill template subcommand
+        Some(("fill_template", matches)) => {
+            let template_data: Vec<u8> = match matches.get_one::<String>("builtin") {
+                Some(template_id) => templates::TEMPLATES
+                    .iter()
+                    .find(|template| template.id == template_id)
+                    .expect("Template not found!")
+                    .get_data(),
+                None => {
+                    let filename = matches
+                        .get_one::<String>("infile")
+                        .expect("Need a template specified with -i or -b!");
+                    let filedata = fs::read(filename)?;
+
+                    templates::decompress(&filedata)?
+                }
+            };
+
+            let template_string = String::from_utf8_lossy(&template_data);
+
+            let filled_template = db.fill_svg_template(&template_string)?;
+
+            let out_name = matches.get_one::<String>("OUT").unwrap();
+
+            match matches.contains_id("pdf") {
+                true => fs::write(out_name, templates::svg_to_pdf(&filled_template)?)?,
+                false => fs::write(out_name, filled_template)?,
+            }
+        }
+        // Add template subcommand
+        Some(("add_template", matches)) => {
+            let id = matches.get_one::<String>("id").unwrap();
+            let data = fs::read(matches.get_one::<String>("IN").unwrap())?;
+
+            let path = templates::add_template(&templates::template_dir()?, id, &data)?;
+
+            writeln!(out, "Added template {}", path.display())?;
+        }
+        // List templates subcommand
+        Some(("list_templates", _)) => {
+            for template in &templates::TEMPLATES {
+                writeln!(out, "{} (built-in, use -b)", template.id)?;
+            }
+
+            for path in templates::user_templates(&templates::template_dir()?)? {
+                writeln!(out, "{} (user, use -i)", path.display())?;
+            }
+        }
+        // List builtin templates subcommand
+        Some(("list_builtin_templates", _)) => {
+            for template in &templates::TEMPLATES {
+                writeln!(out, "{}", template.id)?;
+            }
+        }
+        _ => {
+            panic!("Exhausted list of subcommands and subcommand_required prevents `None`");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_yes() {
+        assert!(confirm(true, || panic!("Shouldn't ask for confirmation!")).unwrap());
+        assert!(confirm(false, || Ok(true)).unwrap());
+        assert!(!confirm(false, || Ok(false)).unwrap());
+
+        // --yes works before or after the subcommand
+        for args in [
+            ["pinv", "-y", "give", "-k", "0", "1"],
+            ["pinv", "give", "-k", "0", "1", "--yes"],
+        ] {
+            let matches = command().try_get_matches_from(args).unwrap();
+
+            assert!(matches.contains_id("yes"));
+        }
+
+        let matches = command()
+            .try_get_matches_from(["pinv", "give", "-k", "0", "1"])
+            .unwrap();
+
+        assert!(!matches.contains_id("yes"));
+    }
+
+    #[test]
+    fn test_command_database() {
+        // --database works before or after the subcommand
+        for args in [
+            ["pinv", "-d", "lab.db3", "tui"],
+            ["pinv", "tui", "--database", "lab.db3"],
+        ] {
+            let matches = command().try_get_matches_from(args).unwrap();
+
+            assert_eq!(
+                matches.get_one::<String>("database").map(String::as_str),
+                Some("lab.db3")
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_bad_key() {
+        let mut db = Db::_new_test();
+
+        for subcommand in ["find", "delete"] {
+            let matches = command()
+                .try_get_matches_from(["pinv", "-y", subcommand, "1_F"])
+                .unwrap();
+
+            let error = run(
+                &mut db,
+                &matches,
+                &Config::default(),
+                &mut "".as_bytes(),
+                &mut Vec::<u8>::new(),
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                error.to_string(),
+                "\"1_F\" isn't a key, keys only use 0-9, A-Z, a-z, + and -!"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_ask() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("SIZE", DataType::TEXT)],
+        ))
+        .unwrap();
+        db.add_entry(Entry::new("BOLT", 0, "bin", 1, 0, 0)).unwrap();
+
+        let matches = command()
+            .try_get_matches_from(["pinv", "give", "-k", "0", "2"])
+            .unwrap();
+
+        // The prompt goes to out along with everything else
+        let mut out = Vec::<u8>::new();
+
+        run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "n\n".as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.ends_with("New quantity: 3\nConfirm?(y/n)\n'y' not selected, aborted!\n"));
+        assert_eq!(db.grab_entry(0).unwrap().quantity, 1);
+
+        run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "y\n".as_bytes(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(db.grab_entry(0).unwrap().quantity, 3);
+    }
+
+    #[test]
+    fn test_delete_entry_no_confirm() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("SIZE", DataType::TEXT)],
+        ))
+        .unwrap();
+        db.add_entry(Entry::new("BOLT", 0, "bin", 1, 0, 0)).unwrap();
+
+        let mut out = Vec::<u8>::new();
+
+        let deleted = delete_entry(
+            &mut db,
+            0,
+            false,
+            true,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(deleted);
+        assert!(out.contains("ONCE AN ENTRY IS DELETED, IT CANNOT BE UNDONE"));
+        assert!(!out.contains("Confirm?"));
+        db.grab_entry(0).unwrap_err();
+    }
+
+    #[test]
+    fn test_import_entries_decimal_comma() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![
+                CatagoryField::new("LENGTH", DataType::REAL),
+                CatagoryField::new("NOTE", DataType::TEXT),
+            ],
+        ))
+        .unwrap();
+
+        let data = "KEY,LOCATION,QUANTITY,LENGTH,NOTE\n\
+                    0,bin,1,\"1,2\",\"hex, zinc\"\n";
+
+        // Without the option the comma isn't a valid number
+        import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::Import,
+            false,
+            &Config::default(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+
+        import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::Import,
+            true,
+            &Config::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry.fields[0], EntryField::new("LENGTH", "1.2e0"));
+        assert_eq!(entry.fields[1], EntryField::new("NOTE", "hex, zinc"));
+    }
+
+    #[test]
+    fn test_run_list() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("SIZE", DataType::TEXT)],
+        ))
+        .unwrap();
+        db.add_entry(Entry::new("BOLT", 0, "bin", 1, 0, 0)).unwrap();
+
+        let matches = command()
+            .try_get_matches_from(["pinv", "list", "-c", "bolt"])
+            .unwrap();
+
+        let mut out = Vec::<u8>::new();
+
+        run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("ENTRY 0, CATAGORY BOLT:\n    LOCATION = bin,"));
+        assert!(out.contains("QUANTITY = 1"));
+
+        // Errors are returned instead of printed
+        let matches = command()
+            .try_get_matches_from(["pinv", "list", "-c", "nut"])
+            .unwrap();
+
+        run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_run_list_table() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("NOTE", DataType::TEXT)],
+        ))
+        .unwrap();
+
+        let mut entry = Entry::new("BOLT", 0, "bin", 1, 0, 0);
+        entry.add_fields(&[EntryField::new("NOTE", &"very long note ".repeat(20))]);
+        db.add_entry(entry).unwrap();
+
+        let matches = command()
+            .try_get_matches_from(["pinv", "list", "-c", "bolt", "--table", "--max-width", "80"])
+            .unwrap();
+
+        let mut out = Vec::<u8>::new();
+
+        run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut out,
+        )
+        .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("KEY | LOCATION | QUANTITY | "));
+        assert!(rows[1].starts_with("0   | bin      | 1        | "));
+
+        for row in rows {
+            assert!(row.chars().count() <= 80);
+        }
+    }
+
+    #[test]
+    fn test_run_list_timestamp_format() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("SIZE", DataType::TEXT)],
+        ))
+        .unwrap();
+        db.add_entry(Entry::new("BOLT", 0, "bin", 1, 0, 0)).unwrap();
+
+        let config = Config::from_toml("timestamp_format = \"%Y\"\ntimezone = \"UTC\"").unwrap();
+
+        // Both the table and the plain list show timestamps like the config says
+        for args in [
+            vec!["pinv", "list", "-c", "bolt", "--table"],
+            vec!["pinv", "list", "-c", "bolt"],
+        ] {
+            let matches = command().try_get_matches_from(args).unwrap();
+
+            let mut out = Vec::<u8>::new();
+
+            run(&mut db, &matches, &config, &mut "".as_bytes(), &mut out).unwrap();
+
+            let out = String::from_utf8(out).unwrap();
+
+            assert_eq!(out.matches("1970").count(), 2, "{}", out);
+            assert!(!out.contains("1970-01-01"));
+        }
+    }
+
+    #[test]
+    fn test_import_entries_preview() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("SIZE", DataType::TEXT)],
+        ))
+        .unwrap();
+
+        let data = "KEY,LOCATION,QUANTITY,SIZE\n\
+                    0,bin 1,10,M3x8\n\
+                    1,bin 2,5,M3x10\n\
+                    2,bin 3,1,M4x8\n";
+
+        let mut out = Vec::<u8>::new();
+
+        let previewed = import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::Preview(2),
+            false,
+            &Config::default(),
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert_eq!(previewed, 2);
+        assert!(out.contains("ENTRY 0, CATAGORY BOLT") && out.contains("M3x10"));
+        assert!(!out.contains("M4x8"));
+
+        // Nothing should have been imported
+        assert_eq!(db.search_catagory("BOLT", &[], None).unwrap(), vec![]);
+
+        let imported = import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::Import,
+            false,
+            &Config::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(imported, 3);
+        assert_eq!(db.search_catagory("BOLT", &[], None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_import_new_catagory() {
+        let mut db = Db::_new_test();
+
+        let data = "bolt,,,,\n\
+                    key,location,quantity,size:t,length:r\n\
+                    0,bin 1,10,M3,\"8,5\"\n\
+                    1,bin 2,5,M4,12\n";
+
+        let (catagory, entries) = csv::csv_to_catagory(data, ',').unwrap();
+        let (catagory_id, count) = import_new_catagory(&mut db, catagory, entries, true).unwrap();
+
+        assert_eq!(catagory_id, "BOLT");
+        assert_eq!(count, 2);
+        assert_eq!(
+            db.grab_catagory("BOLT").unwrap(),
+            Catagory::with_fields(
+                "BOLT",
+                vec![
+                    CatagoryField::new("SIZE", DataType::TEXT),
+                    CatagoryField::new("LENGTH", DataType::REAL),
+                ]
+            )
+        );
+        assert_eq!(
+            db.grab_entry(0).unwrap().fields[1],
+            EntryField::new("LENGTH", "8.5e0")
+        );
+
+        // The catagory isn't kept if its entries can't be imported
+        let data = "nut\n\
+                    key,location,quantity,size:i\n\
+                    2,bin 3,1,M3\n";
+
+        let (catagory, entries) = csv::csv_to_catagory(data, ',').unwrap();
+
+        import_new_catagory(&mut db, catagory, entries, false).unwrap_err();
+        assert_eq!(db.list_catagories().unwrap(), vec!["BOLT"]);
+    }
+
+    #[test]
+    fn test_run_import_file() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("LENGTH", DataType::REAL)],
+        ))
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("pinv_import_{}.csv", std::process::id()));
+
+        fs::write(
+            &path,
+            "KEY;LOCATION;QUANTITY;LENGTH\n\
+             0;\"bin 1; shelf 2\";10;8,5\n\
+             1;bin 2;5;12\n",
+        )
+        .unwrap();
+
+        let matches = command()
+            .try_get_matches_from([
+                "pinv",
+                "-y",
+                "import",
+                "-c",
+                "bolt",
+                "--delimiter",
+                ";",
+                "--decimal-comma",
+                path.to_str().unwrap(),
+            ])
+            .unwrap();
+
+        let mut out = Vec::<u8>::new();
+
+        let result = run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut out,
+        );
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Import 2 entries into BOLT?\nImported 2 entries\n"
+        );
+
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry.location, "bin 1; shelf 2");
+        assert_eq!(entry.fields[0], EntryField::new("LENGTH", "8.5e0"));
+    }
+
+    #[test]
+    fn test_run_import_no_header() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("LENGTH", DataType::REAL)],
+        ))
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("pinv_no_header_{}.csv", std::process::id()));
+
+        // Columns in the order export writes them
+        fs::write(&path, "0,\"bin\" 1,10,100,200,8\n").unwrap();
+
+        let matches = command()
+            .try_get_matches_from([
+                "pinv",
+                "-y",
+                "import",
+                "-c",
+                "bolt",
+                "--no-header",
+                "--no-quotes",
+                path.to_str().unwrap(),
+            ])
+            .unwrap();
+
+        let result = run(
+            &mut db,
+            &matches,
+            &Config::default(),
+            &mut "".as_bytes(),
+            &mut Vec::<u8>::new(),
+        );
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry.location, "\"bin\" 1");
+        assert_eq!(entry.created, 100);
+        assert_eq!(entry.fields[0], EntryField::new("LENGTH", "8e0"));
+    }
+
+    #[test]
+    fn test_run_import_dedupe_keys() {
+        let path = std::env::temp_dir().join(format!("pinv_dedupe_{}.csv", std::process::id()));
+
+        fs::write(
+            &path,
+            "KEY,LOCATION,QUANTITY,LENGTH\n\
+             0,bin 1,10,8\n\
+             1,bin 2,5,12\n\
+             0,bin 3,7,10\n",
+        )
+        .unwrap();
+
+        let import = |mode: &str| {
+            let mut db = Db::_new_test();
+
+            db.add_catagory(Catagory::with_fields(
+                "BOLT",
+                vec![CatagoryField::new("LENGTH", DataType::REAL)],
+            ))
+            .unwrap();
+
+            let matches = command()
+                .try_get_matches_from([
+                    "pinv",
+                    "-y",
+                    "import",
+                    "-c",
+                    "bolt",
+                    "--dedupe-keys",
+                    mode,
+                    path.to_str().unwrap(),
+                ])
+                .unwrap();
+
+            let mut out = Vec::<u8>::new();
+
+            run(
+                &mut db,
+                &matches,
+                &Config::default(),
+                &mut "".as_bytes(),
+                &mut out,
+            )
+            .map(|_| db)
+        };
+
+        let error = import("error").err().unwrap();
+        let db = import("keep-last");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.to_string(), "Duplicate keys in import: 0!");
+
+        // The last entry with key 0 wins
+        let db = db.unwrap();
+
+        assert_eq!(db.grab_entry(0).unwrap().location, "bin 3");
+        assert_eq!(db.count_matching("BOLT", &[]).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_run_import_strong_confirm() {
+        let config = Config::from_toml("bulk_confirm_threshold = 5").unwrap();
+
+        let import = |rows: u64, answer: &str| {
+            let path = std::env::temp_dir().join(format!(
+                "pinv_strong_{}_{}.csv",
+                std::process::id(),
+                rows
+            ));
+
+            let mut data = String::from("KEY,LOCATION,QUANTITY\n");
+
+            for key in 0..rows {
+                data.push_str(&format!("{},bin,1\n", key));
+            }
+
+            fs::write(&path, data).unwrap();
+
+            let mut db = Db::_new_test();
+
+            db.add_catagory(Catagory::with_fields(
+                "BOLT",
+                vec![CatagoryField::new("SIZE", DataType::TEXT)],
+            ))
+            .unwrap();
+
+            let matches = command()
+                .try_get_matches_from(["pinv", "import", "-c", "bolt", path.to_str().unwrap()])
+                .unwrap();
+
+            let mut out = Vec::<u8>::new();
+
+            let result = run(&mut db, &matches, &config, &mut answer.as_bytes(), &mut out);
+            fs::remove_file(&path).unwrap();
+            result.unwrap();
+
+            (
+                db.count_matching("BOLT", &[]).unwrap(),
+                String::from_utf8(out).unwrap(),
+            )
+        };
+
+        // Up to the threshold y is enough
+        let (count, out) = import(5, "y\n");
+
+        assert_eq!(count, 5);
+        assert!(out.contains("Confirm?(y/n)"));
+
+        // Past it the number of entries has to be typed
+        let (count, out) = import(6, "y\n");
+
+        assert_eq!(count, 0);
+        assert!(out.ends_with("Type 6 to confirm:\n6 not typed, aborted!\n"));
+
+        let (count, out) = import(6, "6\n");
+
+        assert_eq!(count, 6);
+        assert!(out.ends_with("Imported 6 entries\n"));
+    }
+
+    #[test]
+    fn test_import_entries_dry_run() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("LENGTH", DataType::INTEGER)],
+        ))
+        .unwrap();
+
+        let data = "KEY,LOCATION,QUANTITY,LENGTH\n\
+                    0,bin 1,10,8\n\
+                    1,bin 2,5,10\n\
+                    2,bin 3,1,8\n\
+                    3,bin 4,7,12\n";
+
+        let checked = import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::DryRun,
+            false,
+            &Config::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(checked, 4);
+        assert_eq!(db.search_catagory("BOLT", &[], None).unwrap(), vec![]);
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 0);
+        assert_eq!(db.history(None).unwrap(), vec![]);
+
+        // Rows that wouldn't import are still caught
+        let data = "KEY,LOCATION,QUANTITY,LENGTH\n\
+                    0,bin 1,10,8\n\
+                    1,bin 2,5,long\n";
+
+        import_entries(
+            &mut db,
+            csv::csv_to_entries(data, "BOLT", ',').unwrap(),
+            "BOLT",
+            ImportMode::DryRun,
+            false,
+            &Config::default(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+    }
+}
@@ -0,0 +1,267 @@
+//! User configuration for pinv, loaded from a TOML file in the config
+//! directory.
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+use chrono::FixedOffset;
+use chrono::Local;
+use chrono::TimeZone;
+use directories::ProjectDirs;
+use serde::Deserialize;
+use simple_error::bail;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Name of the config file within the config directory
+static CONFIG_FILENAME: &str = "config.toml";
+
+/// Format timestamps are shown in unless the config says otherwise
+static DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How strongly the user needs to confirm an operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Confirmation {
+    /// A simple yes or no
+    Normal,
+    /// The user needs to retype the number of entries affected
+    Strong,
+}
+
+/// User configuration. Any option left out of the config file is given its
+/// default value.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Operations affecting more entries than this need a strong confirmation
+    pub bulk_confirm_threshold: usize,
+    /// Keys to use in the TUI in place of the defaults, by binding name
+    pub keys: HashMap<String, String>,
+    /// Entries with this quantity or less are shown in red in the TUI
+    pub low_stock_threshold: u64,
+    /// strftime format the TUI and CLI show timestamps in
+    pub timestamp_format: String,
+    /// Fixed timezone to show timestamps in, like "UTC" or "+02:00". The
+    /// local timezone is used if there is none.
+    pub timezone: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bulk_confirm_threshold: 10,
+            keys: HashMap::new(),
+            low_stock_threshold: 0,
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_owned(),
+            timezone: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file from the config directory, or the default config
+    /// if there is no config file.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let path = Self::path()?;
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)?;
+
+        Self::from_toml(&data)
+    }
+
+    /// Parse a config from a TOML string
+    pub fn from_toml(data: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(data)?)
+    }
+
+    /// Get the path to the config file
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = config_dir()?;
+        path.push(CONFIG_FILENAME);
+
+        Ok(path)
+    }
+
+    /// Make sure the timestamp format and timezone can be used, falling back
+    /// to the defaults if they can't. Returns a warning for each one that
+    /// couldn't be used.
+    pub fn check_timestamps(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if StrftimeItems::new(&self.timestamp_format).any(|item| item == Item::Error) {
+            warnings.push(format!(
+                "\"{}\" isn't a valid timestamp format, using \"{}\"!",
+                self.timestamp_format, DEFAULT_TIMESTAMP_FORMAT
+            ));
+            self.timestamp_format = DEFAULT_TIMESTAMP_FORMAT.to_owned();
+        }
+
+        if let Some(timezone) = &self.timezone {
+            if parse_offset(timezone).is_none() {
+                warnings.push(format!(
+                    "\"{}\" isn't a valid timezone, using the local timezone!",
+                    timezone
+                ));
+                self.timezone = None;
+            }
+        }
+
+        warnings
+    }
+
+    /// Format a unix timestamp with the timestamp format and timezone. Only
+    /// use after check_timestamps, an invalid format can't be written.
+    /// Timestamps out of range are written as they are.
+    pub fn format_timestamp(&self, timestamp: i64) -> String {
+        let offset = self.timezone.as_deref().and_then(parse_offset);
+
+        let formatted = match offset {
+            Some(offset) => offset
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|time| time.format(&self.timestamp_format).to_string()),
+            None => Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|time| time.format(&self.timestamp_format).to_string()),
+        };
+
+        formatted.unwrap_or_else(|| timestamp.to_string())
+    }
+
+    /// Get how strongly an operation affecting count entries should be
+    /// confirmed.
+    pub fn confirmation(&self, count: usize) -> Confirmation {
+        match count > self.bulk_confirm_threshold {
+            true => Confirmation::Strong,
+            false => Confirmation::Normal,
+        }
+    }
+}
+
+/// Parse a fixed timezone, either "UTC" or an offset like "+02:00" or "-05"
+fn parse_offset(timezone: &str) -> Option<FixedOffset> {
+    if timezone.eq_ignore_ascii_case("UTC") {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, offset) = match (timezone.strip_prefix('+'), timezone.strip_prefix('-')) {
+        (Some(offset), _) => (1, offset),
+        (_, Some(offset)) => (-1, offset),
+        _ => return None,
+    };
+
+    let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+
+    let hours: u8 = hours.parse().ok()?;
+    let minutes: u8 = minutes.parse().ok()?;
+
+    if minutes >= 60 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (i32::from(hours) * 3600 + i32::from(minutes) * 60))
+}
+
+/// Get the directory pinv keeps its config in
+pub fn config_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let qualifier = "org";
+    let organisation = crate::ORGANISATION;
+    let application = crate::APPLICATION;
+
+    let dirs = match ProjectDirs::from(qualifier, organisation, application) {
+        Some(dirs) => dirs,
+        None => {
+            bail!("Could not find the config directory!");
+        }
+    };
+
+    Ok(dirs.config_dir().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_toml() {
+        let config = Config::from_toml("bulk_confirm_threshold = 5").unwrap();
+
+        assert_eq!(config.bulk_confirm_threshold, 5);
+
+        // Missing options should fall back to the defaults
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+
+        let config = Config::from_toml("[keys]\nfind = \"/\"").unwrap();
+
+        assert_eq!(config.keys["find"], "/");
+
+        let config = Config::from_toml("low_stock_threshold = 3").unwrap();
+
+        assert_eq!(config.low_stock_threshold, 3);
+
+        // Should fail
+        Config::from_toml("bulk_confirm_threshold = \"five\"").unwrap_err();
+    }
+
+    #[test]
+    fn test_config_format_timestamp() {
+        let mut config =
+            Config::from_toml("timestamp_format = \"%d/%m/%Y %H:%M\"\ntimezone = \"+02:00\"")
+                .unwrap();
+
+        assert_eq!(config.check_timestamps(), Vec::<String>::new());
+        assert_eq!(config.format_timestamp(0), "01/01/1970 02:00");
+
+        config.timezone = Some("UTC".to_owned());
+
+        assert_eq!(config.format_timestamp(86400), "02/01/1970 00:00");
+
+        // Out of range timestamps are written as they are, in either timezone
+        assert_eq!(config.format_timestamp(i64::MAX), i64::MAX.to_string());
+
+        config.timezone = None;
+
+        assert_eq!(config.format_timestamp(i64::MIN), i64::MIN.to_string());
+
+        // Both should fall back to the defaults
+        let mut config =
+            Config::from_toml("timestamp_format = \"%Q\"\ntimezone = \"Mars\"").unwrap();
+
+        assert_eq!(config.check_timestamps().len(), 2);
+        assert_eq!(config.timestamp_format, DEFAULT_TIMESTAMP_FORMAT);
+        assert_eq!(config.timezone, None);
+    }
+
+    #[test]
+    fn test_config_confirmation() {
+        let config = Config {
+            bulk_confirm_threshold: 5,
+            ..Default::default()
+        };
+
+        assert_eq!(config.confirmation(5), Confirmation::Normal);
+        assert_eq!(config.confirmation(6), Confirmation::Strong);
+    }
+}
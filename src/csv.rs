@@ -0,0 +1,502 @@
+//! Helpers for reading and writing comma-separated values in the format
+//! spreadsheets like Excel and LibreOffice expect.
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+use crate::b64;
+use crate::db::{Catagory, CatagoryField, DataType, Db, Entry, EntryField, BUILTIN_FIELDS};
+use chrono::Local;
+use simple_error::bail;
+use std::error::Error;
+
+/// Columns every CSV import needs
+static REQUIRED_COLUMNS: [&str; 3] = ["KEY", "LOCATION", "QUANTITY"];
+
+/// How CSV data is laid out. The default is standard CSV: commas between
+/// values, a header row first, and quotes around values that need them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub has_headers: bool,
+    pub quoting: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            has_headers: true,
+            quoting: true,
+        }
+    }
+}
+
+/// Quote a single value if it contains a delimiter, quote or line break.
+/// Quotes inside the value are doubled.
+pub fn escape_value(value: &str) -> String {
+    if !value.contains([',', '"', '\n', '\r']) {
+        return value.to_owned();
+    }
+
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Format a row of values as a single CSV line, including the line break.
+pub fn format_row<S: AsRef<str>>(values: &[S]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|value| escape_value(value.as_ref()))
+        .collect();
+
+    format!("{}\r\n", values.join(","))
+}
+
+/// Split CSV data into records of values, with values separated by the given
+/// delimiter, usually a comma. Quoted values can contain the delimiter, line
+/// breaks and doubled quotes. Blank lines are skipped.
+pub fn parse_records(data: &str, delimiter: char) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    parse_records_with(
+        data,
+        &CsvOptions {
+            delimiter,
+            ..CsvOptions::default()
+        },
+    )
+}
+
+/// Split CSV data into records like parse_records. Without quoting, quotes
+/// are read as part of the values.
+pub fn parse_records_with(
+    data: &str,
+    options: &CsvOptions,
+) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let delimiter = options.delimiter;
+
+    if matches!(delimiter, '"' | '\r' | '\n') {
+        bail!("{:?} can't be used as a delimiter!", delimiter);
+    }
+
+    let mut records = Vec::<Vec<String>>::new();
+    let mut record = Vec::<String>::new();
+    let mut value = String::new();
+
+    let mut chars = data.chars().peekable();
+    let mut quoted = false;
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    value.push('"');
+                }
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+
+                    value.push(c);
+                }
+            }
+
+            continue;
+        }
+
+        match c {
+            '"' if options.quoting && value.is_empty() => quoted = true,
+            _ if c == delimiter => record.push(std::mem::take(&mut value)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut value));
+
+                // Skip blank lines
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+
+                line += 1;
+            }
+            _ => value.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("Unterminated quote on line {}!", line);
+    }
+
+    // The last record might not end with a line break
+    if !value.is_empty() || !record.is_empty() {
+        record.push(value);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Read entries for a catagory out of CSV data. The first record is a header
+/// naming the field of each column, which must include KEY, LOCATION and
+/// QUANTITY. CREATED and MODIFIED are optional and default to now. Keys are
+/// base64, like in the export. Values are separated by the delimiter.
+pub fn csv_to_entries(
+    data: &str,
+    catagory_id: &str,
+    delimiter: char,
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let options = CsvOptions {
+        delimiter,
+        ..CsvOptions::default()
+    };
+
+    csv_to_entries_with(data, catagory_id, &options, &[])
+}
+
+/// Read entries for a catagory out of CSV data laid out as given by options.
+/// Without a header row, the columns are the ones given, usually from
+/// Db::display_fields so a headerless export reads back in.
+pub fn csv_to_entries_with(
+    data: &str,
+    catagory_id: &str,
+    options: &CsvOptions,
+    columns: &[String],
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let mut records = parse_records_with(data, options)?.into_iter();
+
+    if !options.has_headers {
+        let header: Vec<String> = columns.iter().map(|name| header_name(name)).collect();
+
+        return records_to_entries(&header, records, catagory_id, 1);
+    }
+
+    let header: Vec<String> = match records.next() {
+        Some(header) => header.iter().map(|name| header_name(name)).collect(),
+        None => {
+            bail!("No header in CSV!");
+        }
+    };
+
+    records_to_entries(&header, records, catagory_id, 2)
+}
+
+/// Read a catagory and its entries out of CSV data, so the catagory doesn't
+/// have to exist before importing. The first record names the catagory, and
+/// the header after it gives the type of every field that isn't builtin, like
+/// "MPN:t" or "OHMS:r". The rest is read like csv_to_entries.
+pub fn csv_to_catagory(
+    data: &str,
+    delimiter: char,
+) -> Result<(Catagory, Vec<Entry>), Box<dyn Error>> {
+    let mut records = parse_records(data, delimiter)?.into_iter();
+
+    // Spreadsheets pad every row to the same width, so the catagory's row can
+    // end in blank values
+    let catagory_id = match records.next() {
+        Some(record) if record[1..].iter().all(|value| value.trim().is_empty()) => {
+            header_name(&record[0])
+        }
+        _ => {
+            bail!("The first row of the CSV should only name the catagory!");
+        }
+    };
+
+    Db::check_id_string(&catagory_id)?;
+
+    let typed_header = match records.next() {
+        Some(header) => header,
+        None => {
+            bail!("No header in CSV!");
+        }
+    };
+
+    let mut header = Vec::<String>::with_capacity(typed_header.len());
+    let mut fields = Vec::<CatagoryField>::new();
+
+    for typed_name in typed_header {
+        let (name, datatype) = match typed_name.rsplit_once(':') {
+            Some((name, datatype)) => (header_name(name), Some(datatype.trim())),
+            None => (header_name(&typed_name), None),
+        };
+
+        match (BUILTIN_FIELDS.contains(&name.as_str()), datatype) {
+            (true, None) => {}
+            (true, Some(_)) => {
+                bail!("{} is builtin and can't be given a type!", name);
+            }
+            (false, Some(datatype)) if datatype.chars().count() == 1 => {
+                let datatype = DataType::from_char(datatype.chars().next().unwrap())?;
+
+                fields.push(CatagoryField::new(&name, datatype));
+            }
+            (false, _) => {
+                bail!(
+                    "Field {} needs a one character type, like {}:t!",
+                    name,
+                    name
+                );
+            }
+        }
+
+        header.push(name);
+    }
+
+    let entries = records_to_entries(&header, records, &catagory_id, 2)?;
+
+    Ok((Catagory::with_fields(&catagory_id, fields), entries))
+}
+
+/// Clean up the name of a column in a header, so " \"mpn\"" becomes "MPN"
+fn header_name(name: &str) -> String {
+    name.trim().trim_matches('"').to_uppercase()
+}
+
+/// Read entries for a catagory out of the records following a header, with
+/// the first record on the given row
+fn records_to_entries(
+    header: &[String],
+    records: impl Iterator<Item = Vec<String>>,
+    catagory_id: &str,
+    first_row: usize,
+) -> Result<Vec<Entry>, Box<dyn Error>> {
+    // Make sure every header can be used as a field before reading any rows
+    for (i, name) in header.iter().enumerate() {
+        if let Err(error) = Db::check_id_string(name) {
+            bail!("Invalid header \"{}\" in column {}: {}", name, i + 1, error);
+        }
+
+        if header[..i].contains(name) {
+            bail!("Header \"{}\" appears more than once!", name);
+        }
+    }
+
+    for column in REQUIRED_COLUMNS {
+        if !header.iter().any(|name| name == column) {
+            bail!("CSV is missing the {} column!", column);
+        }
+    }
+
+    let now = Local::now().timestamp();
+    let mut entries = Vec::<Entry>::new();
+
+    for (row, record) in records
+        .enumerate()
+        .map(|(i, record)| (i + first_row, record))
+    {
+        if record.len() != header.len() {
+            bail!(
+                "Row {} has {} values but the header has {}!",
+                row,
+                record.len(),
+                header.len()
+            );
+        }
+
+        let mut entry = Entry::new(catagory_id, 0, "", 0, now, now);
+
+        for (name, value) in header.iter().zip(record) {
+            match name.as_str() {
+                "KEY" => entry.key = b64::to_u64(&value)?,
+                "LOCATION" => entry.location = value,
+                "QUANTITY" => match value.trim().parse() {
+                    Ok(quantity) => entry.quantity = quantity,
+                    Err(_) => {
+                        bail!("Invalid quantity \"{}\" on row {}!", value, row);
+                    }
+                },
+                "CREATED" | "MODIFIED" => {
+                    let timestamp = match value.trim().parse() {
+                        Ok(timestamp) => timestamp,
+                        Err(_) => {
+                            bail!("Invalid {} time \"{}\" on row {}!", name, value, row);
+                        }
+                    };
+
+                    match name.as_str() {
+                        "CREATED" => entry.created = timestamp,
+                        _ => entry.modified = timestamp,
+                    }
+                }
+                _ => entry.add_field(EntryField::new(name, &value)),
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Swap the decimal commas in the given fields of entries for dots, so "1,2"
+/// becomes "1.2". Meant for CSVs from places that write numbers that way, and
+/// should only be used on REAL fields.
+pub fn decimal_commas_to_dots(entries: &mut [Entry], field_ids: &[String]) {
+    for entry in entries {
+        for field in &mut entry.fields {
+            if field_ids.contains(&field.id) {
+                field.value = field.value.replace(',', ".");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_format_row() {
+        assert_eq!(format_row(&["a", "b c", "1.5"]), "a,b c,1.5\r\n");
+        assert_eq!(
+            format_row(&["a,b", "say \"hi\"", "two\nlines"]),
+            "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_parse_records() {
+        let records =
+            parse_records("a,\"b,c\",\"say \"\"hi\"\"\"\r\n\n1,,\"two\nlines\"", ',').unwrap();
+
+        assert_eq!(
+            records,
+            vec![vec!["a", "b,c", "say \"hi\""], vec!["1", "", "two\nlines"],]
+        );
+
+        parse_records("a,\"b", ',').unwrap_err();
+
+        // Other delimiters work the same way
+        assert_eq!(
+            parse_records("a;\"b;c\";1,5\n", ';').unwrap(),
+            vec![vec!["a", "b;c", "1,5"]]
+        );
+
+        parse_records("a,b", '"').unwrap_err();
+    }
+
+    #[test]
+    fn test_csv_to_entries() {
+        let data = "key,location,quantity,\"mpn\",ohms\n\
+                    0,bazville,10,ERJ-PM8F8204V,8.2e6\n\
+                    A,\"shelf 1, bin 2\",2,,1e3\n";
+
+        let entries = csv_to_entries(data, "RESISTOR", ',').unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, 0);
+        assert_eq!(
+            entries[0].fields[0],
+            EntryField::new("MPN", "ERJ-PM8F8204V")
+        );
+        assert_eq!(entries[1].key, 10);
+        assert_eq!(entries[1].location, "shelf 1, bin 2");
+        assert_eq!(entries[1].fields[1], EntryField::new("OHMS", "1e3"));
+    }
+
+    #[test]
+    fn test_csv_to_entries_with() {
+        // Standard CSV from a spreadsheet, read with the default options
+        let data = "KEY,LOCATION,QUANTITY,MPN\r\n\
+                    0,\"shelf 1, bin 2\",10,\"ERJ, \"\"8204\"\"\"\r\n";
+
+        let entries = csv_to_entries_with(data, "RESISTOR", &CsvOptions::default(), &[]).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].location, "shelf 1, bin 2");
+        assert_eq!(
+            entries[0].fields[0],
+            EntryField::new("MPN", "ERJ, \"8204\"")
+        );
+
+        // No header row, so the columns come from the caller
+        let columns: Vec<String> = ["KEY", "LOCATION", "QUANTITY", "MPN"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let options = CsvOptions {
+            delimiter: '\t',
+            has_headers: false,
+            quoting: false,
+        };
+
+        let entries =
+            csv_to_entries_with("A\t\"bin\t3\t5\"x\n", "RESISTOR", &options, &columns).unwrap();
+
+        assert_eq!(entries[0].key, 10);
+        assert_eq!(entries[0].location, "\"bin");
+        assert_eq!(entries[0].fields[0], EntryField::new("MPN", "5\"x"));
+
+        let error = csv_to_entries_with("A\tbin\t3\n", "RESISTOR", &options, &columns).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Row 1 has 3 values but the header has 4!"
+        );
+    }
+
+    #[test]
+    fn test_csv_to_catagory() {
+        let data = "\"resistor\",,\n\
+                    KEY,LOCATION,QUANTITY,mpn:t,OHMS:r\n\
+                    0,bazville,10,ERJ-PM8F8204V,8.2e6\n";
+
+        let (catagory, entries) = csv_to_catagory(data, ',').unwrap();
+
+        assert_eq!(
+            catagory,
+            Catagory::with_fields(
+                "RESISTOR",
+                vec![
+                    CatagoryField::new("MPN", DataType::TEXT),
+                    CatagoryField::new("OHMS", DataType::REAL),
+                ]
+            )
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].catagory_id, "RESISTOR");
+        assert_eq!(entries[0].fields[1], EntryField::new("OHMS", "8.2e6"));
+
+        let error = csv_to_catagory("RESISTOR\nKEY,LOCATION,QUANTITY,MPN\n", ',').unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Field MPN needs a one character type, like MPN:t!"
+        );
+
+        let error = csv_to_catagory("KEY,LOCATION,QUANTITY\n0,bin,1\n", ',').unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The first row of the CSV should only name the catagory!"
+        );
+    }
+
+    #[test]
+    fn test_csv_to_entries_bad_header() {
+        let data = "KEY,LOCATION,QUANTITY,OHMS+\n0,bazville,10,8.2e6\n";
+
+        let error = csv_to_entries(data, "RESISTOR", ',').unwrap_err();
+
+        assert!(error.to_string().contains("\"OHMS+\" in column 4"));
+
+        let data = "KEY,QUANTITY\n0,10\n";
+
+        let error = csv_to_entries(data, "RESISTOR", ',').unwrap_err();
+
+        assert_eq!(error.to_string(), "CSV is missing the LOCATION column!");
+    }
+}
@@ -17,20 +17,84 @@
 // pinv(in a file named COPYING).
 // If not, see <https://www.gnu.org/licenses/>.
 use crate::b64;
-use chrono::{Local, TimeZone};
+use crate::config::Config;
+use crate::csv;
+use crate::templates;
+use chrono::{DateTime, Local, TimeZone};
 use core::fmt;
 use directories::ProjectDirs;
 use lazy_static::lazy_static;
 use regex::Regex;
+use rusqlite::backup::Progress;
+use rusqlite::ffi;
+use rusqlite::types::{ToSql, Value, ValueRef};
+use rusqlite::DatabaseName;
 use rusqlite::Error as SqlError;
-use rusqlite::{types::ValueRef, Connection, OptionalExtension};
+use rusqlite::ErrorCode;
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Params};
+use serde::{Deserialize, Serialize};
 use simple_error::bail;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{cmp, error::Error, fs};
 
+/// Fields every catagory has, in the order they appear in the table
+pub static BUILTIN_FIELDS: [&str; 5] = ["KEY", "LOCATION", "QUANTITY", "CREATED", "MODIFIED"];
+
+/// How long to wait for another process to finish writing before giving up
+/// with "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// Tables pinv uses to keep track of things, which can't be catagories
+pub static RESERVED_TABLES: [&str; 5] = ["KEYS", "METADATA", "FIELD_METADATA", "HISTORY", "TRASH"];
+
+/// Query creating the table that holds extra info about catagories, like the
+/// order their fields are displayed in
+const METADATA_TABLE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS METADATA (CATAGORY TEXT NOT NULL PRIMARY KEY, DISPLAY_ORDER TEXT NOT NULL)";
+
+/// Query creating the table that holds extra info about the fields of
+/// catagories, like whether they can be changed once set
+const FIELD_METADATA_TABLE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS FIELD_METADATA (CATAGORY TEXT NOT NULL, FIELD TEXT NOT NULL, IMMUTABLE INTEGER NOT NULL, PRIMARY KEY (CATAGORY, FIELD))";
+
+/// Query creating the table that journals every change made to entries
+const HISTORY_TABLE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS HISTORY (ID INTEGER NOT NULL PRIMARY KEY, TIMESTAMP INTEGER NOT NULL, OPERATION TEXT NOT NULL, CATAGORY TEXT NOT NULL, KEY INTEGER NOT NULL, DIFF TEXT NOT NULL DEFAULT '')";
+
+/// Query creating the table that holds trashed entries until they're restored
+/// or the trash is emptied. Entries are kept as JSON like export_entry makes.
+const TRASH_TABLE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS TRASH (ID INTEGER NOT NULL PRIMARY KEY, TIMESTAMP INTEGER NOT NULL, CATAGORY TEXT NOT NULL, KEY INTEGER NOT NULL, ENTRY TEXT NOT NULL)";
+
+/// Catagory id paired with its total value, None if the catagory is unpriced
+pub type CatagoryValue = (String, Option<f64>);
+
+/// Location paired with the number of entries there and their total quantity
+pub type LocationCount = (String, usize, u64);
+
+/// Part of an SQL query paired with the values bound to it
+type BoundClause = (String, Vec<Box<dyn ToSql>>);
+
+/// An entry ready to be inserted by insert_entry: its key, catagory, fields
+/// and the insert itself
+type EntryInsert = (u64, String, Vec<EntryField>, BoundClause);
+
+/// Number of bytes of a blob shown before the rest is cut off
+const BLOB_PREVIEW_LEN: usize = 32;
+
+/// Extended SQLite error code for a violated UNIQUE constraint, which
+/// rusqlite doesn't export
+const SQLITE_CONSTRAINT_UNIQUE: i32 = ffi::SQLITE_CONSTRAINT | (8 << 8);
+
+/// Most entries that can be grabbed in one page
+pub const MAX_PAGE_LEN: u64 = 1000;
+
 /// Datatypes in PINV
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     /// Null, nothing
     NULL,
@@ -40,7 +104,7 @@ pub enum DataType {
     REAL,
     /// Any unicode string
     TEXT,
-    /// Raw data, currently not in use
+    /// Raw data, like datasheets or images
     BLOB,
 }
 
@@ -71,7 +135,7 @@ impl DataType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConditionOperator {
     Equal,
     NotEqual,
@@ -79,35 +143,100 @@ pub enum ConditionOperator {
     GreaterThan,
     LessThanEqual,
     GreaterThanEqual,
+    /// Text anywhere in the field, ignoring ASCII case
+    Contains,
+    /// Text at the start of the field, ignoring ASCII case
+    StartsWith,
 }
 
 impl ConditionOperator {
-    pub fn to_string(&self) -> String {
+    pub fn to_sql(&self) -> String {
         match self {
-            Self::Equal => "=".to_string(),
-            Self::NotEqual => "!=".to_string(),
-            Self::LessThan => "<".to_string(),
-            Self::GreaterThan => ">".to_string(),
-            Self::LessThanEqual => "<=".to_string(),
-            Self::GreaterThanEqual => ">=".to_string(),
+            Self::Contains | Self::StartsWith => "LIKE".to_owned(),
+            _ => self.to_string(),
         }
     }
 
+    /// Get an SQL clause comparing a field to a bound parameter
+    pub fn sql_clause(&self, field_id: &str) -> String {
+        match self {
+            Self::Contains => format!("{} LIKE '%' || ? || '%' ESCAPE '\\'", field_id),
+            Self::StartsWith => format!("{} LIKE ? || '%' ESCAPE '\\'", field_id),
+            _ => format!("{}{}?", field_id, self.to_sql()),
+        }
+    }
+
+    /// Check if the operator matches text patterns rather than comparing
+    /// values
+    pub fn is_pattern(&self) -> bool {
+        matches!(self, Self::Contains | Self::StartsWith)
+    }
+
+    /// Escape the wildcards in a LIKE pattern so they're matched literally
+    pub fn escape_pattern(value: &str) -> String {
+        value
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    }
+}
+
+impl fmt::Display for ConditionOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::LessThan => "<",
+            Self::GreaterThan => ">",
+            Self::LessThanEqual => "<=",
+            Self::GreaterThanEqual => ">=",
+            Self::Contains => "contains",
+            Self::StartsWith => "starts with",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
+
+/// How a condition joins onto the conditions before it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConditionConnector {
+    /// Both sides must match
+    #[default]
+    And,
+    /// Either side can match
+    Or,
+}
+
+impl ConditionConnector {
     pub fn to_sql(&self) -> String {
         self.to_string()
     }
 }
 
-impl fmt::Display for ConditionOperator {
+impl fmt::Display for ConditionConnector {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        let connector = match self {
+            Self::And => "AND",
+            Self::Or => "OR",
+        };
+
+        write!(f, "{}", connector)
     }
 }
 
 /// Filter Condition When Searching
+#[derive(Debug, Clone, PartialEq)]
 pub struct Condition {
+    /// How the condition joins onto the conditions before it, ignored for
+    /// the first condition
+    pub connector: ConditionConnector,
+    /// Field to compare
     pub field_id: String,
+    /// How to compare the field to the value
     pub operator: ConditionOperator,
+    /// Value to compare the field to, as a string(not yet parsed). Keys are
+    /// given in base64.
     pub value: String,
 }
 
@@ -117,23 +246,207 @@ impl Condition {
         let value = value.to_string();
 
         Self {
+            connector: ConditionConnector::And,
             field_id,
             operator,
             value,
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{}{}{}", self.field_id, self.operator, self.value)
+    /// Same condition, but joined onto the conditions before it with the
+    /// given connector
+    pub fn with_connector(mut self, connector: ConditionConnector) -> Self {
+        self.connector = connector;
+        self
+    }
+
+    /// Join conditions together from left to right, so "A OR B AND C" is read
+    /// as "(A OR B) AND C". Each condition is formatted with format_condition.
+    pub fn join<F>(conditions: &[Condition], format_condition: F) -> String
+    where
+        F: Fn(&Condition) -> String,
+    {
+        let mut joined = String::new();
+        let mut last_connector = None;
+
+        for (i, condition) in conditions.iter().enumerate() {
+            if i > 0 {
+                // Group everything so far if the connector changes, otherwise
+                // SQL would bind AND tighter than OR
+                if last_connector.is_some() && last_connector != Some(condition.connector) {
+                    joined = format!("({})", joined);
+                }
+
+                joined.push_str(&format!(" {} ", condition.connector));
+                last_connector = Some(condition.connector);
+            }
+
+            joined.push_str(&format_condition(condition));
+        }
+
+        joined
     }
 }
 
 impl fmt::Display for Condition {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self.operator.is_pattern() {
+            true => write!(f, "{} {} {}", self.field_id, self.operator, self.value),
+            false => write!(f, "{}{}{}", self.field_id, self.operator, self.value),
+        }
+    }
+}
+
+/// Stats of a catagory
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CatagoryStats {
+    /// Name of the catagory
+    pub catagory: String,
+    /// Number of entries in the catagory
+    pub entries: u64,
+    /// Sum of the quantities of every entry in the catagory
+    pub total_quantity: u64,
+}
+
+/// Kinds of changes recorded in the history
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// An entry was added
+    Add,
+    /// An entry was deleted
+    Delete,
+    /// Fields of an entry were modified
+    Modify,
+    /// Only the quantity of an entry was raised
+    Give,
+    /// Only the quantity of an entry was lowered
+    Take,
+}
+
+impl Operation {
+    /// Name of the operation as it's stored in the history table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "ADD",
+            Self::Delete => "DELETE",
+            Self::Modify => "MODIFY",
+            Self::Give => "GIVE",
+            Self::Take => "TAKE",
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Operation {
+    type Err = Box<dyn Error>;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        Ok(match string {
+            "ADD" => Self::Add,
+            "DELETE" => Self::Delete,
+            "MODIFY" => Self::Modify,
+            "GIVE" => Self::Give,
+            "TAKE" => Self::Take,
+            _ => bail!("Unknown operation {}!", string),
+        })
+    }
+}
+
+/// A change made to an entry, as recorded in the history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRecord {
+    /// When the change was made in unix time
+    pub timestamp: i64,
+    /// What kind of change it was
+    pub operation: Operation,
+    /// Catagory of the entry changed
+    pub catagory_id: String,
+    /// Key of the entry changed, the old key if it was rekeyed
+    pub key: u64,
+    /// What changed, like "QUANTITY: 50 -> 10". Empty for adds and deletes.
+    pub diff: String,
+}
+
+impl fmt::Display for HistoryRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Timestamps out of range are shown as they're stored
+        let time = match Local.timestamp_opt(self.timestamp, 0).single() {
+            Some(time) => time.to_string(),
+            None => self.timestamp.to_string(),
+        };
+
+        write!(
+            f,
+            "{} {:<6} {} {}",
+            time,
+            self.operation,
+            self.catagory_id,
+            b64::from_u64(self.key)
+        )?;
+
+        if !self.diff.is_empty() {
+            write!(f, " {}", self.diff)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Direction to sort entries in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    /// Smallest first
+    Ascending,
+    /// Largest first
+    Descending,
+}
+
+impl SortOrder {
+    pub fn to_sql(&self) -> String {
+        match self {
+            Self::Ascending => "ASC".to_owned(),
+            Self::Descending => "DESC".to_owned(),
+        }
+    }
+}
+
+/// Field to sort by when searching
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sort {
+    pub field_id: String,
+    pub order: SortOrder,
+}
+
+impl Sort {
+    pub fn new(field_id: &str, order: SortOrder) -> Self {
+        Self {
+            field_id: field_id.to_uppercase(),
+            order,
+        }
     }
 }
 
+/// Everything besides conditions that narrows down or shapes a search, see
+/// Db::search
+#[derive(Default)]
+struct SearchOptions<'a> {
+    /// Only return entries with these keys
+    keys: Option<&'a [u64]>,
+    /// Field to sort entries by
+    sort: Option<&'a Sort>,
+    /// Most entries to return
+    limit: Option<u64>,
+    /// Number of entries to skip
+    offset: Option<u64>,
+    /// Leave out heavy fields, see Db::light_fields
+    light: bool,
+}
+
 /// Datatypes in SQLite
 pub enum SQLValue {
     /// Null, nothing
@@ -149,12 +462,18 @@ pub enum SQLValue {
 }
 
 /// Used to define fields in catagories
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CatagoryField {
     /// id of the field, case insensitive
     pub id: String,
     /// pinv datatype of the field
     pub datatype: DataType,
+    /// If set, no two entries may share a value in the field
+    #[serde(default)]
+    pub unique: bool,
+    /// If set, the field can't be changed once it has a value
+    #[serde(default)]
+    pub immutable: bool,
 }
 
 impl CatagoryField {
@@ -162,8 +481,41 @@ impl CatagoryField {
     pub fn new(id: &str, datatype: DataType) -> Self {
         let id = id.to_owned();
 
-        Self { id, datatype }
+        Self {
+            id,
+            datatype,
+            unique: false,
+            immutable: false,
+        }
+    }
+
+    /// Mark the field as unique, see CatagoryField::unique
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Mark the field as immutable, see CatagoryField::immutable
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    /// Get the type of the field and convert it to it's SQL keyword
+    /// equivalent. E.g. a field with type integer would return "INTEGER"
+    pub fn sql_type(&self) -> String {
+        match &self.datatype {
+            DataType::NULL => "NULL".to_owned(),
+            DataType::INTEGER => "INTEGER".to_owned(),
+            DataType::REAL => "REAL".to_owned(),
+            DataType::TEXT => "TEXT".to_owned(),
+            DataType::BLOB => "BLOB".to_owned(),
+        }
     }
+}
+
+impl FromStr for CatagoryField {
+    type Err = Box<dyn Error>;
 
     /// Create a field from a string.
     ///
@@ -175,9 +527,9 @@ impl CatagoryField {
     /// `max_volts:r`
     ///
     /// would create a field named "max_volts" of type real.
-    pub fn from_str(string: &str) -> Result<Self, Box<dyn Error>> {
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
         // !TODO! Needs better code to detect if a string is valid or not
-        let split_str: Vec<&str> = string.split(":").collect();
+        let split_str: Vec<&str> = string.split(':').collect();
 
         // If the string was split more than once, or not at all, we got a problem!
         if split_str.len() != 2 {
@@ -189,28 +541,20 @@ impl CatagoryField {
         Ok(Self {
             id: split_str[0].to_owned().to_uppercase(), // Make it case insensitive by converting the id to uppercase
             datatype,
+            unique: false,
+            immutable: false,
         })
     }
+}
 
-    /// Get the type of the field and convert it to it's SQL keyword
-    /// equivalent. E.g. a field with type integer would return "INTEGER"
-    pub fn sql_type(&self) -> String {
-        match &self.datatype {
-            DataType::NULL => "NULL".to_owned(),
-            DataType::INTEGER => "INTEGER".to_owned(),
-            DataType::REAL => "REAL".to_owned(),
-            DataType::TEXT => "TEXT".to_owned(),
-            DataType::BLOB => "BLOB".to_owned(),
-        }
-    }
-
-    pub fn to_string(&self) -> String {
-        format!("{}:{}", self.id, self.datatype.get_char())
+impl fmt::Display for CatagoryField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.id, self.datatype.get_char())
     }
 }
 
 /// Used to help define catagories(which are translated directly into sql tables)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Catagory {
     /// ID of the catagory, case insensitive
     pub id: String,
@@ -265,6 +609,14 @@ impl fmt::Display for Catagory {
                 )
                 .as_str(),
             );
+
+            if field.unique {
+                out.push_str(" UNIQUE");
+            }
+
+            if field.immutable {
+                out.push_str(" IMMUTABLE");
+            }
         }
 
         write!(f, "{}", out)
@@ -289,6 +641,16 @@ impl EntryField {
         }
     }
 
+    /// Get the value, formatted for sql
+    ///
+    pub fn get_sql(&self) -> String {
+        self.value.clone()
+    }
+}
+
+impl FromStr for EntryField {
+    type Err = Box<dyn Error>;
+
     /// Create an entry field from a string.
     ///
     /// Format is *id*=*value*, where id is the case-insensitive field id and
@@ -299,24 +661,22 @@ impl EntryField {
     /// `max_volts=3.3`
     ///
     /// Assigns the "max_volts" field a value of 3.3
-    pub fn from_str(string: &str) -> Result<Self, Box<dyn Error>> {
-        let split_str: Vec<&str> = string.split("=").collect();
-
-        if split_str.len() != 2 {
-            bail!("Invalid entry field definition '{}'!", string);
-        }
+    ///
+    /// Only the first "=" separates the id from the value, so values like URLs
+    /// can contain "=" themselves.
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let (id, value) = match string.split_once('=') {
+            Some(split) => split,
+            None => {
+                bail!("Invalid entry field definition '{}'!", string);
+            }
+        };
 
         Ok(Self {
-            id: split_str[0].to_owned().to_uppercase(),
-            value: split_str[1].to_owned(),
+            id: id.to_uppercase(),
+            value: value.to_owned(),
         })
     }
-
-    /// Get the value, formatted for sql
-    ///
-    pub fn get_sql(&self) -> String {
-        self.value.clone()
-    }
 }
 
 /// Used to create database entries
@@ -372,10 +732,48 @@ impl Entry {
     pub fn add_fields(&mut self, fields: &[EntryField]) {
         self.fields.extend_from_slice(fields);
     }
+
+    /// Sort the fields of the entry into the given order, see
+    /// Db::display_order. Fields not in the order are put last.
+    pub fn sort_fields(&mut self, order: &[String]) {
+        self.fields.sort_by_key(|field| {
+            order
+                .iter()
+                .position(|id| *id == field.id)
+                .unwrap_or(order.len())
+        });
+    }
+
+    /// When the entry was created, in local time. None if the timestamp is
+    /// out of range, which imports and set_created can leave behind.
+    pub fn created_datetime(&self) -> Option<DateTime<Local>> {
+        Local.timestamp_opt(self.created, 0).single()
+    }
+
+    /// When the entry was last modified, in local time. None if the timestamp
+    /// is out of range.
+    pub fn modified_datetime(&self) -> Option<DateTime<Local>> {
+        Local.timestamp_opt(self.modified, 0).single()
+    }
+
+    /// When the entry was created, as an RFC 3339 string like
+    /// 2023-11-14T22:13:20+00:00. None if the timestamp is out of range.
+    pub fn created_rfc3339(&self) -> Option<String> {
+        self.created_datetime()
+            .map(|datetime| datetime.to_rfc3339())
+    }
 }
 
 impl fmt::Display for Entry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.formatted(&Config::default()))
+    }
+}
+
+impl Entry {
+    /// Format the entry like Display does, with the timestamps shown the way
+    /// the config asks for
+    pub fn formatted(&self, config: &Config) -> String {
         // Get the longest ID in all the fields
         let mut padlen: usize = 8; // Set the max size to 8(the length of the "location" field)
 
@@ -383,8 +781,8 @@ impl fmt::Display for Entry {
             padlen = cmp::max(padlen, field.id.len());
         }
 
-        let created_str = Local.timestamp_opt(self.created, 0).unwrap().to_string();
-        let modified_str = Local.timestamp_opt(self.modified, 0).unwrap().to_string();
+        let created_str = config.format_timestamp(self.created);
+        let modified_str = config.format_timestamp(self.modified);
 
         let mut out: String = format!(
             r#"ENTRY {}, CATAGORY {}:
@@ -415,10 +813,68 @@ impl fmt::Display for Entry {
             );
         }
 
-        write!(f, "{}", out)
+        out
+    }
+}
+
+/// An entry as it's exported to JSON. The key is base64 like everywhere else
+/// and the fields keep the types of their columns, empty fields being null.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonEntry {
+    /// Catagory the entry belongs to
+    pub catagory_id: String,
+    /// Key of the entry in base64
+    pub key: String,
+    /// Physical location of the entry
+    pub location: String,
+    /// Quantity of the entry
+    pub quantity: u64,
+    /// Creation time of the entry in unix time
+    pub created: i64,
+    /// Modification time of the entry in unix time
+    pub modified: i64,
+    /// Fields associated with the entry, by their ids
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl JsonEntry {
+    /// Convert back into an entry
+    pub fn into_entry(self) -> Result<Entry, Box<dyn Error>> {
+        let mut entry = Entry::new(
+            &self.catagory_id,
+            b64::to_u64(&self.key)?,
+            &self.location,
+            self.quantity,
+            self.created,
+            self.modified,
+        );
+
+        for (id, value) in self.fields {
+            let value = match value {
+                serde_json::Value::Null => "".to_owned(),
+                serde_json::Value::Number(number) => number.to_string(),
+                serde_json::Value::String(string) => string,
+                _ => bail!("Field {} must be a number, string, or null!", id),
+            };
+
+            entry.add_field(EntryField::new(&id, &value));
+        }
+
+        Ok(entry)
     }
 }
 
+/// A single entry shared on its own, along with the catagory it's in so the
+/// catagory can be made if whoever imports it doesn't have it yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedEntry {
+    /// Catagory the entry is in, if it's included
+    #[serde(default)]
+    pub catagory: Option<Catagory>,
+    /// The entry itself
+    pub entry: JsonEntry,
+}
+
 /// Used to interface with the pinv database. As of the current version, sqlite
 /// is used to store and retrieve entries but this may change in the future.
 pub struct Db {
@@ -427,9 +883,9 @@ pub struct Db {
 }
 
 impl Db {
-    /// Initialize the pinv database. The database file is located in the
-    /// current user's home data folder, or in a path specified.
-    pub fn init(path: Option<Arc<str>>) -> Self {
+    /// Initialize the pinv database. The database file is pinv.db3 in the
+    /// current user's home data folder, or in the directory specified.
+    pub fn init(path: Option<Arc<str>>) -> Result<Self, Box<dyn Error>> {
         let data_dir = match path {
             None => {
                 let qualifier = "org";
@@ -437,45 +893,87 @@ impl Db {
                 let application = "pinv";
 
                 // Get the home data directories depending on the system
-                let dirs = ProjectDirs::from(qualifier, organisation, application).unwrap();
+                let dirs = match ProjectDirs::from(qualifier, organisation, application) {
+                    Some(dirs) => dirs,
+                    None => bail!("Couldn't find a home directory to keep the database in!"),
+                };
+
                 dirs.data_dir().to_owned()
             }
             Some(path) => PathBuf::from(path.to_string()),
         };
 
         // Create the path to the datafile
-        let mut db_filepath = data_dir.clone();
+        let mut db_filepath = data_dir;
         db_filepath.push("pinv.db3");
 
+        Self::init_at(&db_filepath)
+    }
+
+    /// Initialize a pinv database in any file, creating the file and the
+    /// directory it's in if they don't exist.
+    pub fn init_at(db_filepath: &Path) -> Result<Self, Box<dyn Error>> {
         // If the data directory doesn't exist, create it
-        // !TODO! Replace unwrap with proper error handling, perhaps
-        if !data_dir.exists() {
-            fs::create_dir_all(data_dir.as_path()).unwrap();
+        if let Some(data_dir) = db_filepath.parent() {
+            if !data_dir.as_os_str().is_empty() && !data_dir.exists() {
+                if let Err(error) = fs::create_dir_all(data_dir) {
+                    bail!(
+                        "Couldn't create the data directory {}: {}",
+                        data_dir.display(),
+                        error
+                    );
+                }
+            }
         }
 
-        let connection = Connection::open(db_filepath).unwrap();
+        let connection = Self::open_connection(db_filepath)?;
 
         // Check to see if the keys table exists in the database...
         // !TODO! use statement or something instead of a raw query, or maybe
         // just ditch raw sql entirely...
         let query = "SELECT name FROM sqlite_master WHERE type='table' AND name='KEYS'";
 
-        match connection
-            .query_row(query, [], |_| Ok(()))
-            .optional()
-            .unwrap()
-        {
+        match connection.query_row(query, [], |_| Ok(())).optional()? {
             Some(_) => {}
             None => {
                 // In the case it doesn't exist, create it
                 let query =
                     "CREATE TABLE KEYS (KEY INTEGER NOT NULL PRIMARY KEY, CATAGORY TEXT NOT NULL)";
 
-                connection.execute(query, []).unwrap();
+                connection.execute(query, [])?;
             }
         }
 
-        Self { connection }
+        // Databases made before the metadata and history tables existed won't
+        // have them
+        connection.execute(METADATA_TABLE_QUERY, [])?;
+        connection.execute(FIELD_METADATA_TABLE_QUERY, [])?;
+        connection.execute(HISTORY_TABLE_QUERY, [])?;
+        Self::add_history_diff(&connection)?;
+        connection.execute(TRASH_TABLE_QUERY, [])?;
+
+        Ok(Self { connection })
+    }
+
+    /// Open a connection to a database file, set up to share it with other
+    /// connections. Every connection to a file has to be opened with this.
+    fn open_connection(db_filepath: &Path) -> Result<Connection, Box<dyn Error>> {
+        let connection = match Connection::open(db_filepath) {
+            Ok(connection) => connection,
+            Err(error) => bail!(
+                "Couldn't open the database {}: {}",
+                db_filepath.display(),
+                error
+            ),
+        };
+
+        // Let the TUI stay open while the CLI is writing from another process.
+        // Setting the journal mode returns the new mode, so it has to be
+        // queried instead of executed
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+
+        Ok(connection)
     }
 
     /// Create a database in RAM for testing purposes...
@@ -487,139 +985,693 @@ impl Db {
         let query = "CREATE TABLE KEYS (KEY INTEGER NOT NULL PRIMARY KEY, CATAGORY TEXT NOT NULL)";
 
         connection.execute(query, []).unwrap();
+        connection.execute(METADATA_TABLE_QUERY, []).unwrap();
+        connection.execute(FIELD_METADATA_TABLE_QUERY, []).unwrap();
+        connection.execute(HISTORY_TABLE_QUERY, []).unwrap();
+        connection.execute(TRASH_TABLE_QUERY, []).unwrap();
 
         Self { connection }
     }
 
-    /// Add a key to the key table.
-    fn add_key(&mut self, key: u64, catagory_id: &str) -> Result<(), Box<dyn Error>> {
-        let query = format!(
-            "INSERT INTO KEYS (KEY, CATAGORY)\nVALUES ({}, '{}')",
-            key, catagory_id
+    /// Fill an empty database with the sample RESISTOR and CAPACITOR
+    /// catagories and a few entries in each, so there's something to explore.
+    /// Databases with catagories or entries in them are left alone.
+    pub fn init_demo(&mut self) -> Result<(), Box<dyn Error>> {
+        let keys: u64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM KEYS", [], |row| row.get(0))?;
+
+        if keys > 0 || !self.list_catagories()?.is_empty() {
+            bail!("Demo data can only be added to an empty database!");
+        }
+
+        let resistor = Catagory::with_fields(
+            "RESISTOR",
+            vec![
+                CatagoryField::new("MPN", DataType::TEXT),
+                CatagoryField::new("OHMS", DataType::REAL),
+                CatagoryField::new("WATTS", DataType::REAL),
+                CatagoryField::new("CASE_CODE", DataType::TEXT),
+            ],
+        );
+        let capacitor = Catagory::with_fields(
+            "CAPACITOR",
+            vec![
+                CatagoryField::new("MPN", DataType::TEXT),
+                CatagoryField::new("FARADS", DataType::REAL),
+                CatagoryField::new("VOLTAGE_DC", DataType::REAL),
+                CatagoryField::new("CASE_CODE", DataType::TEXT),
+            ],
         );
 
-        self.connection.execute(&query, [])?;
+        // Key, catagory, location, quantity and values for the catagory's
+        // fields in order
+        let demo = [
+            (
+                0,
+                &resistor,
+                "drawer 1",
+                50,
+                ["RC0805FR-0710KL", "1e4", "1.25e-1", "0805"],
+            ),
+            (
+                1,
+                &resistor,
+                "drawer 1",
+                25,
+                ["CFR-25JB-52-220R", "2.2e2", "2.5e-1", "Axial"],
+            ),
+            (
+                2,
+                &capacitor,
+                "drawer 2",
+                100,
+                ["CL21B104KBCNNNC", "1e-7", "5e1", "0805"],
+            ),
+            (
+                3,
+                &capacitor,
+                "drawer 2",
+                10,
+                ["ECA-1HM100", "1e-5", "5e1", "Radial"],
+            ),
+        ];
+
+        let now = Local::now().timestamp();
+        let mut entries = Vec::new();
+
+        for (key, catagory, location, quantity, values) in demo {
+            let mut entry = Entry::new(&catagory.id, key, location, quantity, now, now);
+
+            for (field, value) in catagory.fields.iter().zip(values) {
+                entry.add_field(EntryField::new(&field.id, value));
+            }
+
+            entries.push(entry);
+        }
+
+        self.add_catagory(resistor)?;
+        self.add_catagory(capacitor)?;
+        self.add_entries(entries)?;
 
         Ok(())
     }
 
-    /// Swap a key for another in the key table
-    fn swap_key(&mut self, old_key: u64, new_key: u64) -> Result<(), Box<dyn Error>> {
-        let query = format!("UPDATE KEYS SET KEY={} WHERE KEY={}", new_key, old_key);
+    /// Open another connection to the same database file, so long operations
+    /// can be run on another thread.
+    pub fn reopen(&self) -> Result<Self, Box<dyn Error>> {
+        let path = match self.connection.path() {
+            Some(path) => path.to_owned(),
+            None => {
+                bail!("Database has no file to reopen!");
+            }
+        };
 
-        self.connection.execute(&query, [])?;
+        Ok(Self {
+            connection: Self::open_connection(&path)?,
+        })
+    }
+
+    /// Rebuild the database file to reclaim unused space. Can take a while on
+    /// big databases.
+    pub fn vacuum(&self) -> Result<(), Box<dyn Error>> {
+        self.connection.execute("VACUUM", [])?;
 
         Ok(())
     }
 
-    /// Add a catagory to the database.
-    ///
-    /// More or less just converts the catagory struct into an SQL table.
-    pub fn add_catagory(&mut self, catagory: Catagory) -> Result<(), Box<dyn Error>> {
-        // First make sure we have fields in our catagory...
-        if catagory.fields.len() == 0 {
-            bail!("No fields in catagory \"{}!\"", catagory.id);
-        }
+    /// Copy the whole database into a single file, which can be restored with
+    /// restore_from. The copy is consistent even while the database is in
+    /// use.
+    pub fn backup_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        self.connection.backup(DatabaseName::Main, path, None)?;
 
-        // Verify the catagory won't cause any problems...
-        Db::check_id_string(&catagory.id)?;
+        Ok(())
+    }
 
-        // Check to see if the table exists first...
-        let query = format!(
-            "SELECT name FROM sqlite_master WHERE type='table' AND name='{}';",
-            catagory.id
-        );
+    /// Replace everything in the database with the contents of a backup made
+    /// by backup_to.
+    pub fn restore_from(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        // Make sure it's actually a pinv database before overwriting anything
+        let backup = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
 
-        let query_result: Option<String> = self
-            .connection
-            .query_row(query.as_str(), [], |row| row.get(0))
-            .optional()?;
+        let query = "SELECT name FROM sqlite_master WHERE type='table' AND name='KEYS'";
+
+        if backup
+            .query_row(query, [], |_| Ok(()))
+            .optional()?
+            .is_none()
+        {
+            bail!("{} is not a pinv database!", path.display());
+        }
+
+        self.connection
+            .restore(DatabaseName::Main, path, None::<fn(Progress)>)?;
+
+        // Backups made before the metadata table existed won't have it
+        self.connection.execute(METADATA_TABLE_QUERY, [])?;
+        self.connection.execute(FIELD_METADATA_TABLE_QUERY, [])?;
+        self.connection.execute(HISTORY_TABLE_QUERY, [])?;
+        Self::add_history_diff(&self.connection)?;
+        self.connection.execute(TRASH_TABLE_QUERY, [])?;
+
+        Ok(())
+    }
+
+    /// Add the DIFF column to history tables made before it existed
+    fn add_history_diff(connection: &Connection) -> Result<(), Box<dyn Error>> {
+        let has_diff = connection
+            .prepare("SELECT * FROM HISTORY LIMIT 0")?
+            .column_names()
+            .contains(&"DIFF");
+
+        if !has_diff {
+            connection.execute(
+                "ALTER TABLE HISTORY ADD COLUMN DIFF TEXT NOT NULL DEFAULT ''",
+                [],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a key to the key table. Takes the connection so it can be part of
+    /// a transaction.
+    fn add_key(connection: &Connection, key: u64, catagory_id: &str) -> Result<(), Box<dyn Error>> {
+        connection
+            .execute(
+                "INSERT INTO KEYS (KEY, CATAGORY) VALUES (?, ?)",
+                params![key, catagory_id],
+            )
+            .map_err(|error| -> Box<dyn Error> {
+                match error.sqlite_error_code() {
+                    Some(ErrorCode::ConstraintViolation) => {
+                        format!("Key {} is already in use!", b64::from_u64(key)).into()
+                    }
+                    _ => error.into(),
+                }
+            })?;
+
+        Ok(())
+    }
+
+    /// Swap a key for another in the key table. Takes the connection so it can
+    /// be part of a transaction. Fails before changing anything if the new
+    /// key is already in use.
+    fn swap_key(connection: &Connection, old_key: u64, new_key: u64) -> Result<(), Box<dyn Error>> {
+        if old_key == new_key {
+            return Ok(());
+        }
+
+        let in_use = connection
+            .query_row("SELECT 1 FROM KEYS WHERE KEY=?", params![new_key], |_| {
+                Ok(())
+            })
+            .optional()?
+            .is_some();
+
+        if in_use {
+            bail!("Key {} is already in use!", b64::from_u64(new_key));
+        }
 
-        // If there was some result to the query, bail!
-        match query_result {
-            Some(_) => {
-                bail!("Catagory already found in database!");
+        connection.execute(
+            "UPDATE KEYS SET KEY=? WHERE KEY=?",
+            params![new_key, old_key],
+        )?;
+
+        Ok(())
+    }
+
+    /// Add a catagory to the database.
+    ///
+    /// More or less just converts the catagory struct into an SQL table.
+    pub fn add_catagory(&mut self, catagory: Catagory) -> Result<(), Box<dyn Error>> {
+        // Verify the catagory won't cause any problems...
+        Db::check_new_catagory(&catagory)?;
+
+        // Check to see if the table exists first...
+        if self.catagory_exists(&catagory.id)? {
+            bail!("Catagory already found in database!");
+        }
+
+        Self::create_catagory(&self.connection, &catagory)
+    }
+
+    /// Add a catagory along with entries in it, all in one transaction so
+    /// neither is kept if any of the entries can't be added. Returns the
+    /// number of entries added.
+    pub fn add_catagory_with_entries(
+        &mut self,
+        catagory: Catagory,
+        entries: Vec<Entry>,
+    ) -> Result<usize, Box<dyn Error>> {
+        Db::check_new_catagory(&catagory)?;
+
+        if self.catagory_exists(&catagory.id)? {
+            bail!("Catagory already found in database!");
+        }
+
+        let types = Self::catagory_field_types(&catagory);
+        let count = entries.len();
+        let mut inserts = Vec::with_capacity(count);
+
+        for entry in entries {
+            if entry.catagory_id != catagory.id {
+                bail!(
+                    "Entry {} is in {} instead of {}!",
+                    b64::from_u64(entry.key),
+                    entry.catagory_id,
+                    catagory.id
+                );
             }
 
-            _ => {}
+            inserts.push(Self::entry_insert(entry, &types)?);
+        }
+
+        let transaction = self.connection.transaction()?;
+
+        Self::create_catagory(&transaction, &catagory)?;
+
+        for insert in inserts {
+            Self::insert_entry(&transaction, insert)?;
         }
 
-        // Otherwise, add the catagory to the database
+        transaction.commit()?;
+
+        Ok(count)
+    }
+
+    /// Make the table of a catagory that's been checked with
+    /// check_new_catagory. Takes the connection so it can be part of a
+    /// transaction.
+    fn create_catagory(connection: &Connection, catagory: &Catagory) -> Result<(), Box<dyn Error>> {
         let mut query = format!("CREATE TABLE {} (KEY INTEGER NOT NULL PRIMARY KEY, LOCATION TEXT NOT NULL, QUANTITY INTEGER NOT NULL, CREATED INTEGER NOT NULL, MODIFIED INTEGER NOT NULL, ", catagory.id);
 
         for (i, field) in catagory.fields.iter().enumerate() {
-            // Verify that the field won't cause any problems...
-            Db::check_id_string(&field.id)?;
-
             query.push_str(format!("{} {}", field.id, field.sql_type()).as_str());
 
+            if field.unique {
+                query.push_str(" UNIQUE");
+            }
+
             if i < catagory.fields.len() - 1 {
                 query.push(',');
             }
         }
 
-        query.push_str(")");
+        query.push(')');
+
+        connection.execute(&query, [])?;
+
+        for field in catagory.fields.iter().filter(|field| field.immutable) {
+            Self::set_field_immutable(connection, &catagory.id, &field.id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remember that a field can't be changed once it has a value. Takes the
+    /// connection so it can be part of a transaction.
+    fn set_field_immutable(
+        connection: &Connection,
+        catagory_id: &str,
+        field_id: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        connection.execute(
+            "INSERT OR REPLACE INTO FIELD_METADATA (CATAGORY, FIELD, IMMUTABLE) VALUES (?, ?, 1)",
+            params![catagory_id, field_id],
+        )?;
+
+        Ok(())
+    }
+
+    /// Grab the fields of a catagory that can't be changed once they have a
+    /// value
+    pub fn immutable_fields(&self, catagory_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT FIELD FROM FIELD_METADATA WHERE CATAGORY=? AND IMMUTABLE=1 ORDER BY FIELD",
+        )?;
+
+        let fields = statement
+            .query_map(params![catagory_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        Ok(fields)
+    }
+
+    /// Add a field to a catagory that already exists. Entries already in the
+    /// catagory are left with nothing(NULL) in the new field.
+    pub fn add_catagory_field(
+        &mut self,
+        catagory_id: &str,
+        field: CatagoryField,
+    ) -> Result<(), Box<dyn Error>> {
+        // Both the catagory and the field are part of the query, so make sure
+        // they're valid
+        self.check_catagory(catagory_id)?;
+        Db::check_id_string(&field.id)?;
+
+        if self.grab_catagory_fields(catagory_id)?.contains(&field.id) {
+            bail!("Field {} already found in {}!", field.id, catagory_id);
+        }
+
+        // SQLite can't add UNIQUE columns to a table that already exists
+        if field.unique {
+            bail!("Unique fields can only be added when creating a catagory!");
+        }
+
+        let query = format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            catagory_id,
+            field.id,
+            field.sql_type()
+        );
 
         self.connection.execute(&query, [])?;
 
+        if field.immutable {
+            Self::set_field_immutable(&self.connection, catagory_id, &field.id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Rename a field in a catagory, keeping the values stored in it. The
+    /// builtin fields can't be renamed.
+    pub fn rename_catagory_field(
+        &mut self,
+        catagory_id: &str,
+        old_field: &str,
+        new_field: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        // All the ids are part of the query, so make sure they're valid
+        self.check_catagory(catagory_id)?;
+        Db::check_id_string(old_field)?;
+        Db::check_id_string(new_field)?;
+
+        if BUILTIN_FIELDS.contains(&old_field) || BUILTIN_FIELDS.contains(&new_field) {
+            bail!("Builtin fields can't be renamed!");
+        }
+
+        let fields = self.grab_catagory_fields(catagory_id)?;
+
+        if !fields.iter().any(|field| field == old_field) {
+            bail!("Field {} not found in {}!", old_field, catagory_id);
+        }
+
+        if fields.iter().any(|field| field == new_field) {
+            bail!("Field {} already found in {}!", new_field, catagory_id);
+        }
+
+        // The display order refers to fields by name, so it has to follow
+        let order: Vec<String> = self
+            .display_order(catagory_id)?
+            .into_iter()
+            .map(|field| match field == old_field {
+                true => new_field.to_owned(),
+                false => field,
+            })
+            .collect();
+
+        let has_order = self
+            .connection
+            .query_row(
+                "SELECT CATAGORY FROM METADATA WHERE CATAGORY=?",
+                params![catagory_id],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+
+        let query = format!(
+            "ALTER TABLE {} RENAME COLUMN {} TO {}",
+            catagory_id, old_field, new_field
+        );
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(&query, [])?;
+
+        transaction.execute(
+            "UPDATE FIELD_METADATA SET FIELD=? WHERE CATAGORY=? AND FIELD=?",
+            params![new_field, catagory_id, old_field],
+        )?;
+
+        // The order came from display_order, so it's already known to be good
+        if has_order {
+            transaction.execute(
+                "INSERT OR REPLACE INTO METADATA (CATAGORY, DISPLAY_ORDER) VALUES (?, ?)",
+                params![catagory_id, order.join(" ")],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Make sure a catagory id is valid and the catagory is in the database,
+    /// so it's safe to put in a query
+    fn check_catagory(&self, catagory_id: &str) -> Result<(), Box<dyn Error>> {
+        Db::check_catagory_id(catagory_id)?;
+
+        if !self.catagory_exists(catagory_id)? {
+            bail!("Catagory {} not found!", catagory_id);
+        }
+
         Ok(())
     }
 
+    /// Check if a catagory is in the database
+    fn catagory_exists(&self, catagory_id: &str) -> Result<bool, Box<dyn Error>> {
+        let query = "SELECT name FROM sqlite_master WHERE type='table' AND name=?";
+
+        let query_result: Option<String> = self
+            .connection
+            .query_row(query, params![catagory_id], |row| row.get(0))
+            .optional()?;
+
+        Ok(query_result.is_some())
+    }
+
     /// Add an entry to the database.
     ///
     /// More or less just converts the entry struct into SQL.
     pub fn add_entry(&mut self, entry: Entry) -> Result<(), Box<dyn Error>> {
-        // Check and make sure the location is a valid string, and format it...
-        let location =
-            self.format_string_to_field(&entry.catagory_id, "LOCATION", &entry.location)?;
-        let mut query_a = format!(
-            "INSERT INTO {} (KEY, LOCATION, QUANTITY, CREATED, MODIFIED",
-            entry.catagory_id
-        );
+        self.add_entries(vec![entry])?;
 
-        let mut query_b = format!(
-            ")\nVALUES ({}, {}, {}, {}, {}",
-            entry.key, location, entry.quantity, entry.created, entry.modified
-        );
+        Ok(())
+    }
+
+    /// Add many entries to the database at once, returning the number of
+    /// entries added. Either every entry is added or none of them are, and
+    /// it's a lot faster than adding them one by one.
+    pub fn add_entries(&mut self, entries: Vec<Entry>) -> Result<usize, Box<dyn Error>> {
+        self.insert_entries(entries, true)
+    }
+
+    /// Check that entries could be added without adding them, returning the
+    /// number of entries checked. Every check add_entries makes is done,
+    /// including for keys already in use and unique fields.
+    pub fn check_entries(&mut self, entries: Vec<Entry>) -> Result<usize, Box<dyn Error>> {
+        self.insert_entries(entries, false)
+    }
+
+    /// Insert entries in a single transaction, which is only committed if
+    /// commit is set. Otherwise everything is rolled back once the inserts
+    /// have been checked.
+    fn insert_entries(
+        &mut self,
+        entries: Vec<Entry>,
+        commit: bool,
+    ) -> Result<usize, Box<dyn Error>> {
+        let count = entries.len();
+
+        // Build every insert first, the transaction needs the connection to
+        // itself
+        let mut inserts = Vec::with_capacity(count);
+        let mut catagory_types = HashMap::<String, Vec<(String, DataType)>>::new();
+
+        for entry in entries {
+            if !catagory_types.contains_key(&entry.catagory_id) {
+                let types = self.field_types(&entry.catagory_id)?;
+
+                catagory_types.insert(entry.catagory_id.clone(), types);
+            }
+
+            let types = &catagory_types[&entry.catagory_id];
+
+            inserts.push(Self::entry_insert(entry, types)?);
+        }
+
+        let transaction = self.connection.transaction()?;
+
+        for insert in inserts {
+            Self::insert_entry(&transaction, insert)?;
+        }
+
+        if commit {
+            transaction.commit()?;
+        }
+
+        Ok(count)
+    }
+
+    /// Insert an entry built with entry_insert along with its key, and record
+    /// it in the history. Takes the connection so it can be part of a
+    /// transaction.
+    fn insert_entry(connection: &Connection, insert: EntryInsert) -> Result<(), Box<dyn Error>> {
+        let (key, catagory_id, fields, (query, values)) = insert;
+
+        Self::add_key(connection, key, &catagory_id)?;
+        connection
+            .execute(&query, params_from_iter(values))
+            .map_err(|error| Self::explain_unique_violation(error, &fields))?;
+        Self::record_history(connection, Operation::Add, &catagory_id, key, "")?;
+
+        Ok(())
+    }
+
+    /// Build the query inserting an entry into its catagory, along with
+    /// everything insert_entry needs. The types are the fields of the
+    /// catagory and their types, see field_types.
+    fn entry_insert(
+        entry: Entry,
+        types: &[(String, DataType)],
+    ) -> Result<EntryInsert, Box<dyn Error>> {
+        // The catagory is part of the query, so make sure it's valid
+        Db::check_id_string(&entry.catagory_id)?;
+
+        let mut columns = vec![
+            "KEY".to_owned(),
+            "LOCATION".to_owned(),
+            "QUANTITY".to_owned(),
+            "CREATED".to_owned(),
+            "MODIFIED".to_owned(),
+        ];
+
+        // Check and make sure the location is a valid string...
+        let location = Self::typed_value(types, &entry.catagory_id, "LOCATION", &entry.location)?;
+
+        let mut values: Vec<Box<dyn ToSql>> = vec![
+            Box::new(entry.key),
+            Box::new(location),
+            Box::new(entry.quantity),
+            Box::new(entry.created),
+            Box::new(entry.modified),
+        ];
+
+        for field in &entry.fields {
+            // Verify they are valid names and types...
+            Db::check_id_string(&field.id)?;
 
-        for field in entry.fields {
-            let field_id = field.id;
             let field_value =
-                self.format_string_to_field(&entry.catagory_id, &field_id, &field.value)?;
+                Self::typed_value(types, &entry.catagory_id, &field.id, &field.value)?;
 
-            // Skip this field if the value is null
-            if field_value.len() == 0 {
+            columns.push(field.id.clone());
+            values.push(Box::new(field_value));
+        }
+
+        let query = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            entry.catagory_id,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+
+        Ok((entry.key, entry.catagory_id, entry.fields, (query, values)))
+    }
+
+    /// Replace the values of blob fields that aren't hex with the contents of
+    /// the file they name, as hex. Only for values typed in by the user, the
+    /// database itself never reads files so imported data can't pull them in.
+    pub fn read_blob_files(
+        &self,
+        catagory_id: &str,
+        fields: &mut [EntryField],
+    ) -> Result<(), Box<dyn Error>> {
+        let types = self.field_types(catagory_id)?;
+
+        for field in fields {
+            if field.value.is_empty() || field.value.starts_with("0x") {
                 continue;
             }
-            // Verify they are valid names and types...
-            Db::check_id_string(&field_id)?;
 
-            query_a.push(',');
-            query_b.push(',');
-            query_a.push_str(&field_id);
-            query_b.push_str(&field_value);
+            let is_blob = types
+                .iter()
+                .any(|(id, datatype)| id == &field.id && *datatype == DataType::BLOB);
+
+            if !is_blob {
+                continue;
+            }
+
+            match fs::read(&field.value) {
+                Ok(data) => field.value = Self::blob_to_hex(&data),
+                Err(error) => {
+                    bail!("Couldn't read {}: {}!", field.value, error);
+                }
+            }
         }
 
-        query_b.push(')');
-        query_a.push_str(query_b.as_str());
+        Ok(())
+    }
+
+    /// Grab every field of a catagory along with its type
+    fn field_types(&self, catagory_id: &str) -> Result<Vec<(String, DataType)>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(catagory_id)?;
+        let types = self.grab_catagory_types(catagory_id)?;
+
+        Ok(fields.into_iter().zip(types).collect())
+    }
+
+    /// The fields of a catagory that isn't in the database yet along with
+    /// their types, the same as field_types would give once it's added
+    fn catagory_field_types(catagory: &Catagory) -> Vec<(String, DataType)> {
+        let mut types: Vec<(String, DataType)> = BUILTIN_FIELDS
+            .iter()
+            .map(|field| match *field {
+                "LOCATION" => (field.to_string(), DataType::TEXT),
+                _ => (field.to_string(), DataType::INTEGER),
+            })
+            .collect();
 
-        let query = query_a;
+        types.extend(
+            catagory
+                .fields
+                .iter()
+                .map(|field| (field.id.clone(), field.datatype)),
+        );
 
-        // Add the key to the key table
-        self.add_key(entry.key, &entry.catagory_id)?;
+        types
+    }
 
-        match self.connection.execute(&query, []) {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                self.remove_key(entry.key).unwrap();
+    /// Convert a string to a value of a field, given the fields of its
+    /// catagory and their types. Blank strings are NULL.
+    fn typed_value(
+        types: &[(String, DataType)],
+        catagory_id: &str,
+        field_id: &str,
+        field_value: &str,
+    ) -> Result<Value, Box<dyn Error>> {
+        if field_value.is_empty() {
+            return Ok(Value::Null);
+        }
 
-                Err(Box::new(e))
+        match types.iter().find(|(id, _)| id == field_id) {
+            Some((_, datatype)) => Db::value_from_string(field_value, *datatype),
+            None => {
+                bail!("Field {} not found in {}!", field_id, catagory_id);
             }
         }
     }
 
-    /// Get an entry from a query string
-    pub fn query_to_entry(&self, query: &str, catagory_id: &str) -> Result<Entry, Box<dyn Error>> {
+    /// Get an entry from a query string and the parameters bound to it
+    pub fn query_to_entry<P: Params>(
+        &self,
+        query: &str,
+        params: P,
+        catagory_id: &str,
+    ) -> Result<Entry, Box<dyn Error>> {
         let mut statement = self.connection.prepare(query)?;
         let mut column_names = Vec::<String>::new();
 
@@ -629,7 +1681,7 @@ impl Db {
 
         // Assumes the key and other mandatory entry fields are in the same
         // column. Shouldn't change, right?
-        Ok(statement.query_row([], |row| {
+        let mut entry = statement.query_row(params, |row| {
             let mut entry = Entry::new(
                 catagory_id,
                 row.get(0).unwrap(),
@@ -643,7 +1695,7 @@ impl Db {
             let mut i: usize = 5;
             loop {
                 let value: String = match row.get_ref(i) {
-                    Ok(result) => format!("{}", Self::sqlval_to_string(result)),
+                    Ok(result) => Self::sqlval_to_string(result).to_string(),
                     Err(e) => match e {
                         // Break if we ran out of columns
                         SqlError::InvalidColumnIndex(_) => {
@@ -662,26 +1714,58 @@ impl Db {
             }
 
             Ok(entry)
-        })?)
+        })?;
+
+        entry.sort_fields(&self.display_order(catagory_id)?);
+
+        Ok(entry)
     }
 
-    /// Get entries from a query
-    pub fn query_to_entries(
+    /// Get entries from a query string and the parameters bound to it. If a
+    /// limit or offset is given only that window of entries is returned, and
+    /// the limit is capped to MAX_PAGE_LEN.
+    pub fn query_to_entries<P: Params>(
         &self,
         query: &str,
+        params: P,
         catagory_id: &str,
+        limit: Option<u64>,
+        offset: Option<u64>,
     ) -> Result<Vec<Entry>, Box<dyn Error>> {
-        let mut statement = self.connection.prepare(query)?;
+        let query = match (limit, offset) {
+            (None, None) => query.to_owned(),
+            _ => {
+                let limit = limit.unwrap_or(MAX_PAGE_LEN).min(MAX_PAGE_LEN);
+                let offset = offset.unwrap_or(0);
+
+                // Offsets past i64::MAX can't be bound, and would be empty
+                // anyway
+                if i64::try_from(offset).is_err() {
+                    bail!("Offset {} is too large!", offset);
+                }
+
+                format!(
+                    "{} LIMIT {} OFFSET {};",
+                    query.trim_end().trim_end_matches(';'),
+                    limit,
+                    offset
+                )
+            }
+        };
+
+        let mut statement = self.connection.prepare(&query)?;
         let mut column_names = Vec::<String>::new();
 
         for name in statement.column_names() {
             column_names.push(name.to_string())
         }
 
-        let mut rows = statement.query([])?;
+        let mut rows = statement.query(params)?;
 
         let mut entries = Vec::<Entry>::new();
 
+        let order = self.display_order(catagory_id)?;
+
         while let Some(row) = rows.next()? {
             let mut entry = Entry::new(
                 catagory_id,
@@ -696,7 +1780,7 @@ impl Db {
 
             loop {
                 let value: String = match row.get_ref(i) {
-                    Ok(result) => format!("{}", Self::sqlval_to_string(result)),
+                    Ok(result) => Self::sqlval_to_string(result).to_string(),
                     Err(e) => match e {
                         SqlError::InvalidColumnIndex(_) => {
                             break;
@@ -712,6 +1796,8 @@ impl Db {
                 i += 1;
             }
 
+            entry.sort_fields(&order);
+
             entries.push(entry);
         }
 
@@ -720,6 +1806,8 @@ impl Db {
 
     /// Grab the ids of the fields in a catagory.
     pub fn grab_catagory_fields(&self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        self.check_catagory(name)?;
+
         let statement = self
             .connection
             .prepare(&format!("SELECT * FROM {}", name))?;
@@ -732,988 +1820,4259 @@ impl Db {
         Ok(column_names)
     }
 
-    /// Grab the types of the fields in a catagory.
-    ///
-    /// !TODO! Change the return type to the DataType enum.
-    pub fn grab_catagory_types(&self, name: &str) -> Result<Vec<DataType>, Box<dyn Error>> {
-        let mut statement = self
-            .connection
-            .prepare(&format!("PRAGMA table_info({})", name))?;
-
-        let mut rows = statement.query([])?;
-        let mut types = Vec::<DataType>::new();
+    /// Grab the ids of the fields in a catagory, optionally leaving out the
+    /// fields every catagory has(KEY, LOCATION, QUANTITY, CREATED, MODIFIED)
+    pub fn grab_catagory_fields_opts(
+        &self,
+        name: &str,
+        include_builtins: bool,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(name)?;
 
-        while let Some(row) = rows.next()? {
-            let type_str: String = row.get(2)?;
-            match type_str.as_str() {
-                "INTEGER" => types.push(DataType::INTEGER),
-                "REAL" => types.push(DataType::REAL),
-                _ => types.push(DataType::TEXT),
-            }
+        if include_builtins {
+            return Ok(fields);
         }
 
-        Ok(types)
+        Ok(fields
+            .into_iter()
+            .filter(|field| !BUILTIN_FIELDS.contains(&field.as_str()))
+            .collect())
     }
 
-    /// Grab the catagory associated with a key.
-    pub fn grab_catagory_from_key(&self, key: u64) -> Result<String, Box<dyn Error>> {
-        let query = format!("SELECT CATAGORY FROM KEYS WHERE KEY={}", key);
+    /// Grab the ids of the fields in a catagory that are loaded by light
+    /// searches, which is every field except blobs. Fields are in display
+    /// order, see display_order.
+    pub fn light_fields(&self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(name)?;
+        let types = self.grab_catagory_types(name)?;
+
+        let light: Vec<String> = fields
+            .into_iter()
+            .zip(types)
+            .filter(|(_, datatype)| *datatype != DataType::BLOB)
+            .map(|(field, _)| field)
+            .collect();
+
+        Ok(self
+            .display_fields(name)?
+            .into_iter()
+            .filter(|field| light.contains(field))
+            .collect())
+    }
 
-        Ok(self.connection.query_row(&query, [], |row| row.get(0))?)
+    /// Grab the ids of the fields in a catagory that hold a certain type
+    pub fn fields_of_type(
+        &self,
+        name: &str,
+        datatype: DataType,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(name)?;
+        let types = self.grab_catagory_types(name)?;
+
+        Ok(fields
+            .into_iter()
+            .zip(types)
+            .filter(|(_, field_type)| *field_type == datatype)
+            .map(|(field, _)| field)
+            .collect())
     }
 
-    /// Grab an entry using only a key
-    pub fn grab_entry(&self, key: u64) -> Result<Entry, Box<dyn Error>> {
-        // First get the catagory the entry is in
-        let catagory = self.grab_catagory_from_key(key)?;
+    /// Grab the ids of the fields in a catagory in the order they're
+    /// displayed in, builtin fields first. See display_order.
+    pub fn display_fields(&self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut fields: Vec<String> = BUILTIN_FIELDS.iter().map(|x| x.to_string()).collect();
 
-        // Next grab the entry from the catagory
-        let query = format!("SELECT * FROM {} WHERE KEY={}", catagory, key);
+        fields.extend(self.display_order(name)?);
 
-        self.query_to_entry(&query, &catagory)
+        Ok(fields)
     }
 
-    /// Get the next unused key in the database
-    pub fn grab_next_available_key(&self, key: u64) -> Result<u64, Box<dyn Error>> {
-        // Prepare a statement where the key provided is seached for in the
-        // key table.
-        //
-        // !TODO! Can be optimised.
-        let mut statement = self
+    /// Grab the order the fields of a catagory are displayed in, leaving out
+    /// the builtin fields. Fields in the order set by set_display_order come
+    /// first, followed by any other fields in the order they were added to the
+    /// catagory.
+    pub fn display_order(&self, catagory_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields_opts(catagory_id, false)?;
+
+        let stored: Option<String> = self
             .connection
-            .prepare("SELECT KEY FROM KEYS WHERE KEY = ?")?;
+            .query_row(
+                "SELECT DISPLAY_ORDER FROM METADATA WHERE CATAGORY=?",
+                params![catagory_id],
+                |row| row.get(0),
+            )
+            .optional()?;
 
-        let mut key = key;
+        // Fields could have been added since the order was set, and they
+        // still need to be shown
+        let mut order: Vec<String> = match stored {
+            Some(stored) => stored
+                .split_whitespace()
+                .filter(|field| fields.iter().any(|x| x == field))
+                .map(|field| field.to_owned())
+                .collect(),
+            None => Vec::new(),
+        };
 
-        loop {
-            match statement
-                .query_row(rusqlite::params![key], |_| Ok(()))
-                .optional()?
-            {
-                // If the key is in the table, increment the key and loop
-                Some(_) => {}
-                // Otherwise break and return the key
-                None => {
-                    break;
-                }
+        for field in fields {
+            if !order.contains(&field) {
+                order.push(field);
             }
-
-            key += 1
         }
 
-        Ok(key)
+        Ok(order)
     }
 
-    /// Get all the catagories in the database.
-    pub fn list_catagories(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        // Select all tables excluding the keys table
-        let mut statement = self.connection.prepare(
-            "SELECT name FROM sqlite_master WHERE type='table' AND name!='KEYS' ORDER BY name;",
+    /// Set the order the fields of a catagory are displayed in. Only the
+    /// fields that aren't builtin can be ordered, and any left out are shown
+    /// after the ones given. An empty order goes back to the order the fields
+    /// were added in. The fields are still stored in the same order.
+    pub fn set_display_order(
+        &mut self,
+        catagory_id: &str,
+        order: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        self.check_catagory(catagory_id)?;
+
+        let fields = self.grab_catagory_fields_opts(catagory_id, false)?;
+
+        for (i, field) in order.iter().enumerate() {
+            if !fields.contains(field) {
+                bail!("Field {} not found in {}!", field, catagory_id);
+            }
+
+            if order[..i].contains(field) {
+                bail!("Field {} is in the order twice!", field);
+            }
+        }
+
+        if order.is_empty() {
+            self.connection.execute(
+                "DELETE FROM METADATA WHERE CATAGORY=?",
+                params![catagory_id],
+            )?;
+
+            return Ok(());
+        }
+
+        self.connection.execute(
+            "INSERT OR REPLACE INTO METADATA (CATAGORY, DISPLAY_ORDER) VALUES (?, ?)",
+            params![catagory_id, order.join(" ")],
         )?;
 
-        let mut rows = statement.query([])?;
+        Ok(())
+    }
 
-        let mut names = Vec::<String>::new();
+    /// Grab the ids of the fields in a catagory marked unique when it was made
+    pub fn unique_fields(&self, catagory_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        self.check_catagory(catagory_id)?;
 
-        while let Some(row) = rows.next()? {
-            names.push(row.get(0)?);
+        let mut statement = self
+            .connection
+            .prepare(&format!("PRAGMA index_list({})", catagory_id))?;
+
+        // Only look at the indexes made by UNIQUE constraints
+        let indexes = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(1)?, row.get::<_, String>(3)?))
+            })?
+            .collect::<Result<Vec<(String, String)>, _>>()?;
+
+        let mut fields = Vec::<String>::new();
+
+        for (index, origin) in indexes {
+            if origin != "u" {
+                continue;
+            }
+
+            let mut statement = self
+                .connection
+                .prepare(&format!("PRAGMA index_info('{}')", index))?;
+
+            let columns = statement
+                .query_map([], |row| row.get::<_, String>(2))?
+                .collect::<Result<Vec<String>, _>>()?;
+
+            if let [field] = columns.as_slice() {
+                fields.push(field.clone());
+            }
         }
 
-        Ok(names)
+        Ok(fields)
     }
 
-    /// Get the stats of all catagories in the database. Currently only
-    /// retrieves name and number of entries in a catagory.
-    pub fn stat_catagories(&self) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
-        let catagories = self.list_catagories()?;
+    /// Grab a catagory from the database, with every field that isn't builtin
+    pub fn grab_catagory(&self, catagory_id: &str) -> Result<Catagory, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(catagory_id)?;
+        let types = self.grab_catagory_types(catagory_id)?;
+        let unique = self.unique_fields(catagory_id)?;
+        let immutable = self.immutable_fields(catagory_id)?;
 
-        let mut catagory_table = Vec::<Vec<String>>::with_capacity(catagories.len());
+        let mut catagory = Catagory::new(catagory_id);
 
-        for catagory in catagories {
-            let count: usize = self.connection.query_row(
-                &format!("SELECT COUNT(*) FROM {}", catagory),
-                [],
-                |row| row.get(0),
-            )?;
+        for (id, datatype) in fields.into_iter().zip(types) {
+            if BUILTIN_FIELDS.contains(&id.as_str()) {
+                continue;
+            }
 
-            let catagory_row = vec![catagory, count.to_string()];
+            let mut field = CatagoryField::new(&id, datatype);
+            field.unique = unique.contains(&id);
+            field.immutable = immutable.contains(&id);
 
-            catagory_table.push(catagory_row);
+            catagory.add_field(field);
         }
 
-        Ok(catagory_table)
+        Ok(catagory)
     }
 
-    /// Delete an empty catagory
-    pub fn delete_empty_catagory(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
-        // First check to see if the catagory is empty
-        let entries = self.search_catagory(name, &Vec::new())?;
+    /// Grab the types of the fields in a catagory.
+    ///
+    /// !TODO! Change the return type to the DataType enum.
+    pub fn grab_catagory_types(&self, name: &str) -> Result<Vec<DataType>, Box<dyn Error>> {
+        self.check_catagory(name)?;
 
-        if entries.len() != 0 {
-            bail!("Catagory \"{}\" is not empty!", name);
+        let mut statement = self
+            .connection
+            .prepare(&format!("PRAGMA table_info({})", name))?;
+
+        let mut rows = statement.query([])?;
+        let mut types = Vec::<DataType>::new();
+
+        while let Some(row) = rows.next()? {
+            let type_str: String = row.get(2)?;
+            match type_str.as_str() {
+                "INTEGER" => types.push(DataType::INTEGER),
+                "REAL" => types.push(DataType::REAL),
+                "BLOB" => types.push(DataType::BLOB),
+                _ => types.push(DataType::TEXT),
+            }
         }
 
-        let query = format!("DROP TABLE {}", name);
+        Ok(types)
+    }
 
-        self.connection.execute(&query, [])?;
+    /// Grab the catagory associated with a key.
+    pub fn grab_catagory_from_key(&self, key: u64) -> Result<String, Box<dyn Error>> {
+        let query = "SELECT CATAGORY FROM KEYS WHERE KEY=?";
 
-        Ok(())
+        Ok(self
+            .connection
+            .query_row(query, params![key], |row| row.get(0))?)
     }
 
-    /// Delete an entry given only the key
-    pub fn delete_entry(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
-        // First, get the catagory the entry is in
+    /// Grab an entry using only a key
+    pub fn grab_entry(&self, key: u64) -> Result<Entry, Box<dyn Error>> {
+        // First get the catagory the entry is in
         let catagory = self.grab_catagory_from_key(key)?;
 
-        // Next delete the entry from the catagory
-        let query = format!("DELETE FROM {} WHERE KEY={}", catagory, key);
+        // Next grab the entry from the catagory
+        let query = format!("SELECT * FROM {} WHERE KEY=?", catagory);
 
-        self.connection.execute(&query, [])?;
+        self.query_to_entry(&query, params![key], &catagory)
+    }
 
-        // Delete the key
-        self.remove_key(key).unwrap();
-        Ok(())
+    /// Grab an entry, looking in the catagory it's probably in first. This
+    /// skips the key table when the guess is right, and falls back to
+    /// grab_entry when the entry isn't in the catagory.
+    pub fn grab_entry_in(&self, key: u64, catagory_hint: &str) -> Result<Entry, Box<dyn Error>> {
+        Db::check_catagory_id(catagory_hint)?;
+
+        if self.catagory_exists(catagory_hint)? {
+            let query = format!("SELECT * FROM {} WHERE KEY=?", catagory_hint);
+
+            match self.query_to_entry(&query, params![key], catagory_hint) {
+                Ok(entry) => return Ok(entry),
+                Err(error) => {
+                    if !matches!(
+                        error.downcast_ref::<SqlError>(),
+                        Some(SqlError::QueryReturnedNoRows)
+                    ) {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        self.grab_entry(key)
     }
 
-    /// Return entries in a catagory that match the given conditions
-    pub fn search_catagory(
-        &self,
-        catagory_id: &str,
-        conditions: &[Condition],
-    ) -> Result<Vec<Entry>, Box<dyn Error>> {
-        if conditions.len() == 0 {
-            let query = format!("SELECT * FROM {}", catagory_id);
+    /// Grab the raw bytes of a blob field in an entry. Entries with no value in
+    /// the field give an empty blob.
+    pub fn grab_entry_blob(&self, key: u64, field_id: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        // The field is part of the query, so make sure it's valid
+        Db::check_id_string(field_id)?;
 
-            return self.query_to_entries(&query, catagory_id);
-        }
+        let catagory = self.grab_catagory_from_key(key)?;
 
-        let mut query = format!("SELECT * FROM {} WHERE ", catagory_id);
+        if self.field_type(&catagory, field_id)? != DataType::BLOB {
+            bail!("Field {} in {} is not a blob!", field_id, catagory);
+        }
 
-        for (i, condition) in conditions.iter().enumerate() {
-            let field_id = &condition.field_id;
-            let operator = condition.operator.to_sql();
-            let value = &condition.value;
+        let query = format!("SELECT {} FROM {} WHERE KEY=?", field_id, catagory);
 
-            // Make sure the field_id is valid and format the value
-            Self::check_id_string(&field_id)?;
-            let value = Self::format_string_to_field(&self, catagory_id, &field_id, &value)?;
+        let data: Option<Vec<u8>> = self
+            .connection
+            .query_row(&query, params![key], |row| row.get(0))?;
 
-            query.push_str(&format!("{}{}{}", field_id, operator, value));
+        Ok(data.unwrap_or_default())
+    }
 
-            // Check to see if we are at the end of the list of conditions,
-            // and add a semicolor if close
-            query.push_str(match i.cmp(&(conditions.len() - 1)) {
-                cmp::Ordering::Less => " AND ",
-                _ => ";",
-            })
-        }
+    /// Get the next unused key in the database
+    pub fn grab_next_available_key(&self, key: u64) -> Result<u64, Box<dyn Error>> {
+        // The key itself if it's free, otherwise the end of the first run of
+        // used keys after it, found in one query instead of probing each key
+        let query = "SELECT CASE
+                WHEN NOT EXISTS (SELECT 1 FROM KEYS WHERE KEY = ?1) THEN ?1
+                ELSE (
+                    SELECT MIN(USED.KEY) + 1 FROM KEYS AS USED
+                    WHERE USED.KEY >= ?1
+                    AND NOT EXISTS (SELECT 1 FROM KEYS WHERE KEY = USED.KEY + 1)
+                )
+            END";
 
-        self.query_to_entries(&query, catagory_id)
+        Ok(self
+            .connection
+            .query_row(query, params![key], |row| row.get(0))?)
     }
 
-    /// Take an SVG template and fill it with all available keys
-    pub fn fill_svg_template(&self, data: &str) -> Result<String, Box<dyn Error>> {
-        let chunks: Vec<String> = data.split("FOO!").map(|chunk| chunk.to_owned()).collect();
+    /// Get a key that's never been used, one past the largest key in use or
+    /// recorded in the history. Unlike grab_next_available_key deleted keys
+    /// aren't given out again, so old labels can't be mistaken for new
+    /// entries. Keys deleted before the history was kept can still come back.
+    pub fn grab_max_key_plus_one(&self) -> Result<u64, Box<dyn Error>> {
+        let query = "SELECT COALESCE(MAX(KEY) + 1, 0) FROM (SELECT KEY FROM KEYS UNION ALL SELECT KEY FROM HISTORY)";
 
-        let mut data = String::new();
-        let mut key = 0;
+        Ok(self.connection.query_row(query, [], |row| row.get(0))?)
+    }
 
-        for (i, chunk) in chunks.iter().enumerate() {
-            data.push_str(&chunk);
+    /// Every key in use along with the catagory it's in, in key order
+    pub fn all_keys(&self) -> Result<Vec<(u64, String)>, Box<dyn Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT KEY, CATAGORY FROM KEYS ORDER BY KEY")?;
 
-            key = self.grab_next_available_key(key)?;
-            if i < chunks.len() - 1 {
-                data.push_str(&b64::from_u64(key));
-            }
+        let mut rows = statement.query([])?;
 
-            key += 1
+        let mut keys = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            keys.push((row.get(0)?, row.get(1)?));
         }
 
-        Ok(data)
+        Ok(keys)
     }
 
-    /// Remove a key from the key table
-    fn remove_key(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
-        let query = format!("DELETE FROM KEYS WHERE KEY={}", key);
+    /// Get all the catagories in the database, sorted by name.
+    pub fn list_catagories(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        self.list_catagories_sorted(SortOrder::Ascending)
+    }
 
-        self.connection.execute(&query, [])?;
+    /// Get all the catagories in the database, sorted by name in the given
+    /// order. Names are compared ignoring case.
+    pub fn list_catagories_sorted(&self, order: SortOrder) -> Result<Vec<String>, Box<dyn Error>> {
+        // Select all tables excluding the ones pinv keeps for itself. Names
+        // that only differ in case are sorted by case so the order is stable
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT name FROM sqlite_master WHERE type='table' AND name NOT IN ({reserved}) ORDER BY name COLLATE NOCASE {order}, name {order};",
+            reserved = vec!["?"; RESERVED_TABLES.len()].join(", "),
+            order = order.to_sql()
+        ))?;
 
-        Ok(())
-    }
+        let mut rows = statement.query(params_from_iter(RESERVED_TABLES))?;
 
-    /// Modify a entry with only a key and the fields to be modified
-    pub fn mod_entry(&mut self, key: u64, fields: Vec<EntryField>) -> Result<(), Box<dyn Error>> {
-        // First get the catagory the entry is in
-        let catagory = self.grab_catagory_from_key(key)?;
-        let mod_time_string = Local::now().timestamp().to_string();
+        let mut names = Vec::<String>::new();
 
-        let mut fields_str = format!("MODIFIED={},", mod_time_string);
+        while let Some(row) = rows.next()? {
+            names.push(row.get(0)?);
+        }
 
-        let mut new_key: Option<u64> = Option::None;
+        Ok(names)
+    }
 
-        for (i, field) in fields.iter().enumerate() {
-            // If the key is being modified, we need to update the key table
-            let field_value = match field.id.as_str() {
-                "KEY" => {
-                    let field_value = b64::to_u64(&field.value)?;
+    /// Get the stats of all catagories in the database as a table. Currently
+    /// only retrieves name and number of entries in a catagory.
+    pub fn stat_catagories(&self) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+        Ok(self
+            .catagory_stats()?
+            .into_iter()
+            .map(|stats| vec![stats.catagory, stats.entries.to_string()])
+            .collect())
+    }
 
-                    new_key = Option::Some(field_value);
-                    field_value.to_string()
-                }
-                // Otherise format the field
-                _ => self.format_string_to_field(&catagory, &field.id, &field.value)?,
-            };
+    /// Get the stats of all catagories in the database
+    pub fn catagory_stats(&self) -> Result<Vec<CatagoryStats>, Box<dyn Error>> {
+        let catagories = self.list_catagories()?;
 
-            // Check and make sure the fields value is a-ok
+        let mut stats = Vec::<CatagoryStats>::with_capacity(catagories.len());
 
-            fields_str.push_str(&format!("{}={}", field.id, field_value));
+        for catagory in catagories {
+            let (entries, total_quantity) = self.connection.query_row(
+                &format!(
+                    "SELECT COUNT(*), COALESCE(SUM(QUANTITY), 0) FROM {}",
+                    catagory
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
 
-            if i < fields.len() - 1 {
-                fields_str.push(',')
-            }
+            stats.push(CatagoryStats {
+                catagory,
+                entries,
+                total_quantity,
+            });
         }
 
-        // Next update the entry
-        let query = format!("UPDATE {} SET {} WHERE KEY={}", catagory, fields_str, key);
+        Ok(stats)
+    }
 
-        // Swap the keys if a new key was specified
-        if let Some(new_key) = new_key {
-            self.swap_key(key, new_key)?;
+    /// Count the entries and add up the quantity at each location, across
+    /// every catagory. Returns (location, entries, total quantity) sorted by
+    /// location.
+    pub fn count_by_location(&self) -> Result<Vec<LocationCount>, Box<dyn Error>> {
+        let catagories = self.list_catagories()?;
+
+        if catagories.is_empty() {
+            return Ok(Vec::new());
         }
 
-        match self.connection.execute(&query, []) {
-            Ok(_) => Ok(()),
-            Err(error) => {
-                // Swap the keys back if there's an error!
-                if let Some(new_key) = new_key {
-                    self.swap_key(new_key, key)?;
-                }
+        let union = catagories
+            .iter()
+            .map(|catagory| format!("SELECT LOCATION, QUANTITY FROM {}", catagory))
+            .collect::<Vec<String>>()
+            .join(" UNION ALL ");
 
-                Err(Box::new(error))
-            }
-        }
-    }
+        let query = format!(
+            "SELECT LOCATION, COUNT(*), SUM(QUANTITY) FROM ({}) GROUP BY LOCATION ORDER BY LOCATION",
+            union
+        );
 
-    /// Convert an SQL valueref into a string
-    fn sqlval_to_string(value: ValueRef) -> String {
-        match value {
-            ValueRef::Null => "".to_owned(),
-            ValueRef::Integer(i) => format!("{}", i),
-            ValueRef::Real(f) => format!("{:e}", f),
-            ValueRef::Text(s) => format!("{}", String::from_utf8_lossy(s)),
-            ValueRef::Blob(_) => "BLOB".to_owned(),
-        }
+        let mut statement = self.connection.prepare(&query)?;
+
+        let locations = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<Vec<LocationCount>, _>>()?;
+
+        Ok(locations)
     }
 
-    /// Check the valididy of an ID string and throw an error if not valid
-    pub fn check_id_string(id: &str) -> Result<(), Box<dyn Error>> {
-        lazy_static! {
-            static ref VALID_RE: Regex =
-                Regex::new(r#"\A[A-Z][\S&&[^a-z+\\\-*/%&|\^=><;]]*\z"#).unwrap();
+    /// Get the total value of a catagory, which is the sum of QUANTITY * PRICE
+    /// over every entry. Returns None if the catagory has no numeric PRICE
+    /// field.
+    pub fn total_value(&self, catagory_id: &str) -> Result<Option<f64>, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(catagory_id)?;
+
+        if !fields.iter().any(|field| field == "PRICE") {
+            return Ok(None);
         }
 
-        match VALID_RE.is_match(id) {
-            true => Ok(()),
-            false => {
-                bail!("{} is not a valid ID string!", id);
+        match self.field_type(catagory_id, "PRICE")? {
+            DataType::INTEGER | DataType::REAL => {}
+            _ => {
+                return Ok(None);
             }
         }
-    }
 
-    /// Check the valididy of a value string and throw an error if not valid
-    pub fn check_value_string(value: &str, datatype: DataType) -> Result<(), Box<dyn Error>> {
-        lazy_static! {
-            static ref VALID_TEXT_PREP_RE: Regex = Regex::new(r#"\\'"#).unwrap();
-            static ref VALID_TEXT_RE: Regex = Regex::new(r#"\A'[^']*'\z"#).unwrap();
-            static ref VALID_INTEGER_PREP_RE: Regex = Regex::new(r#"e\d+"#).unwrap();
-            static ref VALID_INTEGER_RE: Regex = Regex::new(r#"\A\-*\d+\z"#).unwrap();
-            static ref VALID_REAL_PREP_RE: Regex = Regex::new(r#"\.\d+|e\d+|e\-\d"#).unwrap();
-            static ref VALID_REAL_RE: Regex = VALID_INTEGER_RE.clone();
-        }
+        // TOTAL returns 0.0 instead of NULL if there are no entries
+        let value: f64 = self.connection.query_row(
+            &format!("SELECT TOTAL(QUANTITY * PRICE) FROM {}", catagory_id),
+            [],
+            |row| row.get(0),
+        )?;
 
-        match datatype {
-            DataType::TEXT => {
-                let value = VALID_TEXT_PREP_RE.replace_all(value, "");
+        Ok(Some(value))
+    }
 
-                match VALID_TEXT_RE.is_match(&value) {
-                    true => Ok(()),
-                    false => {
-                        bail!("{} is not a valid text!", value);
-                    }
-                }
-            }
+    /// Get the total value of every catagory in the database, see total_value
+    pub fn total_value_all(&self) -> Result<Vec<CatagoryValue>, Box<dyn Error>> {
+        let catagories = self.list_catagories()?;
 
-            DataType::INTEGER => {
-                let value = VALID_INTEGER_PREP_RE.replace_all(value, "");
-                match VALID_INTEGER_RE.is_match(&value) {
-                    true => Ok(()),
-                    false => {
-                        bail!("{} is not a valid integer!", value);
-                    }
-                }
-            }
+        let mut values = Vec::<CatagoryValue>::with_capacity(catagories.len());
 
-            DataType::REAL => {
-                let value = VALID_REAL_PREP_RE.replace_all(value, "");
-                match VALID_REAL_RE.is_match(&value) {
-                    true => Ok(()),
-                    false => {
-                        bail!("{} is not a valid real!", value);
-                    }
-                }
-            }
+        for catagory in catagories {
+            let value = self.total_value(&catagory)?;
 
-            _ => {
-                bail!("Unsupported type!");
-            }
+            values.push((catagory, value));
         }
+
+        Ok(values)
     }
 
-    /// Format a string to be appropriate to the field it belongs to
-    fn format_string_to_field(
-        &self,
-        catagory_id: &str,
-        field_id: &str,
-        field_value: &str,
-    ) -> Result<String, Box<dyn Error>> {
-        // If the string is blank, it's "NULL" and therefore we don't need to
-        // find the datatype
-        if field_value.len() == 0 {
-            return Ok("NULL".to_string());
+    /// Delete an empty catagory
+    pub fn delete_empty_catagory(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        // First check to see if the catagory is empty
+        let entries = self.search_catagory(name, &Vec::new(), None)?;
+
+        if !entries.is_empty() {
+            bail!("Catagory \"{}\" is not empty!", name);
         }
 
-        let datatype = self.field_type(catagory_id, field_id)?;
+        Db::check_catagory_id(name)?;
 
-        let out = match datatype {
-            DataType::TEXT => format!("'{}'", field_value),
-            _ => field_value.to_string(),
-        };
+        let query = format!("DROP TABLE {}", name);
+
+        self.connection.execute(&query, [])?;
 
-        Db::check_value_string(&out, datatype)?;
+        // Don't leave the display order or field metadata behind for a new
+        // catagory with the same name
+        self.connection
+            .execute("DELETE FROM METADATA WHERE CATAGORY=?", params![name])?;
+        self.connection
+            .execute("DELETE FROM FIELD_METADATA WHERE CATAGORY=?", params![name])?;
 
-        Ok(out)
+        Ok(())
     }
 
-    /// Get the type of a field
-    pub fn field_type(
-        &self,
-        catagory_id: &str,
-        field_id: &str,
-    ) -> Result<DataType, Box<dyn Error>> {
-        let fields = self.grab_catagory_fields(catagory_id)?;
-        let types = self.grab_catagory_types(catagory_id)?;
+    /// Delete a catagory along with every entry in it, returning the number of
+    /// entries deleted. Unlike delete_empty_catagory this can't be undone, so
+    /// make sure the user really means it.
+    pub fn delete_catagory_cascade(&mut self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.check_catagory(name)?;
 
-        let i = match fields.iter().position(move |field| field == field_id) {
-            Some(i) => i,
-            None => {
-                bail!("Field {} not found in {}!", field_id, catagory_id);
+        let transaction = self.connection.transaction()?;
+
+        let keys = transaction
+            .prepare("SELECT KEY FROM KEYS WHERE CATAGORY=? ORDER BY KEY")?
+            .query_map(params![name], |row| row.get::<_, u64>(0))?
+            .collect::<Result<Vec<u64>, _>>()?;
+
+        for key in &keys {
+            Self::record_history(&transaction, Operation::Delete, name, *key, "")?;
+        }
+
+        let deleted = transaction.execute("DELETE FROM KEYS WHERE CATAGORY=?", params![name])?;
+        transaction.execute(&format!("DROP TABLE {}", name), [])?;
+        transaction.execute("DELETE FROM METADATA WHERE CATAGORY=?", params![name])?;
+        transaction.execute("DELETE FROM FIELD_METADATA WHERE CATAGORY=?", params![name])?;
+
+        transaction.commit()?;
+
+        Ok(deleted)
+    }
+
+    /// Delete an entry given only the key
+    pub fn delete_entry(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
+        self.delete_entries(&[key])?;
+
+        Ok(())
+    }
+
+    /// Delete several entries in a single transaction, nothing is deleted if
+    /// any of the keys aren't in use or a key is given twice. Returns how many
+    /// were deleted.
+    pub fn delete_entries(&mut self, keys: &[u64]) -> Result<usize, Box<dyn Error>> {
+        let mut seen = HashSet::<u64>::with_capacity(keys.len());
+
+        for key in keys {
+            if !seen.insert(*key) {
+                bail!("Key {} is given more than once!", b64::from_u64(*key));
             }
+        }
+
+        // Find every catagory first, the transaction needs the connection to
+        // itself
+        let catagories = keys
+            .iter()
+            .map(|key| self.grab_catagory_from_key(*key))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let transaction = self.connection.transaction()?;
+
+        for (key, catagory) in keys.iter().zip(&catagories) {
+            let query = format!("DELETE FROM {} WHERE KEY=?", catagory);
+
+            transaction.execute(&query, params![key])?;
+            transaction.execute("DELETE FROM KEYS WHERE KEY=?", params![key])?;
+            Self::record_history(&transaction, Operation::Delete, catagory, *key, "")?;
+        }
+
+        transaction.commit()?;
+
+        Ok(keys.len())
+    }
+
+    /// Move an entry to the trash instead of deleting it outright, so it can
+    /// be brought back with restore_trashed. Its key is freed like a delete.
+    pub fn trash_entry(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
+        let catagory = self.grab_catagory_from_key(key)?;
+        // Kept as export_entry JSON, which has whole blobs in hex
+        let json = self.export_entry(key)?;
+
+        let query = format!("DELETE FROM {} WHERE KEY=?", catagory);
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "INSERT INTO TRASH (TIMESTAMP, CATAGORY, KEY, ENTRY) VALUES (?, ?, ?, ?)",
+            params![Local::now().timestamp(), catagory, key, json],
+        )?;
+        transaction.execute(&query, params![key])?;
+        transaction.execute("DELETE FROM KEYS WHERE KEY=?", params![key])?;
+        Self::record_history(&transaction, Operation::Delete, &catagory, key, "")?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Bring back the entry with the given key from the trash. If it was
+    /// trashed more than once the latest one is restored. Fails if the key
+    /// has been used since.
+    pub fn restore_trashed(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
+        let trashed: Option<(i64, String)> = self
+            .connection
+            .query_row(
+                "SELECT ID, ENTRY FROM TRASH WHERE KEY=? ORDER BY ID DESC LIMIT 1",
+                params![key],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let (id, json) = match trashed {
+            Some(trashed) => trashed,
+            None => bail!("No entry {} in the trash!", b64::from_u64(key)),
         };
 
-        Ok(types[i].clone())
+        let (new_catagory, insert) = self.shared_entry_insert(&json)?;
+
+        let transaction = self.connection.transaction()?;
+
+        Self::insert_shared_entry(&transaction, new_catagory.as_ref(), insert)?;
+        transaction.execute("DELETE FROM TRASH WHERE ID=?", params![id])?;
+
+        transaction.commit()?;
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-    // This test uses two template catagories and two template entries per
-    // catagory. The catagories are to represent real life scenarios in which
-    // I plan to use pinv for, so they should cover the base use cases
-    //
-    //  CATAGORY 'RESISTOR':
-    //      MPN:        TEXT,
-    //      MFCD_BY:    TEXT,
-    //      OHMS:       REAL,
-    //      WATTS:      REAL,
-    //      TOLERANCE:  REAL,
-    //      PPM_C:      REAL,
-    //      TERM_STYLE: TEXT,
-    //      MAKEUP:     TEXT,
-    //      CASE_CODE:  TEXT,
-    //      DATASHEET:  TEXT,
-    //
-    //  CATAGORY 'CAPACITOR':
-    //      MPN:        TEXT,
-    //      FARADS:     REAL,
-    //      VOLTAGE_DC: REAL,
-    //      VOLTAGE_AC: REAL,
-    //      HOURS:      REAL,
-    //      TOLERANCE:  REAL,
-    //      TERM_STYLE: TEXT,
-    //      MAKEUP:     TEXT,
-    //      CASE_CODE:  TEXT,
-    //      DATASHEET:  TEXT,
-    //
-    //
-    //
-    //  ENTRY 0, CATAGORY 'RESISTOR':
-    //      KEY         = 0
-    //      LOCATION    = 'bazville'
-    //      QUANTITY    = 10
-    //      MPN         = 'ERJ-PM8F8204V'
-    //      MFCD_BY     = 'Panasonic'
-    //      OHMS        = 8.2e6
-    //      WATTS       = 660e-3
-    //      TOLERANCE   = 0.01
-    //      PPM_C       = 100
-    //      TERM_STYLE  = 'SMD'
-    //      MAKEUP      = 'Thick Film'
-    //      CASE_CODE   = '1206'
-    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf'
-    //
-    //  ENTRY 1, CATAGORY 'RESISTOR':
-    //      KEY         = 1
-    //      LOCATION    = 'bazville'
-    //      QUANTITY    = 2
-    //      MPN         = 'HPCR0819AK39RST'
-    //      MFCD_BY     = 'TE Connectivity/Holsworthy'
-    //      OHMS        = 39
-    //      WATTS       = 2
-    //      TOLERANCE   = 0.1
-    //      PPM_C       = -800
-    //      TERM_STYLE  = 'Through Hole'
-    //      MAKEUP      = 'Ceramic Comp'
-    //      CASE_CODE   = '19.1x7.9 Axial'
-    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/418/8/ENG_DS_1773193_1_B-2888555.pdf'
-    //
-    //  ENTRY 2, CATAGORY 'CAPACITOR':
-    //      KEY         = 2
-    //      LOCATION    = 'bartown'
-    //      QUANTITY    = 21
-    //      MPN         = 'MAL217250122E3'
-    //      MFCD_BY     = 'Vishay'
-    //      FARADS      = 1.2e-3
-    //      VOLTAGE_DC  = 35
-    //      TOLERANCE   = 0.2
-    //      TERM_STYLE  = 'Through Hole'
-    //      MAKEUP      = 'Aluminum Electrolytic'
-    //      CASE_CODE   = '25x12.5 Radial'
-    //      DATASHEET   = 'https://www.vishay.com/doc?28499'
-    //
-    //  Entry 3, CATAGORY 'CAPACITOR':
-    //      KEY         = 3
-    //      LOCATION    = 'bartown'
-    //      QUANTITY    = 100
-    //      MPN         = 'MC08EA220J-TF'
-    //      MFCD_BY     = 'Cornell Dubilier - CDE'
-    //      FARADS      = 22e-12
-    //      VOLTAGE_DC  = 100
-    //      VOLTAGE_AC  = 70
-    //      TOLERANCE   = 0.05
-    //      TERM_STYLE  = 'SMD'
-    //      MAKEUP      = 'Mica'
-    //      CASE_CODE   = '0805'
-    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/88/CDUB_S_A0011956908_1-2540249.pdf'
+    /// Permanently delete everything in the trash, returning the number of
+    /// entries deleted
+    pub fn empty_trash(&mut self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.connection.execute("DELETE FROM TRASH", [])?)
+    }
 
-    // Return test catagory a, 'RESISTOR'
-    pub fn test_catagory_a() -> Catagory {
-        Catagory {
-            id: "RESISTOR".to_owned(),
-            fields: vec![
-                CatagoryField {
-                    id: "MPN".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "MFCD_BY".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "OHMS".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "WATTS".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "TOLERANCE".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "PPM_C".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "TERM_STYLE".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "MAKEUP".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "CASE_CODE".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "DATASHEET".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-            ],
+    /// Return entries in a catagory that match the given conditions. If keys
+    /// are given, only entries with one of those keys are returned.
+    pub fn search_catagory(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        keys: Option<&[u64]>,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        self.search_catagory_sorted(catagory_id, conditions, keys, None)
+    }
+
+    /// Search every catagory for entries matching the conditions. Catagories
+    /// missing any of the fields the conditions use are skipped, since their
+    /// entries can't match. Entries are grouped by catagory, A to Z.
+    pub fn search_all(&self, conditions: &[Condition]) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let mut entries = Vec::<Entry>::new();
+
+        for catagory in self.list_catagories()? {
+            let fields = self.grab_catagory_fields(&catagory)?;
+
+            if conditions
+                .iter()
+                .any(|condition| !fields.contains(&condition.field_id))
+            {
+                continue;
+            }
+
+            entries.extend(self.search_catagory(&catagory, conditions, None)?);
         }
+
+        Ok(entries)
     }
 
-    // Return test catagory b, 'CAPACITOR'
-    pub fn test_catagory_b() -> Catagory {
-        Catagory {
-            id: "CAPACITOR".to_owned(),
-            fields: vec![
-                CatagoryField {
-                    id: "MPN".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "MFCD_BY".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "FARADS".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "VOLTAGE_DC".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "VOLTAGE_AC".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "HOURS".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "TOLERANCE".to_owned(),
-                    datatype: DataType::REAL,
-                },
-                CatagoryField {
-                    id: "TERM_STYLE".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "MAKEUP".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "CASE_CODE".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-                CatagoryField {
-                    id: "DATASHEET".to_owned(),
-                    datatype: DataType::TEXT,
-                },
-            ],
+    /// Same as search_catagory, but entries are sorted by a field if one is
+    /// given. Entries with the same value in that field are sorted by key.
+    pub fn search_catagory_sorted(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        keys: Option<&[u64]>,
+        sort: Option<&Sort>,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let options = SearchOptions {
+            keys,
+            sort,
+            ..Default::default()
+        };
+
+        self.search(catagory_id, conditions, options)
+    }
+
+    /// Return a page of at most limit entries that match the given conditions,
+    /// skipping the first offset entries. The limit is capped to MAX_PAGE_LEN.
+    pub fn search_catagory_page(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        sort: Option<&Sort>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let options = SearchOptions {
+            sort,
+            limit: Some(limit),
+            offset: Some(offset),
+            ..Default::default()
+        };
+
+        self.search(catagory_id, conditions, options)
+    }
+
+    /// Same as search_catagory_sorted, but heavy fields are left out of the
+    /// entries so big catagories load quickly in list views. Use grab_entry
+    /// to get the full entry when it's needed.
+    pub fn search_catagory_light(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        sort: Option<&Sort>,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let options = SearchOptions {
+            light: true,
+            sort,
+            ..Default::default()
+        };
+
+        self.search(catagory_id, conditions, options)
+    }
+
+    /// Build and run a search, see search_catagory_sorted,
+    /// search_catagory_page and search_catagory_light
+    fn search(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        options: SearchOptions,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        // The catagory is part of the query, so make sure it's valid
+        self.check_catagory(catagory_id)?;
+
+        let (where_clause, values) = self.where_clause(catagory_id, conditions, options.keys)?;
+
+        let columns = match options.light {
+            true => self.light_fields(catagory_id)?.join(", "),
+            false => "*".to_owned(),
+        };
+
+        let mut query = format!("SELECT {} FROM {}{}", columns, catagory_id, where_clause);
+
+        if let Some(sort) = options.sort {
+            // Only sort by fields that actually exist in the catagory
+            if !self
+                .grab_catagory_fields(catagory_id)?
+                .contains(&sort.field_id)
+            {
+                bail!("Field {} not found in {}!", sort.field_id, catagory_id);
+            }
+
+            query.push_str(&format!(
+                " ORDER BY {} {}, KEY",
+                sort.field_id,
+                sort.order.to_sql()
+            ));
         }
+
+        query.push(';');
+
+        self.query_to_entries(
+            &query,
+            params_from_iter(values),
+            catagory_id,
+            options.limit,
+            options.offset,
+        )
     }
 
-    // Test entry 0
-    pub fn test_entry_0() -> Entry {
-        Entry {
-            catagory_id: "RESISTOR".to_owned(),
-            key: 0,
-            location: "bazville".to_owned(),
-            quantity: 10,
-            created: 0,
-            modified: 0,
-            fields: vec![
-                EntryField{
-                    id: "MPN".to_owned(),
-                    value: "ERJ-PM8F8204V".to_owned()
-                },
-                EntryField {
-                    id: "MFCD_BY".to_owned(),
-                    value: "Panasonic".to_owned()
-                },
-                EntryField {
-                    id: "OHMS".to_owned(),
-                    value: "8.2e6".to_owned()
-                },
-                EntryField {
-                    id: "WATTS".to_owned(),
-                    value: "6.6e-1".to_owned()
-                },
-                EntryField {
-                    id: "TOLERANCE".to_owned(),
-                    value: "1e-2".to_owned()
-                },
-                EntryField {
-                    id: "PPM_C".to_owned(),
-                    value: "1e2".to_owned()
-                },
-                EntryField {
-                    id: "TERM_STYLE".to_owned(),
-                    value: "SMD".to_owned()
-                },
-                EntryField {
-                    id: "MAKEUP".to_owned(),
-                    value: "Thick Film".to_owned()
-                },
-                EntryField {
-                    id: "CASE_CODE".to_owned(),
-                    value: "1206".to_owned()
-                },
-                EntryField {
-                    id: "DATASHEET".to_owned(),
-                    value: "https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf".to_owned()
-                }
-            ]
+    /// Count the entries in a catagory that match the given conditions,
+    /// without loading them
+    pub fn count_matching(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+    ) -> Result<u64, Box<dyn Error>> {
+        // The catagory is part of the query, so make sure it's valid
+        self.check_catagory(catagory_id)?;
+
+        let (where_clause, values) = self.where_clause(catagory_id, conditions, None)?;
+
+        let query = format!("SELECT COUNT(*) FROM {}{};", catagory_id, where_clause);
+
+        Ok(self
+            .connection
+            .query_row(&query, params_from_iter(values), |row| row.get(0))?)
+    }
+
+    /// Find the entries in every catagory with a quantity at or below the
+    /// threshold, sorted by catagory and then by quantity
+    pub fn low_stock(&self, threshold: u64) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let conditions = [Condition::new(
+            "QUANTITY",
+            ConditionOperator::LessThanEqual,
+            &threshold.to_string(),
+        )];
+        let sort = Sort::new("QUANTITY", SortOrder::Ascending);
+
+        let mut entries = Vec::<Entry>::new();
+
+        for catagory in self.list_catagories()? {
+            entries.extend(self.search_catagory_sorted(
+                &catagory,
+                &conditions,
+                None,
+                Some(&sort),
+            )?);
         }
+
+        Ok(entries)
     }
 
-    // Test entry 1
-    pub fn test_entry_1() -> Entry {
-        Entry {
-            catagory_id: "RESISTOR".to_owned(),
-            key: 1,
-            location: "bazville".to_owned(),
-            quantity: 2,
-            created: 0,
-            modified: 0,
-            fields: vec![
-                EntryField {
-                    id: "MPN".to_owned(),
-                    value: "HPCR0819AK39RST".to_owned(),
-                },
-                EntryField {
-                    id: "MFCD_BY".to_owned(),
-                    value: "TE Connectivity/Holsworthy".to_owned(),
-                },
-                EntryField {
-                    id: "OHMS".to_owned(),
-                    value: "3.9e1".to_owned(),
-                },
-                EntryField {
-                    id: "WATTS".to_owned(),
-                    value: "2e0".to_owned(),
-                },
-                EntryField {
-                    id: "TOLERANCE".to_owned(),
-                    value: "1e1".to_owned(),
-                },
-                EntryField {
-                    id: "PPM_C".to_owned(),
-                    value: "-8e2".to_owned(),
-                },
-                EntryField {
-                    id: "TERM_STYLE".to_owned(),
-                    value: "Through Hole".to_owned(),
-                },
-                EntryField {
-                    id: "MAKEUP".to_owned(),
-                    value: "Ceramic Comp".to_owned(),
-                },
-                EntryField {
-                    id: "CASE_CODE".to_owned(),
-                    value: "19.1x7.9 Axial".to_owned(),
-                },
-                EntryField {
-                    id: "DATASHEET".to_owned(),
-                    value:
-                        "https://www.mouser.com/datasheet/2/418/8/ENG_DS_1773193_1_B-2888555.pdf"
-                            .to_owned(),
-                },
-            ],
+    /// Add up a numeric field over the entries in a catagory that match the
+    /// given conditions, without loading them. Empty fields are skipped and
+    /// the sum is 0 if nothing matches.
+    pub fn sum_field(
+        &self,
+        catagory_id: &str,
+        field_id: &str,
+        conditions: &[Condition],
+    ) -> Result<f64, Box<dyn Error>> {
+        // Both the catagory and the field are part of the query, so make sure
+        // they're valid
+        self.check_catagory(catagory_id)?;
+        Db::check_id_string(field_id)?;
+
+        match self.field_type(catagory_id, field_id)? {
+            DataType::INTEGER | DataType::REAL => {}
+            datatype => {
+                bail!(
+                    "Can't sum {}, it's {} instead of a number!",
+                    field_id,
+                    CatagoryField::new(field_id, datatype).sql_type()
+                );
+            }
         }
+
+        let (where_clause, values) = self.where_clause(catagory_id, conditions, None)?;
+
+        let query = format!(
+            "SELECT COALESCE(SUM({}), 0.0) FROM {}{};",
+            field_id, catagory_id, where_clause
+        );
+
+        Ok(self
+            .connection
+            .query_row(&query, params_from_iter(values), |row| row.get(0))?)
     }
 
-    // Test entry 2
-    pub fn test_entry_2() -> Entry {
-        Entry {
-            catagory_id: "CAPACITOR".to_owned(),
-            key: 2,
-            location: "barville".to_owned(),
-            quantity: 21,
-            created: 0,
-            modified: 0,
-            fields: vec![
-                EntryField {
-                    id: "MPN".to_owned(),
-                    value: "HPCR0819AK39RST".to_owned(),
-                },
-                EntryField {
-                    id: "MFCD_BY".to_owned(),
-                    value: "Vishay".to_owned(),
-                },
-                EntryField {
-                    id: "FARADS".to_owned(),
-                    value: "1.2e-3".to_owned(),
-                },
-                EntryField {
-                    id: "VOLTAGE_DC".to_owned(),
-                    value: "3.5e1".to_owned(),
-                },
-                EntryField {
-                    id: "TOLERANCE".to_owned(),
-                    value: "2e-1".to_owned(),
-                },
-                EntryField {
-                    id: "TERM_STYLE".to_owned(),
-                    value: "Through Hole".to_owned(),
-                },
-                EntryField {
-                    id: "MAKEUP".to_owned(),
-                    value: "Aluminum Electrolytic".to_owned(),
-                },
-                EntryField {
-                    id: "CASE_CODE".to_owned(),
-                    value: "25x12.5 Radial".to_owned(),
-                },
-                EntryField {
-                    id: "DATASHEET".to_owned(),
-                    value: "https://www.vishay.com/doc?28499".to_owned(),
-                },
-            ],
+    /// Build the WHERE clause of a search along with the values bound to it.
+    /// The clause is empty if there's nothing to narrow the search down by.
+    fn where_clause(
+        &self,
+        catagory_id: &str,
+        conditions: &[Condition],
+        keys: Option<&[u64]>,
+    ) -> Result<BoundClause, Box<dyn Error>> {
+        let mut clauses = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+
+        for condition in conditions {
+            let field_id = &condition.field_id;
+
+            // Make sure the field_id is valid and convert the value
+            Self::check_id_string(field_id)?;
+
+            match field_id.as_str() {
+                // Patterns are always text, whatever the field holds
+                _ if condition.operator.is_pattern() => values.push(Box::new(
+                    ConditionOperator::escape_pattern(&condition.value),
+                )),
+                "KEY" => values.push(Box::new(b64::to_u64(&condition.value)?)),
+                _ => values.push(Box::new(self.field_value(
+                    catagory_id,
+                    field_id,
+                    &condition.value,
+                )?)),
+            }
+        }
+
+        if !conditions.is_empty() {
+            let clause = Condition::join(conditions, |condition| {
+                condition.operator.sql_clause(&condition.field_id)
+            });
+
+            clauses.push(format!("({})", clause));
+        }
+
+        if let Some(keys) = keys {
+            clauses.push(format!("KEY IN ({})", vec!["?"; keys.len()].join(",")));
+
+            for key in keys {
+                values.push(Box::new(*key));
+            }
+        }
+
+        if clauses.is_empty() {
+            return Ok((String::new(), values));
         }
+
+        Ok((format!(" WHERE {}", clauses.join(" AND ")), values))
     }
 
-    // Test entry 3
-    pub fn test_entry_3() -> Entry {
-        Entry {
-            catagory_id: "CAPACITOR".to_owned(),
-            key: 3,
-            location: "barville".to_owned(),
-            quantity: 100,
-            created: 0,
-            modified: 0,
-            fields: vec![
-                EntryField {
-                    id: "MPN".to_owned(),
-                    value: "MC08EA220J-TF".to_owned(),
-                },
-                EntryField {
-                    id: "MFCD_BY".to_owned(),
-                    value: "Cornell Dubilier - CDE".to_owned(),
-                },
-                EntryField {
-                    id: "FARADS".to_owned(),
-                    value: "2.2e-13".to_owned(),
-                },
-                EntryField {
-                    id: "VOLTAGE_DC".to_owned(),
-                    value: "1e2".to_owned(),
-                },
-                EntryField {
-                    id: "VOLTAGE_AC".to_owned(),
-                    value: "7e1".to_owned(),
-                },
-                EntryField {
-                    id: "TOLERANCE".to_owned(),
-                    value: "5e-2".to_owned(),
-                },
-                EntryField {
-                    id: "TERM_STYLE".to_owned(),
-                    value: "SMD".to_owned(),
-                },
-                EntryField {
-                    id: "MAKEUP".to_owned(),
-                    value: "Mica".to_owned(),
-                },
-                EntryField {
-                    id: "CASE_CODE".to_owned(),
-                    value: "0805".to_owned(),
-                },
-                EntryField {
-                    id: "DATASHEET".to_owned(),
-                    value: "https://www.mouser.com/datasheet/2/88/CDUB_S_A0011956908_1-2540249.pdf"
-                        .to_owned(),
-                },
+    /// Export a catagory as CSV with a header row of field names, meant to be
+    /// opened and edited in a spreadsheet. Keys are written in base64.
+    pub fn export_catagory_csv(&self, catagory_id: &str) -> Result<String, Box<dyn Error>> {
+        let entries = self.search_catagory(catagory_id, &[], None)?;
+
+        self.entries_to_csv(catagory_id, entries)
+    }
+
+    /// Write entries of a catagory as CSV, the same way export_catagory_csv
+    /// does. Used to export only some of the entries.
+    pub fn entries_to_csv(
+        &self,
+        catagory_id: &str,
+        entries: Vec<Entry>,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut out = csv::format_row(&self.display_fields(catagory_id)?);
+
+        for entry in self.with_full_blobs(catagory_id, entries)? {
+            let mut row = vec![
+                b64::from_u64(entry.key),
+                entry.location,
+                entry.quantity.to_string(),
+                entry.created.to_string(),
+                entry.modified.to_string(),
+            ];
+
+            row.extend(entry.fields.into_iter().map(|field| field.value));
+
+            out.push_str(&csv::format_row(&row));
+        }
+
+        Ok(out)
+    }
+
+    /// Export all entries in a catagory as a JSON array of JsonEntry
+    pub fn export_catagory_json(&self, catagory_id: &str) -> Result<String, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(catagory_id)?;
+        let types = self.grab_catagory_types(catagory_id)?;
+
+        let mut json_entries = Vec::<JsonEntry>::new();
+
+        let entries = self.search_catagory(catagory_id, &[], None)?;
+
+        for entry in self.with_full_blobs(catagory_id, entries)? {
+            json_entries.push(Self::entry_to_json(entry, &fields, &types)?);
+        }
+
+        Ok(serde_json::to_string_pretty(&json_entries)?)
+    }
+
+    /// Swap the cut short previews of blobs in entries of a catagory for the
+    /// whole blob in hex, so exports can be read back in
+    fn with_full_blobs(
+        &self,
+        catagory_id: &str,
+        mut entries: Vec<Entry>,
+    ) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let blob_fields = self.fields_of_type(catagory_id, DataType::BLOB)?;
+
+        for entry in &mut entries {
+            for field in &mut entry.fields {
+                if field.value.is_empty() || !blob_fields.contains(&field.id) {
+                    continue;
+                }
+
+                let query = format!("SELECT {} FROM {} WHERE KEY=?", field.id, catagory_id);
+
+                let blob: Vec<u8> =
+                    self.connection
+                        .query_row(&query, params![entry.key], |row| row.get(0))?;
+
+                field.value = Self::blob_to_hex(&blob);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Convert an entry to JSON, given the fields of its catagory and their
+    /// types as grab_catagory_fields and grab_catagory_types return them
+    fn entry_to_json(
+        entry: Entry,
+        fields: &[String],
+        types: &[DataType],
+    ) -> Result<JsonEntry, Box<dyn Error>> {
+        let mut json_fields = serde_json::Map::new();
+
+        for field in entry.fields {
+            let datatype = fields
+                .iter()
+                .position(|id| *id == field.id)
+                .map(|i| types[i])
+                .unwrap_or(DataType::TEXT);
+
+            let value = match (field.value.is_empty(), datatype) {
+                (true, _) => serde_json::Value::Null,
+                (false, DataType::INTEGER) => serde_json::Value::from(field.value.parse::<i64>()?),
+                (false, DataType::REAL) => serde_json::Value::from(field.value.parse::<f64>()?),
+                (false, _) => serde_json::Value::from(field.value),
+            };
+
+            json_fields.insert(field.id, value);
+        }
+
+        Ok(JsonEntry {
+            catagory_id: entry.catagory_id,
+            key: b64::from_u64(entry.key),
+            location: entry.location,
+            quantity: entry.quantity,
+            created: entry.created,
+            modified: entry.modified,
+            fields: json_fields,
+        })
+    }
+
+    /// Export a single entry as JSON to share, along with its catagory. See
+    /// SharedEntry.
+    pub fn export_entry(&self, key: u64) -> Result<String, Box<dyn Error>> {
+        let entry = self.grab_entry(key)?;
+        let entry = self
+            .with_full_blobs(&entry.catagory_id.clone(), vec![entry])?
+            .remove(0);
+
+        let fields = self.grab_catagory_fields(&entry.catagory_id)?;
+        let types = self.grab_catagory_types(&entry.catagory_id)?;
+        let catagory = self.grab_catagory(&entry.catagory_id)?;
+
+        let shared = SharedEntry {
+            catagory: Some(catagory),
+            entry: Self::entry_to_json(entry, &fields, &types)?,
+        };
+
+        Ok(serde_json::to_string_pretty(&shared)?)
+    }
+
+    /// Import a single entry exported with export_entry, returning its key.
+    /// If its catagory isn't in the database it's made from the catagory
+    /// included with the entry, in the same transaction as the entry so
+    /// nothing is left behind if the entry can't be added.
+    pub fn import_entry(&mut self, json: &str) -> Result<u64, Box<dyn Error>> {
+        let (new_catagory, insert) = self.shared_entry_insert(json)?;
+        let key = insert.0;
+
+        let transaction = self.connection.transaction()?;
+
+        Self::insert_shared_entry(&transaction, new_catagory.as_ref(), insert)?;
+
+        transaction.commit()?;
+
+        Ok(key)
+    }
+
+    /// Read an entry exported with export_entry and build its insert, along
+    /// with its catagory if that isn't in the database yet
+    fn shared_entry_insert(
+        &self,
+        json: &str,
+    ) -> Result<(Option<Catagory>, EntryInsert), Box<dyn Error>> {
+        let shared: SharedEntry = serde_json::from_str(json)?;
+
+        let entry = shared.entry.into_entry()?;
+
+        let new_catagory = match shared.catagory {
+            Some(catagory) => {
+                if catagory.id != entry.catagory_id {
+                    bail!(
+                        "Entry is in {} but the catagory included is {}!",
+                        entry.catagory_id,
+                        catagory.id
+                    );
+                }
+
+                match self.catagory_exists(&catagory.id)? {
+                    true => None,
+                    false => Some(catagory),
+                }
+            }
+            None => None,
+        };
+
+        let types = match &new_catagory {
+            Some(catagory) => {
+                Db::check_new_catagory(catagory)?;
+
+                Self::catagory_field_types(catagory)
+            }
+            None => self.field_types(&entry.catagory_id)?,
+        };
+
+        let insert = Self::entry_insert(entry, &types)?;
+
+        Ok((new_catagory, insert))
+    }
+
+    /// Make the catagory from shared_entry_insert if there is one, then
+    /// insert the entry. Takes the connection so it can be part of a
+    /// transaction.
+    fn insert_shared_entry(
+        connection: &Connection,
+        new_catagory: Option<&Catagory>,
+        insert: EntryInsert,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(catagory) = new_catagory {
+            Self::create_catagory(connection, catagory)?;
+        }
+
+        Self::insert_entry(connection, insert)
+    }
+
+    /// Import entries from a JSON array like the one export_catagory_json
+    /// makes, returning the number of entries imported. Entries keep their
+    /// keys, and nothing is imported if any of the keys are already in use.
+    pub fn import_catagory_json(&mut self, json: &str) -> Result<usize, Box<dyn Error>> {
+        self.add_entries(Self::json_to_entries(json)?)
+    }
+
+    /// Read entries out of a JSON array like the one export_catagory_json
+    /// makes, without importing them
+    pub fn json_to_entries(json: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let json_entries: Vec<JsonEntry> = serde_json::from_str(json)?;
+
+        let mut entries = Vec::with_capacity(json_entries.len());
+
+        for json_entry in json_entries {
+            entries.push(json_entry.into_entry()?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Work out what each label in an SVG template will be filled with. Every
+    /// placeholder in the template is one label, and each label is given the
+    /// next available key.
+    pub fn label_substitutions(&self, data: &str) -> Result<Vec<Vec<EntryField>>, Box<dyn Error>> {
+        templates::check_template(data)?;
+
+        let label_count = templates::label_count(data);
+
+        let mut labels = Vec::<Vec<EntryField>>::with_capacity(label_count);
+        let mut key = 0;
+
+        for _ in 0..label_count {
+            key = self.grab_next_available_key(key)?;
+
+            labels.push(vec![EntryField::new(
+                "KEY",
+                &b64::from_u64_padded(key, b64::KEY_WIDTH),
+            )]);
+
+            key += 1
+        }
+
+        Ok(labels)
+    }
+
+    /// Work out what each label in an SVG template will be filled with when
+    /// printing labels for entries that already exist, one label for each key
+    /// in the order given. Labels left over are left blank.
+    pub fn label_substitutions_for_keys(
+        &self,
+        data: &str,
+        keys: &[u64],
+    ) -> Result<Vec<Vec<EntryField>>, Box<dyn Error>> {
+        templates::check_template(data)?;
+
+        let label_count = templates::label_count(data);
+
+        if keys.len() > label_count {
+            bail!(
+                "The template only has room for {} labels, not {}!",
+                label_count,
+                keys.len()
+            );
+        }
+
+        let mut labels = Vec::<Vec<EntryField>>::with_capacity(keys.len());
+
+        for key in keys {
+            labels.push(Self::entry_label(&self.grab_entry(*key)?));
+        }
+
+        Ok(labels)
+    }
+
+    /// Every field of an entry a label can be filled with, the key in base64
+    /// padded to the same width as every other label's
+    pub fn entry_label(entry: &Entry) -> Vec<EntryField> {
+        let mut label = vec![
+            EntryField::new("KEY", &b64::from_u64_padded(entry.key, b64::KEY_WIDTH)),
+            EntryField::new("LOCATION", &entry.location),
+            EntryField::new("QUANTITY", &entry.quantity.to_string()),
+        ];
+
+        label.extend(entry.fields.iter().cloned());
+
+        label
+    }
+
+    /// Take an SVG template and fill it with all available keys
+    pub fn fill_svg_template(&self, data: &str) -> Result<String, Box<dyn Error>> {
+        let labels = self.label_substitutions(data)?;
+
+        Ok(templates::fill_labels(data, &labels))
+    }
+
+    /// Take an SVG template and fill it with the fields of an entry, for
+    /// templates with a single label
+    pub fn fill_svg_entry(data: &str, entry: &Entry) -> String {
+        templates::fill_labels(data, &[Self::entry_label(entry)])
+    }
+
+    /// Take an SVG template and fill it with the keys of existing entries, see
+    /// label_substitutions_for_keys
+    pub fn fill_svg_template_with_keys(
+        &self,
+        data: &str,
+        keys: &[u64],
+    ) -> Result<String, Box<dyn Error>> {
+        let labels = self.label_substitutions_for_keys(data, keys)?;
+
+        Ok(templates::fill_labels(data, &labels))
+    }
+
+    /// Remove a key from the key table
+    fn remove_key(&mut self, key: u64) -> Result<(), Box<dyn Error>> {
+        self.connection
+            .execute("DELETE FROM KEYS WHERE KEY=?", params![key])?;
+
+        Ok(())
+    }
+
+    /// Find keys in the key table whose catagory is no longer in the
+    /// database, which happens when a catagory table is dropped outside of
+    /// pinv. Returns the keys paired with the catagory they point to.
+    pub fn find_orphans(&self) -> Result<Vec<(u64, String)>, Box<dyn Error>> {
+        let catagories = self.list_catagories()?;
+
+        Ok(self
+            .all_keys()?
+            .into_iter()
+            .filter(|(_, catagory)| !catagories.contains(catagory))
+            .collect())
+    }
+
+    /// Remove the keys found by find_orphans from the key table, returning
+    /// the keys that were removed.
+    pub fn remove_orphans(&mut self) -> Result<Vec<(u64, String)>, Box<dyn Error>> {
+        let orphans = self.find_orphans()?;
+
+        for (key, _) in &orphans {
+            self.remove_key(*key)?;
+        }
+
+        Ok(orphans)
+    }
+
+    /// Modify a entry with only a key and the fields to be modified
+    pub fn mod_entry(&mut self, key: u64, fields: Vec<EntryField>) -> Result<(), Box<dyn Error>> {
+        // First get the catagory the entry is in
+        let catagory = self.grab_catagory_from_key(key)?;
+
+        let mut assignments = vec!["MODIFIED=?".to_owned()];
+        let mut values: Vec<Box<dyn ToSql>> = vec![Box::new(Local::now().timestamp())];
+
+        let mut new_key: Option<u64> = Option::None;
+
+        let immutable_fields = self.immutable_fields(&catagory)?;
+        let catagory_fields = self.grab_catagory_fields(&catagory)?;
+
+        for field in &fields {
+            // Verify the field name is valid, it's part of the query
+            Db::check_id_string(&field.id)?;
+
+            if !catagory_fields.contains(&field.id) {
+                bail!("No such field {} in catagory {}!", field.id, catagory);
+            }
+
+            // Immutable fields can be set once, and "changed" to what they
+            // already are
+            if immutable_fields.contains(&field.id) {
+                let current: Value = self.connection.query_row(
+                    &format!("SELECT {} FROM {} WHERE KEY=?", field.id, catagory),
+                    params![key],
+                    |row| row.get(0),
+                )?;
+
+                let new = self.field_value(&catagory, &field.id, &field.value)?;
+
+                if current != Value::Null && current != new {
+                    bail!("Field {} can't be changed once it's set!", field.id);
+                }
+            }
+
+            // If the key is being modified, we need to update the key table
+            match field.id.as_str() {
+                "KEY" => {
+                    let field_value = b64::to_u64(&field.value)?;
+
+                    new_key = Option::Some(field_value);
+                    values.push(Box::new(field_value));
+                }
+                // Otherise convert the field
+                _ => values.push(Box::new(self.field_value(
+                    &catagory,
+                    &field.id,
+                    &field.value,
+                )?)),
+            };
+
+            assignments.push(format!("{}=?", field.id));
+        }
+
+        values.push(Box::new(key));
+
+        let old_entry = self.grab_entry(key)?;
+
+        // Changes to only the quantity are recorded as gives and takes
+        let operation = match fields.as_slice() {
+            [field] if field.id == "QUANTITY" => {
+                match field.value.parse::<u64>()? >= old_entry.quantity {
+                    true => Operation::Give,
+                    false => Operation::Take,
+                }
+            }
+            _ => Operation::Modify,
+        };
+
+        let diff = Self::entry_diff(&old_entry, &fields);
+
+        // Next update the entry
+        let query = format!(
+            "UPDATE {} SET {} WHERE KEY=?",
+            catagory,
+            assignments.join(",")
+        );
+
+        // The key table and the entry are updated together or not at all
+        let transaction = self.connection.transaction()?;
+
+        // Swap the keys if a new key was specified
+        if let Some(new_key) = new_key {
+            Self::swap_key(&transaction, key, new_key)?;
+        }
+
+        transaction
+            .execute(&query, params_from_iter(values))
+            .map_err(|error| Self::explain_unique_violation(error, &fields))?;
+        Self::record_history(&transaction, operation, &catagory, key, &diff)?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Describe the changes modifying an entry with the given fields makes,
+    /// like "QUANTITY: 50 -> 10, LOCATION: bin 1 -> bin 2". Fields set to
+    /// what they already are are left out.
+    fn entry_diff(entry: &Entry, fields: &[EntryField]) -> String {
+        let mut changes = Vec::<String>::new();
+
+        for field in fields {
+            let old_value = match field.id.as_str() {
+                "KEY" => b64::from_u64(entry.key),
+                "LOCATION" => entry.location.clone(),
+                "QUANTITY" => entry.quantity.to_string(),
+                _ => entry
+                    .fields
+                    .iter()
+                    .find(|old_field| old_field.id == field.id)
+                    .map(|old_field| old_field.value.clone())
+                    .unwrap_or_default(),
+            };
+
+            if old_value != field.value {
+                changes.push(format!("{}: {} -> {}", field.id, old_value, field.value));
+            }
+        }
+
+        changes.join(", ")
+    }
+
+    /// Record a change to an entry in the history, along with a short
+    /// description of what changed. Takes the connection so it can be part of
+    /// the transaction making the change.
+    fn record_history(
+        connection: &Connection,
+        operation: Operation,
+        catagory_id: &str,
+        key: u64,
+        diff: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        connection.execute(
+            "INSERT INTO HISTORY (TIMESTAMP, OPERATION, CATAGORY, KEY, DIFF) VALUES (?, ?, ?, ?, ?)",
+            params![
+                Local::now().timestamp(),
+                operation.as_str(),
+                catagory_id,
+                key,
+                diff
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Grab the most recent changes made to entries, newest first. If a limit
+    /// is given, no more than that many are returned.
+    pub fn history(&self, limit: Option<u64>) -> Result<Vec<HistoryRecord>, Box<dyn Error>> {
+        let mut query =
+            "SELECT TIMESTAMP, OPERATION, CATAGORY, KEY, DIFF FROM HISTORY ORDER BY ID DESC"
+                .to_owned();
+
+        if let Some(limit) = limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut statement = self.connection.prepare(&query)?;
+        let mut rows = statement.query([])?;
+
+        let mut records = Vec::<HistoryRecord>::new();
+
+        while let Some(row) = rows.next()? {
+            records.push(HistoryRecord {
+                timestamp: row.get(0)?,
+                operation: row.get::<usize, String>(1)?.parse()?,
+                catagory_id: row.get(2)?,
+                key: row.get(3)?,
+                diff: row.get(4)?,
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// Change the key of an entry. The new key must not already be in use.
+    /// The key table and the entry are updated together or not at all.
+    pub fn rekey_entry(&mut self, old_key: u64, new_key: u64) -> Result<(), Box<dyn Error>> {
+        let catagory = self.grab_catagory_from_key(old_key)?;
+
+        if old_key == new_key {
+            return Ok(());
+        }
+
+        let mod_time = Local::now().timestamp();
+
+        let transaction = self.connection.transaction()?;
+
+        // Checks the new key is free in the same transaction it's taken in
+        Self::swap_key(&transaction, old_key, new_key)?;
+        transaction.execute(
+            &format!("UPDATE {} SET KEY=?, MODIFIED=? WHERE KEY=?", catagory),
+            params![new_key, mod_time, old_key],
+        )?;
+        Self::record_history(
+            &transaction,
+            Operation::Modify,
+            &catagory,
+            old_key,
+            &format!(
+                "KEY: {} -> {}",
+                b64::from_u64(old_key),
+                b64::from_u64(new_key)
+            ),
+        )?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    /// Move an entry to another catagory, keeping its key. Only the fields
+    /// both catagories have are moved, and the ids of any fields with values
+    /// that had to be left behind are returned so the user can be warned.
+    pub fn move_entry(
+        &mut self,
+        key: u64,
+        new_catagory: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let entry = self.grab_entry(key)?;
+        let old_catagory = entry.catagory_id.clone();
+
+        self.check_catagory(new_catagory)?;
+
+        if old_catagory == new_catagory {
+            bail!(
+                "Entry {} is already in {}!",
+                b64::from_u64(key),
+                new_catagory
+            );
+        }
+
+        let new_fields = self.grab_catagory_fields_opts(new_catagory, false)?;
+
+        let (fields, dropped): (Vec<EntryField>, Vec<EntryField>) = entry
+            .fields
+            .into_iter()
+            .partition(|field| new_fields.contains(&field.id));
+
+        let dropped: Vec<String> = dropped
+            .into_iter()
+            .filter(|field| !field.value.is_empty())
+            .map(|field| field.id)
+            .collect();
+
+        // Copy the stored values rather than the strings shown, blobs are
+        // only previewed. Fields that change type are converted.
+        let old_types = self.field_types(&old_catagory)?;
+        let new_types = self.field_types(new_catagory)?;
+
+        let mut columns = vec!["KEY", "LOCATION", "QUANTITY", "CREATED"];
+        columns.extend(fields.iter().map(|field| field.id.as_str()));
+
+        let query = format!(
+            "SELECT {} FROM {} WHERE KEY=?",
+            columns.join(", "),
+            old_catagory
+        );
+
+        let mut values: Vec<Value> = self.connection.query_row(&query, params![key], |row| {
+            (0..columns.len()).map(|i| row.get(i)).collect()
+        })?;
+
+        for (column, value) in columns.iter().zip(values.iter_mut()) {
+            let old_type = old_types.iter().find(|(id, _)| id == column);
+            let new_type = new_types.iter().find(|(id, _)| id == column);
+
+            if let (Some((_, old_type)), Some((_, new_type))) = (old_type, new_type) {
+                if old_type != new_type && *value != Value::Null {
+                    let string = match &*value {
+                        Value::Blob(blob) => Self::blob_to_hex(blob),
+                        value => Self::sqlval_to_string(ValueRef::from(value)),
+                    };
+
+                    *value = Db::value_from_string(&string, *new_type)?;
+                }
+            }
+        }
+
+        columns.push("MODIFIED");
+        values.push(Value::Integer(Local::now().timestamp()));
+
+        let query = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            new_catagory,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            &format!("DELETE FROM {} WHERE KEY=?", old_catagory),
+            params![key],
+        )?;
+        transaction
+            .execute(&query, params_from_iter(values))
+            .map_err(|error| Self::explain_unique_violation(error, &fields))?;
+        transaction.execute(
+            "UPDATE KEYS SET CATAGORY=? WHERE KEY=?",
+            params![new_catagory, key],
+        )?;
+        Self::record_history(
+            &transaction,
+            Operation::Modify,
+            new_catagory,
+            key,
+            &format!("CATAGORY: {} -> {}", old_catagory, new_catagory),
+        )?;
+
+        transaction.commit()?;
+
+        Ok(dropped)
+    }
+
+    /// Override the creation time of an entry. Meant for importing historical
+    /// data, not for normal modification.
+    pub fn set_created(&mut self, key: u64, timestamp: i64) -> Result<(), Box<dyn Error>> {
+        self.set_timestamp(key, "CREATED", timestamp)
+    }
+
+    /// Override the modification time of an entry. Meant for importing
+    /// historical data, not for normal modification.
+    pub fn set_modified(&mut self, key: u64, timestamp: i64) -> Result<(), Box<dyn Error>> {
+        self.set_timestamp(key, "MODIFIED", timestamp)
+    }
+
+    /// Set one of the timestamp fields of an entry
+    fn set_timestamp(
+        &mut self,
+        key: u64,
+        field_id: &str,
+        timestamp: i64,
+    ) -> Result<(), Box<dyn Error>> {
+        let catagory = self.grab_catagory_from_key(key)?;
+
+        let query = format!("UPDATE {} SET {}=? WHERE KEY=?", catagory, field_id);
+
+        self.connection.execute(&query, params![timestamp, key])?;
+
+        Ok(())
+    }
+
+    /// Turn a violated UNIQUE constraint into an error saying which value is
+    /// already taken, fields being what was written. Other errors are passed
+    /// along as they are.
+    fn explain_unique_violation(error: SqlError, fields: &[EntryField]) -> Box<dyn Error> {
+        if let SqlError::SqliteFailure(failure, Some(message)) = &error {
+            // SQLite names the column like "UNIQUE constraint failed: TABLE.FIELD"
+            let field_id = message
+                .strip_prefix("UNIQUE constraint failed: ")
+                .and_then(|column| column.split_once('.'))
+                .map(|(_, field_id)| field_id);
+
+            if let (SQLITE_CONSTRAINT_UNIQUE, Some(field_id)) = (failure.extended_code, field_id) {
+                if let Some(field) = fields.iter().find(|field| field.id == field_id) {
+                    return format!("{} '{}' already exists!", field.id, field.value).into();
+                }
+            }
+        }
+
+        error.into()
+    }
+
+    /// Convert an SQL valueref into a string
+    fn sqlval_to_string(value: ValueRef) -> String {
+        match value {
+            ValueRef::Null => "".to_owned(),
+            ValueRef::Integer(i) => format!("{}", i),
+            ValueRef::Real(f) => format!("{:e}", f),
+            ValueRef::Text(s) => format!("{}", String::from_utf8_lossy(s)),
+            ValueRef::Blob(b) => Self::blob_to_string(b),
+        }
+    }
+
+    /// Convert a blob to a hex string starting with "0x" to show it. Blobs
+    /// longer than BLOB_PREVIEW_LEN are cut short and followed by their length.
+    fn blob_to_string(blob: &[u8]) -> String {
+        match blob.len() > BLOB_PREVIEW_LEN {
+            true => format!(
+                "{}... ({} bytes)",
+                Self::blob_to_hex(&blob[..BLOB_PREVIEW_LEN]),
+                blob.len()
+            ),
+            false => Self::blob_to_hex(blob),
+        }
+    }
+
+    /// Convert a whole blob to a hex string starting with "0x", which
+    /// blob_from_string reads back
+    fn blob_to_hex(blob: &[u8]) -> String {
+        let hex: String = blob.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        format!("0x{}", hex)
+    }
+
+    /// Get the bytes of a blob from a hex string starting with "0x". Files
+    /// are never read here, see read_blob_files.
+    fn blob_from_string(value: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let hex = match value.strip_prefix("0x") {
+            Some(hex) => hex,
+            None => {
+                bail!(
+                    "{} is not a valid blob, blobs are hex starting with 0x!",
+                    value
+                );
+            }
+        };
+
+        let mut data = Vec::with_capacity(hex.len() / 2);
+
+        for i in (0..hex.len()).step_by(2) {
+            data.push(u8::from_str_radix(&hex[i..i + 2], 16)?);
+        }
+
+        Ok(data)
+    }
+
+    /// Check a list of entries about to be imported for repeated keys. If
+    /// keep_last is false an error listing every repeated key is returned,
+    /// otherwise only the last entry with a given key is kept.
+    pub fn dedupe_keys(entries: Vec<Entry>, keep_last: bool) -> Result<Vec<Entry>, Box<dyn Error>> {
+        let mut seen = HashSet::<u64>::with_capacity(entries.len());
+
+        if !keep_last {
+            let mut duplicates = Vec::<String>::new();
+
+            for entry in &entries {
+                if !seen.insert(entry.key) {
+                    let key = b64::from_u64(entry.key);
+
+                    if !duplicates.contains(&key) {
+                        duplicates.push(key);
+                    }
+                }
+            }
+
+            if !duplicates.is_empty() {
+                bail!("Duplicate keys in import: {}!", duplicates.join(", "));
+            }
+
+            return Ok(entries);
+        }
+
+        // Walk backwards so the last entry with a key is the one that's kept
+        let mut deduped: Vec<Entry> = entries
+            .into_iter()
+            .rev()
+            .filter(|entry| seen.insert(entry.key))
+            .collect();
+
+        deduped.reverse();
+
+        Ok(deduped)
+    }
+
+    /// Check the validity of a catagory ID and throw an error with a
+    /// friendlier message than check_id_string if not valid
+    pub fn check_catagory_id(id: &str) -> Result<(), Box<dyn Error>> {
+        if id.trim().is_empty() {
+            bail!("Catagory name can't be empty!");
+        }
+
+        if id != id.to_uppercase() {
+            bail!("Catagory name {} must be uppercase!", id);
+        }
+
+        if RESERVED_TABLES.contains(&id) {
+            bail!("Catagory name {} is reserved!", id);
+        }
+
+        Db::check_id_string(id)
+    }
+
+    /// Check a catagory is valid before it's added to the database, with a
+    /// name and at least one field
+    pub fn check_new_catagory(catagory: &Catagory) -> Result<(), Box<dyn Error>> {
+        Db::check_catagory_id(&catagory.id)?;
+
+        if catagory.fields.is_empty() {
+            bail!("No fields in catagory {}!", catagory.id);
+        }
+
+        for (i, field) in catagory.fields.iter().enumerate() {
+            Db::check_catagory_field(field, &catagory.fields[..i])?;
+        }
+
+        Ok(())
+    }
+
+    /// Check a field can be added to a new catagory alongside the fields
+    /// already in it
+    pub fn check_catagory_field(
+        field: &CatagoryField,
+        others: &[CatagoryField],
+    ) -> Result<(), Box<dyn Error>> {
+        if field.id.trim().is_empty() {
+            bail!("Field name can't be empty!");
+        }
+
+        if BUILTIN_FIELDS.contains(&field.id.as_str()) {
+            bail!("Every catagory already has a {} field!", field.id);
+        }
+
+        Db::check_id_string(&field.id)?;
+
+        if others.iter().any(|other| other.id == field.id) {
+            bail!("Field {} is in the catagory twice!", field.id);
+        }
+
+        Ok(())
+    }
+
+    /// Check the valididy of an ID string and throw an error if not valid
+    pub fn check_id_string(id: &str) -> Result<(), Box<dyn Error>> {
+        lazy_static! {
+            static ref VALID_RE: Regex =
+                Regex::new(r#"\A[A-Z][\S&&[^a-z+\\\-*/%&|\^=><;]]*\z"#).unwrap();
+        }
+
+        match VALID_RE.is_match(id) {
+            true => Ok(()),
+            false => {
+                bail!("{} is not a valid ID string!", id);
+            }
+        }
+    }
+
+    /// Check the valididy of a value string and throw an error if not valid
+    pub fn check_value_string(value: &str, datatype: DataType) -> Result<(), Box<dyn Error>> {
+        lazy_static! {
+            static ref VALID_TEXT_PREP_RE: Regex = Regex::new(r#"\\'"#).unwrap();
+            static ref VALID_TEXT_RE: Regex = Regex::new(r#"\A'[^']*'\z"#).unwrap();
+            static ref VALID_INTEGER_PREP_RE: Regex = Regex::new(r#"e\d+"#).unwrap();
+            static ref VALID_INTEGER_RE: Regex = Regex::new(r#"\A\-*\d+\z"#).unwrap();
+            static ref VALID_REAL_PREP_RE: Regex = Regex::new(r#"\.\d+|e\d+|e\-\d"#).unwrap();
+            static ref VALID_REAL_RE: Regex = VALID_INTEGER_RE.clone();
+        }
+
+        match datatype {
+            DataType::TEXT => {
+                let value = VALID_TEXT_PREP_RE.replace_all(value, "");
+
+                match VALID_TEXT_RE.is_match(&value) {
+                    true => Ok(()),
+                    false => {
+                        bail!("{} is not a valid text!", value);
+                    }
+                }
+            }
+
+            DataType::INTEGER => {
+                let value = VALID_INTEGER_PREP_RE.replace_all(value, "");
+                match VALID_INTEGER_RE.is_match(&value) {
+                    true => Ok(()),
+                    false => {
+                        bail!("{} is not a valid integer!", value);
+                    }
+                }
+            }
+
+            DataType::REAL => {
+                let value = VALID_REAL_PREP_RE.replace_all(value, "");
+                match VALID_REAL_RE.is_match(&value) {
+                    true => Ok(()),
+                    false => {
+                        bail!("{} is not a valid real!", value);
+                    }
+                }
+            }
+
+            DataType::BLOB => match value.strip_prefix("0x") {
+                Some(hex) => {
+                    match hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                        true => Ok(()),
+                        false => {
+                            bail!("{} is not a valid blob!", value);
+                        }
+                    }
+                }
+                None => {
+                    bail!(
+                        "{} is not a valid blob, blobs are hex starting with 0x!",
+                        value
+                    );
+                }
+            },
+
+            // Blank values are how NULL is written, see field_value
+            DataType::NULL => match value.is_empty() {
+                true => Ok(()),
+                false => {
+                    bail!("{} is not NULL, leave the value blank for NULL!", value);
+                }
+            },
+        }
+    }
+
+    /// Convert a string to a value appropriate to the field it belongs to,
+    /// ready to be bound to a query. Blank strings are NULL.
+    fn field_value(
+        &self,
+        catagory_id: &str,
+        field_id: &str,
+        field_value: &str,
+    ) -> Result<Value, Box<dyn Error>> {
+        // If the string is blank, it's NULL and therefore we don't need to
+        // find the datatype
+        if field_value.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        let datatype = self.field_type(catagory_id, field_id)?;
+
+        Db::value_from_string(field_value, datatype)
+    }
+
+    /// Convert a string that isn't blank to a value of the given type, ready
+    /// to be bound to a query. Blobs have to be hex.
+    pub fn value_from_string(
+        field_value: &str,
+        datatype: DataType,
+    ) -> Result<Value, Box<dyn Error>> {
+        let value = match datatype {
+            // Text is bound as-is, quotes and all
+            DataType::TEXT => Value::Text(field_value.to_owned()),
+            DataType::INTEGER => {
+                Db::check_value_string(field_value, datatype)?;
+
+                // Integers can be written with an exponent, e.g. 1e3
+                match field_value.parse::<i64>() {
+                    Ok(integer) => Value::Integer(integer),
+                    Err(_) => match field_value.parse::<f64>() {
+                        // The cast saturates, so anything outside an i64 has
+                        // to be caught first. i64::MAX as f64 rounds up to
+                        // 2^63, which is already too big.
+                        Ok(real)
+                            if real.fract() == 0.0
+                                && real >= i64::MIN as f64
+                                && real < i64::MAX as f64 =>
+                        {
+                            Value::Integer(real as i64)
+                        }
+                        Ok(real) if real.fract() == 0.0 => {
+                            bail!("{} is too big for an integer!", field_value);
+                        }
+                        _ => {
+                            bail!("{} is not a valid integer!", field_value);
+                        }
+                    },
+                }
+            }
+            DataType::REAL => {
+                Db::check_value_string(field_value, datatype)?;
+
+                match field_value.parse::<f64>() {
+                    Ok(real) => Value::Real(real),
+                    Err(_) => {
+                        bail!("{} is not a valid real!", field_value);
+                    }
+                }
+            }
+            DataType::BLOB => {
+                Db::check_value_string(field_value, datatype)?;
+
+                Value::Blob(Db::blob_from_string(field_value)?)
+            }
+            _ => {
+                bail!("Unsupported type!");
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Get the type of a field
+    pub fn field_type(
+        &self,
+        catagory_id: &str,
+        field_id: &str,
+    ) -> Result<DataType, Box<dyn Error>> {
+        let fields = self.grab_catagory_fields(catagory_id)?;
+        let types = self.grab_catagory_types(catagory_id)?;
+
+        let i = match fields.iter().position(move |field| field == field_id) {
+            Some(i) => i,
+            None => {
+                bail!("Field {} not found in {}!", field_id, catagory_id);
+            }
+        };
+
+        Ok(types[i])
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    // This test uses two template catagories and two template entries per
+    // catagory. The catagories are to represent real life scenarios in which
+    // I plan to use pinv for, so they should cover the base use cases
+    //
+    //  CATAGORY 'RESISTOR':
+    //      MPN:        TEXT,
+    //      MFCD_BY:    TEXT,
+    //      OHMS:       REAL,
+    //      WATTS:      REAL,
+    //      TOLERANCE:  REAL,
+    //      PPM_C:      REAL,
+    //      TERM_STYLE: TEXT,
+    //      MAKEUP:     TEXT,
+    //      CASE_CODE:  TEXT,
+    //      DATASHEET:  TEXT,
+    //
+    //  CATAGORY 'CAPACITOR':
+    //      MPN:        TEXT,
+    //      FARADS:     REAL,
+    //      VOLTAGE_DC: REAL,
+    //      VOLTAGE_AC: REAL,
+    //      HOURS:      REAL,
+    //      TOLERANCE:  REAL,
+    //      TERM_STYLE: TEXT,
+    //      MAKEUP:     TEXT,
+    //      CASE_CODE:  TEXT,
+    //      DATASHEET:  TEXT,
+    //
+    //
+    //
+    //  ENTRY 0, CATAGORY 'RESISTOR':
+    //      KEY         = 0
+    //      LOCATION    = 'bazville'
+    //      QUANTITY    = 10
+    //      MPN         = 'ERJ-PM8F8204V'
+    //      MFCD_BY     = 'Panasonic'
+    //      OHMS        = 8.2e6
+    //      WATTS       = 660e-3
+    //      TOLERANCE   = 0.01
+    //      PPM_C       = 100
+    //      TERM_STYLE  = 'SMD'
+    //      MAKEUP      = 'Thick Film'
+    //      CASE_CODE   = '1206'
+    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf'
+    //
+    //  ENTRY 1, CATAGORY 'RESISTOR':
+    //      KEY         = 1
+    //      LOCATION    = 'bazville'
+    //      QUANTITY    = 2
+    //      MPN         = 'HPCR0819AK39RST'
+    //      MFCD_BY     = 'TE Connectivity/Holsworthy'
+    //      OHMS        = 39
+    //      WATTS       = 2
+    //      TOLERANCE   = 0.1
+    //      PPM_C       = -800
+    //      TERM_STYLE  = 'Through Hole'
+    //      MAKEUP      = 'Ceramic Comp'
+    //      CASE_CODE   = '19.1x7.9 Axial'
+    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/418/8/ENG_DS_1773193_1_B-2888555.pdf'
+    //
+    //  ENTRY 2, CATAGORY 'CAPACITOR':
+    //      KEY         = 2
+    //      LOCATION    = 'bartown'
+    //      QUANTITY    = 21
+    //      MPN         = 'MAL217250122E3'
+    //      MFCD_BY     = 'Vishay'
+    //      FARADS      = 1.2e-3
+    //      VOLTAGE_DC  = 35
+    //      TOLERANCE   = 0.2
+    //      TERM_STYLE  = 'Through Hole'
+    //      MAKEUP      = 'Aluminum Electrolytic'
+    //      CASE_CODE   = '25x12.5 Radial'
+    //      DATASHEET   = 'https://www.vishay.com/doc?28499'
+    //
+    //  Entry 3, CATAGORY 'CAPACITOR':
+    //      KEY         = 3
+    //      LOCATION    = 'bartown'
+    //      QUANTITY    = 100
+    //      MPN         = 'MC08EA220J-TF'
+    //      MFCD_BY     = 'Cornell Dubilier - CDE'
+    //      FARADS      = 22e-12
+    //      VOLTAGE_DC  = 100
+    //      VOLTAGE_AC  = 70
+    //      TOLERANCE   = 0.05
+    //      TERM_STYLE  = 'SMD'
+    //      MAKEUP      = 'Mica'
+    //      CASE_CODE   = '0805'
+    //      DATASHEET   = 'https://www.mouser.com/datasheet/2/88/CDUB_S_A0011956908_1-2540249.pdf'
+
+    // Return test catagory a, 'RESISTOR'
+    pub fn test_catagory_a() -> Catagory {
+        Catagory {
+            id: "RESISTOR".to_owned(),
+            fields: vec![
+                CatagoryField::new("MPN", DataType::TEXT),
+                CatagoryField::new("MFCD_BY", DataType::TEXT),
+                CatagoryField::new("OHMS", DataType::REAL),
+                CatagoryField::new("WATTS", DataType::REAL),
+                CatagoryField::new("TOLERANCE", DataType::REAL),
+                CatagoryField::new("PPM_C", DataType::REAL),
+                CatagoryField::new("TERM_STYLE", DataType::TEXT),
+                CatagoryField::new("MAKEUP", DataType::TEXT),
+                CatagoryField::new("CASE_CODE", DataType::TEXT),
+                CatagoryField::new("DATASHEET", DataType::TEXT),
+            ],
+        }
+    }
+
+    // Return test catagory b, 'CAPACITOR'
+    pub fn test_catagory_b() -> Catagory {
+        Catagory {
+            id: "CAPACITOR".to_owned(),
+            fields: vec![
+                CatagoryField::new("MPN", DataType::TEXT),
+                CatagoryField::new("MFCD_BY", DataType::TEXT),
+                CatagoryField::new("FARADS", DataType::REAL),
+                CatagoryField::new("VOLTAGE_DC", DataType::REAL),
+                CatagoryField::new("VOLTAGE_AC", DataType::REAL),
+                CatagoryField::new("HOURS", DataType::REAL),
+                CatagoryField::new("TOLERANCE", DataType::REAL),
+                CatagoryField::new("TERM_STYLE", DataType::TEXT),
+                CatagoryField::new("MAKEUP", DataType::TEXT),
+                CatagoryField::new("CASE_CODE", DataType::TEXT),
+                CatagoryField::new("DATASHEET", DataType::TEXT),
+            ],
+        }
+    }
+
+    // Test entry 0
+    pub fn test_entry_0() -> Entry {
+        Entry {
+            catagory_id: "RESISTOR".to_owned(),
+            key: 0,
+            location: "bazville".to_owned(),
+            quantity: 10,
+            created: 0,
+            modified: 0,
+            fields: vec![
+                EntryField{
+                    id: "MPN".to_owned(),
+                    value: "ERJ-PM8F8204V".to_owned()
+                },
+                EntryField {
+                    id: "MFCD_BY".to_owned(),
+                    value: "Panasonic".to_owned()
+                },
+                EntryField {
+                    id: "OHMS".to_owned(),
+                    value: "8.2e6".to_owned()
+                },
+                EntryField {
+                    id: "WATTS".to_owned(),
+                    value: "6.6e-1".to_owned()
+                },
+                EntryField {
+                    id: "TOLERANCE".to_owned(),
+                    value: "1e-2".to_owned()
+                },
+                EntryField {
+                    id: "PPM_C".to_owned(),
+                    value: "1e2".to_owned()
+                },
+                EntryField {
+                    id: "TERM_STYLE".to_owned(),
+                    value: "SMD".to_owned()
+                },
+                EntryField {
+                    id: "MAKEUP".to_owned(),
+                    value: "Thick Film".to_owned()
+                },
+                EntryField {
+                    id: "CASE_CODE".to_owned(),
+                    value: "1206".to_owned()
+                },
+                EntryField {
+                    id: "DATASHEET".to_owned(),
+                    value: "https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf".to_owned()
+                }
+            ]
+        }
+    }
+
+    // Test entry 1
+    pub fn test_entry_1() -> Entry {
+        Entry {
+            catagory_id: "RESISTOR".to_owned(),
+            key: 1,
+            location: "bazville".to_owned(),
+            quantity: 2,
+            created: 0,
+            modified: 0,
+            fields: vec![
+                EntryField {
+                    id: "MPN".to_owned(),
+                    value: "HPCR0819AK39RST".to_owned(),
+                },
+                EntryField {
+                    id: "MFCD_BY".to_owned(),
+                    value: "TE Connectivity/Holsworthy".to_owned(),
+                },
+                EntryField {
+                    id: "OHMS".to_owned(),
+                    value: "3.9e1".to_owned(),
+                },
+                EntryField {
+                    id: "WATTS".to_owned(),
+                    value: "2e0".to_owned(),
+                },
+                EntryField {
+                    id: "TOLERANCE".to_owned(),
+                    value: "1e1".to_owned(),
+                },
+                EntryField {
+                    id: "PPM_C".to_owned(),
+                    value: "-8e2".to_owned(),
+                },
+                EntryField {
+                    id: "TERM_STYLE".to_owned(),
+                    value: "Through Hole".to_owned(),
+                },
+                EntryField {
+                    id: "MAKEUP".to_owned(),
+                    value: "Ceramic Comp".to_owned(),
+                },
+                EntryField {
+                    id: "CASE_CODE".to_owned(),
+                    value: "19.1x7.9 Axial".to_owned(),
+                },
+                EntryField {
+                    id: "DATASHEET".to_owned(),
+                    value:
+                        "https://www.mouser.com/datasheet/2/418/8/ENG_DS_1773193_1_B-2888555.pdf"
+                            .to_owned(),
+                },
+            ],
+        }
+    }
+
+    // Test entry 2
+    pub fn test_entry_2() -> Entry {
+        Entry {
+            catagory_id: "CAPACITOR".to_owned(),
+            key: 2,
+            location: "barville".to_owned(),
+            quantity: 21,
+            created: 0,
+            modified: 0,
+            fields: vec![
+                EntryField {
+                    id: "MPN".to_owned(),
+                    value: "HPCR0819AK39RST".to_owned(),
+                },
+                EntryField {
+                    id: "MFCD_BY".to_owned(),
+                    value: "Vishay".to_owned(),
+                },
+                EntryField {
+                    id: "FARADS".to_owned(),
+                    value: "1.2e-3".to_owned(),
+                },
+                EntryField {
+                    id: "VOLTAGE_DC".to_owned(),
+                    value: "3.5e1".to_owned(),
+                },
+                EntryField {
+                    id: "TOLERANCE".to_owned(),
+                    value: "2e-1".to_owned(),
+                },
+                EntryField {
+                    id: "TERM_STYLE".to_owned(),
+                    value: "Through Hole".to_owned(),
+                },
+                EntryField {
+                    id: "MAKEUP".to_owned(),
+                    value: "Aluminum Electrolytic".to_owned(),
+                },
+                EntryField {
+                    id: "CASE_CODE".to_owned(),
+                    value: "25x12.5 Radial".to_owned(),
+                },
+                EntryField {
+                    id: "DATASHEET".to_owned(),
+                    value: "https://www.vishay.com/doc?28499".to_owned(),
+                },
+            ],
+        }
+    }
+
+    // Test entry 3
+    pub fn test_entry_3() -> Entry {
+        Entry {
+            catagory_id: "CAPACITOR".to_owned(),
+            key: 3,
+            location: "barville".to_owned(),
+            quantity: 100,
+            created: 0,
+            modified: 0,
+            fields: vec![
+                EntryField {
+                    id: "MPN".to_owned(),
+                    value: "MC08EA220J-TF".to_owned(),
+                },
+                EntryField {
+                    id: "MFCD_BY".to_owned(),
+                    value: "Cornell Dubilier - CDE".to_owned(),
+                },
+                EntryField {
+                    id: "FARADS".to_owned(),
+                    value: "2.2e-13".to_owned(),
+                },
+                EntryField {
+                    id: "VOLTAGE_DC".to_owned(),
+                    value: "1e2".to_owned(),
+                },
+                EntryField {
+                    id: "VOLTAGE_AC".to_owned(),
+                    value: "7e1".to_owned(),
+                },
+                EntryField {
+                    id: "TOLERANCE".to_owned(),
+                    value: "5e-2".to_owned(),
+                },
+                EntryField {
+                    id: "TERM_STYLE".to_owned(),
+                    value: "SMD".to_owned(),
+                },
+                EntryField {
+                    id: "MAKEUP".to_owned(),
+                    value: "Mica".to_owned(),
+                },
+                EntryField {
+                    id: "CASE_CODE".to_owned(),
+                    value: "0805".to_owned(),
+                },
+                EntryField {
+                    id: "DATASHEET".to_owned(),
+                    value: "https://www.mouser.com/datasheet/2/88/CDUB_S_A0011956908_1-2540249.pdf"
+                        .to_owned(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_db_init_demo() {
+        let mut db = Db::_new_test();
+
+        db.init_demo().unwrap();
+
+        assert_eq!(db.list_catagories().unwrap(), vec!["CAPACITOR", "RESISTOR"]);
+        assert_eq!(db.count_matching("RESISTOR", &[]).unwrap(), 2);
+        assert_eq!(db.count_matching("CAPACITOR", &[]).unwrap(), 2);
+
+        let entry = db.grab_entry(2).unwrap();
+        assert_eq!(entry.catagory_id, "CAPACITOR");
+        assert_eq!(entry.quantity, 100);
+        assert_eq!(entry.fields[1], EntryField::new("FARADS", "1e-7"));
+
+        // Seeding twice would clobber what's there
+        let error = db.init_demo().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Demo data can only be added to an empty database!"
+        );
+
+        let mut db = Db::_new_test();
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        db.init_demo().unwrap_err();
+    }
+
+    // Test creating a field from a string
+    #[test]
+    fn test_db_create_field_from_string() {
+        let field = CatagoryField::from_str("foo:i").unwrap();
+
+        assert_eq!(field.datatype, DataType::INTEGER);
+    }
+
+    // Test getting the sql type from a field
+    #[test]
+    fn test_db_get_sql_type() {
+        let field = CatagoryField::from_str("foo:i").unwrap();
+
+        assert_eq!(field.sql_type(), "INTEGER");
+    }
+
+    // Test creating a catagory
+    #[test]
+    fn test_db_new_catagory() {
+        let mut catagory = Catagory::new("resistor");
+
+        catagory.add_field(CatagoryField::from_str("mpn:t").unwrap());
+        catagory.add_field(CatagoryField::from_str("mfcd_by:t").unwrap());
+        catagory.add_field(CatagoryField::from_str("ohms:r").unwrap());
+        catagory.add_field(CatagoryField::from_str("watts:r").unwrap());
+        catagory.add_field(CatagoryField::from_str("tolerance:r").unwrap());
+        catagory.add_field(CatagoryField::from_str("ppm_c:r").unwrap());
+        catagory.add_field(CatagoryField::from_str("term_style:t").unwrap());
+        catagory.add_field(CatagoryField::from_str("makeup:t").unwrap());
+        catagory.add_field(CatagoryField::from_str("case_code:t").unwrap());
+        catagory.add_field(CatagoryField::from_str("datasheet:t").unwrap());
+
+        assert_eq!(catagory, test_catagory_a());
+    }
+    // Test adding a catagory to a database
+    #[test]
+    fn test_db_add_catagory() {
+        let mut db = Db::_new_test();
+        let catagory_a = test_catagory_a();
+        let catagory_b = test_catagory_b();
+
+        // Shouldn't fail the first time
+        db.add_catagory(catagory_a.clone()).unwrap();
+
+        // Should fail the second time
+        db.add_catagory(catagory_a).unwrap_err();
+
+        // Test adding multiple catagories
+        db.add_catagory(catagory_b).unwrap();
+    }
+
+    #[test]
+    fn test_db_add_catagory_field() {
+        let mut db = Db::_new_test();
+
+        // The catagory has to exist first
+        db.add_catagory_field("RESISTOR", CatagoryField::new("LEADS", DataType::INTEGER))
+            .unwrap_err();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        db.add_catagory_field("RESISTOR", CatagoryField::new("LEADS", DataType::INTEGER))
+            .unwrap();
+
+        // pinv's own tables aren't catagories
+        db.add_catagory_field("METADATA", CatagoryField::new("LEADS", DataType::INTEGER))
+            .unwrap_err();
+
+        // Can't add the same field twice, or a field with a bad id
+        db.add_catagory_field("RESISTOR", CatagoryField::new("LEADS", DataType::INTEGER))
+            .unwrap_err();
+        db.add_catagory_field("RESISTOR", CatagoryField::new("leads;", DataType::INTEGER))
+            .unwrap_err();
+
+        // Entries already in the catagory have nothing in the new field
+        let mut entry_0 = test_entry_0();
+        entry_0.add_field(EntryField::new("LEADS", ""));
+
+        assert_eq!(db.grab_entry(0).unwrap(), entry_0);
+
+        // New entries can set it
+        let mut entry_1 = test_entry_1();
+        entry_1.add_field(EntryField::new("LEADS", "2"));
+        db.add_entry(entry_1.clone()).unwrap();
+
+        assert_eq!(db.grab_entry(1).unwrap(), entry_1);
+        assert_eq!(
+            db.search_catagory(
+                "RESISTOR",
+                &[Condition::new("LEADS", ConditionOperator::Equal, "2")],
+                None
+            )
+            .unwrap(),
+            vec![entry_1]
+        );
+    }
+
+    #[test]
+    fn test_db_delete_catagory_cascade() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        // The catagory isn't empty, so it can only be deleted with a cascade
+        db.delete_empty_catagory("RESISTOR").unwrap_err();
+
+        assert_eq!(db.delete_catagory_cascade("RESISTOR").unwrap(), 2);
+
+        // Every entry deleted with it is recorded
+        let history: Vec<(Operation, String, u64)> = db
+            .history(Some(2))
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.operation, record.catagory_id, record.key))
+            .collect();
+
+        assert_eq!(
+            history,
+            vec![
+                (Operation::Delete, "RESISTOR".to_owned(), 1),
+                (Operation::Delete, "RESISTOR".to_owned(), 0),
+            ]
+        );
+
+        assert_eq!(db.list_catagories().unwrap(), vec!["CAPACITOR"]);
+        db.grab_catagory_from_key(0).unwrap_err();
+        db.grab_catagory_from_key(1).unwrap_err();
+        assert_eq!(db.find_orphans().unwrap(), vec![]);
+
+        db.delete_catagory_cascade("RESISTOR").unwrap_err();
+    }
+
+    #[test]
+    fn test_db_add_entries() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        let entries = vec![
+            test_entry_0(),
+            test_entry_1(),
+            test_entry_2(),
+            test_entry_3(),
+        ];
+
+        assert_eq!(db.add_entries(entries.clone()).unwrap(), 4);
+
+        for entry in entries {
+            // Fields left out of the test entries come back empty
+            let mut grabbed = db.grab_entry(entry.key).unwrap();
+            grabbed.fields.retain(|field| !field.value.is_empty());
+
+            assert_eq!(grabbed, entry);
+        }
+
+        // One bad entry stops the whole batch
+        let mut bad_entry = test_entry_0();
+        bad_entry.key = 5;
+        bad_entry.location = "".to_owned();
+
+        let mut good_entry = test_entry_1();
+        good_entry.key = 4;
+
+        db.add_entries(vec![good_entry, bad_entry]).unwrap_err();
+        db.grab_entry(4).unwrap_err();
+        assert_eq!(db.grab_next_available_key(4).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_db_add_catagory_with_entries() {
+        let mut db = Db::_new_test();
+
+        let mut bad_entry = test_entry_1();
+        bad_entry.location = "".to_owned();
+
+        // Neither the catagory nor the good entry is kept
+        db.add_catagory_with_entries(test_catagory_a(), vec![test_entry_0(), bad_entry])
+            .unwrap_err();
+        assert_eq!(db.list_catagories().unwrap(), Vec::<String>::new());
+        db.grab_entry(0).unwrap_err();
+
+        // Entries have to be in the new catagory
+        let error = db
+            .add_catagory_with_entries(test_catagory_a(), vec![test_entry_2()])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Entry 2 is in CAPACITOR instead of RESISTOR!"
+        );
+
+        assert_eq!(
+            db.add_catagory_with_entries(test_catagory_a(), vec![test_entry_0(), test_entry_1()])
+                .unwrap(),
+            2
+        );
+        assert_eq!(db.list_catagories().unwrap(), vec!["RESISTOR"]);
+        assert_eq!(db.grab_entry(1).unwrap().quantity, 2);
+
+        db.add_catagory_with_entries(test_catagory_a(), vec![])
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_db_next_available_key_dense() {
+        let db = Db::_new_test();
+
+        // A dense block of keys from 0, with a gap at 700
+        let mut statement = db
+            .connection
+            .prepare("INSERT INTO KEYS (KEY, CATAGORY) VALUES (?, 'RESISTOR')")
+            .unwrap();
+
+        for key in (0..1000).filter(|key| *key != 700) {
+            statement.execute(params![key]).unwrap();
+        }
+
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 700);
+        assert_eq!(db.grab_next_available_key(500).unwrap(), 700);
+        assert_eq!(db.grab_next_available_key(700).unwrap(), 700);
+        assert_eq!(db.grab_next_available_key(701).unwrap(), 1000);
+        assert_eq!(db.grab_next_available_key(5000).unwrap(), 5000);
+    }
+
+    #[test]
+    fn test_db_key_strategies() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        assert_eq!(db.grab_max_key_plus_one().unwrap(), 0);
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let mut entry = test_entry_0();
+        entry.key = 2;
+        db.add_entry(entry).unwrap();
+
+        db.delete_entry(1).unwrap();
+
+        // Filling gaps gives the deleted key back, new keys come after it all
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 1);
+        assert_eq!(db.grab_max_key_plus_one().unwrap(), 3);
+
+        // Even the largest key isn't reused once it's deleted
+        db.delete_entry(2).unwrap();
+
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 1);
+        assert_eq!(db.grab_max_key_plus_one().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_db_entry_transactions() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        // An empty location is NULL, which the catagory table won't take
+        let mut entry = test_entry_0();
+        entry.location = "".to_owned();
+
+        db.add_entry(entry).unwrap_err();
+
+        // The key shouldn't have been left behind in the key table
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 0);
+        db.grab_catagory_from_key(0).unwrap_err();
+
+        db.add_entry(test_entry_0()).unwrap();
+
+        // Same goes for modifying, the key change shouldn't stick either
+        db.mod_entry(
+            0,
+            vec![EntryField::new("KEY", "1"), EntryField::new("LOCATION", "")],
+        )
+        .unwrap_err();
+
+        assert_eq!(db.grab_entry(0).unwrap(), test_entry_0());
+        db.grab_catagory_from_key(1).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_find_orphans() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        assert_eq!(db.find_orphans().unwrap(), vec![]);
+
+        // Drop the table behind pinv's back, leaving its keys in the key table
+        db.connection.execute("DROP TABLE RESISTOR", []).unwrap();
+
+        let orphans = vec![(0, "RESISTOR".to_owned()), (1, "RESISTOR".to_owned())];
+
+        assert_eq!(db.find_orphans().unwrap(), orphans);
+        db.grab_entry(0).unwrap_err();
+
+        assert_eq!(db.remove_orphans().unwrap(), orphans);
+        assert_eq!(db.find_orphans().unwrap(), vec![]);
+
+        // The keys are free to use again
+        assert_eq!(db.grab_next_available_key(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_db_grab_entry_in() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_2()).unwrap();
+
+        // Take the key out of the key table, so only the hint can find it
+        db.connection
+            .execute("DELETE FROM KEYS WHERE KEY=0", [])
+            .unwrap();
+
+        db.grab_entry(0).unwrap_err();
+        assert_eq!(db.grab_entry_in(0, "RESISTOR").unwrap(), test_entry_0());
+
+        // A wrong hint falls back to the key table
+        let entry = db.grab_entry(2).unwrap();
+
+        assert_eq!(db.grab_entry_in(2, "RESISTOR").unwrap(), entry);
+        assert_eq!(db.grab_entry_in(2, "INDUCTOR").unwrap(), entry);
+        db.grab_entry_in(0, "CAPACITOR").unwrap_err();
+    }
+
+    #[test]
+    fn test_db_rename_catagory_field() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.set_display_order("RESISTOR", &["MFCD_BY".to_owned()])
+            .unwrap();
+
+        db.rename_catagory_field("RESISTOR", "MFCD_BY", "MANUFACTURER")
+            .unwrap();
+
+        let fields = db.grab_catagory_fields("RESISTOR").unwrap();
+
+        assert!(fields.contains(&"MANUFACTURER".to_owned()));
+        assert!(!fields.contains(&"MFCD_BY".to_owned()));
+
+        // The value stays, and so does its place in the display order
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(
+            entry.fields[0],
+            EntryField::new("MANUFACTURER", "Panasonic")
+        );
+
+        // Builtin fields, missing fields, and taken names are all errors
+        db.rename_catagory_field("RESISTOR", "LOCATION", "PLACE")
+            .unwrap_err();
+        db.rename_catagory_field("RESISTOR", "OHMS", "KEY")
+            .unwrap_err();
+        db.rename_catagory_field("RESISTOR", "MFCD_BY", "MAKER")
+            .unwrap_err();
+        db.rename_catagory_field("RESISTOR", "OHMS", "MPN")
+            .unwrap_err();
+        db.rename_catagory_field("RESISTOR", "OHMS", "ohms")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_db_display_order() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        let order = vec!["DATASHEET".to_owned(), "OHMS".to_owned()];
+
+        // Every field has to be in the catagory, and only listed once
+        db.set_display_order("RESISTOR", &["COLOR".to_owned()])
+            .unwrap_err();
+        db.set_display_order("RESISTOR", &["OHMS".to_owned(), "OHMS".to_owned()])
+            .unwrap_err();
+
+        db.set_display_order("RESISTOR", &order).unwrap();
+
+        // The ordered fields come first, then the rest as they're stored
+        let mut entry = test_entry_0();
+        entry.sort_fields(&order);
+
+        assert_eq!(entry.fields[0].id, "DATASHEET");
+        assert_eq!(entry.fields[1].id, "OHMS");
+        assert_eq!(entry.fields[2].id, "MPN");
+        assert_eq!(db.grab_entry(0).unwrap(), entry);
+
+        let shown = db.grab_entry(0).unwrap().to_string();
+        assert!(shown.find("DATASHEET").unwrap() < shown.find("MPN").unwrap());
+
+        assert_eq!(&db.light_fields("RESISTOR").unwrap()[5..7], &order[..]);
+        assert!(db
+            .export_catagory_csv("RESISTOR")
+            .unwrap()
+            .starts_with("KEY,LOCATION,QUANTITY,CREATED,MODIFIED,DATASHEET,OHMS,MPN,"));
+
+        // Storage stays the same
+        assert_eq!(
+            db.grab_catagory_fields("RESISTOR").unwrap()[5..7],
+            ["MPN", "MFCD_BY"]
+        );
+
+        // Fields added later still show up, at the end
+        db.add_catagory_field("RESISTOR", CatagoryField::new("LEADS", DataType::INTEGER))
+            .unwrap();
+        assert_eq!(
+            db.display_order("RESISTOR").unwrap().last().unwrap(),
+            "LEADS"
+        );
+
+        // An empty order goes back to storage order
+        db.set_display_order("RESISTOR", &[]).unwrap();
+        assert_eq!(
+            db.display_order("RESISTOR").unwrap(),
+            db.grab_catagory_fields_opts("RESISTOR", false).unwrap()
+        );
+    }
+
+    // Test creating an entry
+    #[test]
+    fn test_db_new_entry() {
+        let mut entry = Entry::new("resistor", 0, "bazville", 10, 0, 0);
+
+        entry.add_field(EntryField::from_str("mpn=ERJ-PM8F8204V").unwrap());
+        entry.add_field(EntryField::from_str("mfcd_by=Panasonic").unwrap());
+        entry.add_field(EntryField::from_str("ohms=8.2e6").unwrap());
+        entry.add_field(EntryField::from_str("watts=6.6e-1").unwrap());
+        entry.add_field(EntryField::from_str("tolerance=1e-2").unwrap());
+        entry.add_field(EntryField::from_str("ppm_c=1e2").unwrap());
+        entry.add_field(EntryField::from_str("term_style=SMD").unwrap());
+        entry.add_field(EntryField::from_str("makeup=Thick Film").unwrap());
+        entry.add_field(EntryField::from_str("case_code=1206").unwrap());
+        entry.add_field(EntryField::from_str("datasheet=https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf").unwrap());
+
+        assert_eq!(entry, test_entry_0());
+    }
+
+    #[test]
+    fn test_db_entry_field_from_str() {
+        let field = EntryField::from_str("datasheet=https://x?a=b&c=d").unwrap();
+
+        assert_eq!(field, EntryField::new("DATASHEET", "https://x?a=b&c=d"));
+
+        EntryField::from_str("datasheet").unwrap_err();
+    }
+
+    #[test]
+    fn test_db_add_entry() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        // test entry 0 belongs to catagory a, so this should return an error
+        db.add_entry(test_entry_0()).unwrap_err();
+
+        // add catagory a and expect no errors
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        // try to add the same entry again and expect an error
+        db.add_entry(test_entry_0()).unwrap_err();
+
+        // add a new entry and expect no errors
+        db.add_entry(test_entry_1()).unwrap();
+
+        // Just change the catagory on the entry 0 and expect an error
+        let mut entry_0 = test_entry_0();
+
+        entry_0.catagory_id = "CAPACITOR".to_owned();
+
+        db.add_entry(entry_0).unwrap_err();
+
+        // Add a different type of entry to a different catagory
+        db.add_entry(test_entry_2()).unwrap();
+
+        // Add another entry to catagory b
+        db.add_entry(test_entry_3()).unwrap();
+
+        // Change the key to one that exists in another catagory, expect an error
+        let mut entry_3 = test_entry_3();
+
+        entry_3.key = 0;
+
+        db.add_entry(entry_3).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_grab_by_key() {
+        let mut db = Db::_new_test();
+
+        // Should fail
+        db.grab_entry(0).unwrap_err();
+        // Add the catagories and entries we need
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        // Should still fail
+        db.grab_entry(0).unwrap_err();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        // Grab the first entry by it's key
+
+        let entry_0 = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry_0, test_entry_0());
+    }
+
+    #[test]
+    fn test_db_delete_by_key() {
+        let mut db = Db::_new_test();
+
+        // Should fail
+        db.delete_entry(0).unwrap_err();
+        // Add catagories and entries needed
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        // Should still fail
+        db.delete_entry(0).unwrap_err();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        // Delete the first entry
+        db.delete_entry(0).unwrap();
+
+        // Try to grab it(should fail!)
+        db.grab_entry(0).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_all_keys() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        assert_eq!(db.all_keys().unwrap(), vec![]);
+
+        // Added out of order, listed in key order
+        db.add_entry(test_entry_3()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_2()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        assert_eq!(
+            db.all_keys().unwrap(),
+            vec![
+                (0, "RESISTOR".to_owned()),
+                (1, "RESISTOR".to_owned()),
+                (2, "CAPACITOR".to_owned()),
+                (3, "CAPACITOR".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_db_delete_entries() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        // Should fail without deleting anything, key 2 isn't in use
+        db.delete_entries(&[0, 2]).unwrap_err();
+        db.grab_entry(0).unwrap();
+
+        // Keys can't be deleted twice over
+        assert_eq!(
+            db.delete_entries(&[0, 1, 0]).unwrap_err().to_string(),
+            "Key 0 is given more than once!"
+        );
+        db.grab_entry(0).unwrap();
+        assert_eq!(db.history(None).unwrap().len(), 2);
+
+        // Entries in different catagories can be deleted together
+        assert_eq!(db.delete_entries(&[0, 1]).unwrap(), 2);
+
+        db.grab_entry(0).unwrap_err();
+        db.grab_entry(1).unwrap_err();
+        assert_eq!(db.history(None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_db_format_entry() {
+        // Entries should be formatted a certian way, alike the comments above
+        //
+        //  ENTRY {key}, CATAGORY {catagory}:
+        //      LOCATION     = {location},
+        //      QUANTITY     = {quantity},
+        //      {field_1_id} = {field_1_val},
+        //      {field_2_id} = {field_2_val},
+        //      ...
+        //      {field_x_id} = {field_x_val}
+
+        let test_string: String = format!(
+            r#"ENTRY 0, CATAGORY RESISTOR:
+    LOCATION   = bazville,
+    QUANTITY   = 10,
+    CREATED    = {time},
+    MODIFIED   = {time},
+    MPN        = ERJ-PM8F8204V,
+    MFCD_BY    = Panasonic,
+    OHMS       = 8.2e6,
+    WATTS      = 6.6e-1,
+    TOLERANCE  = 1e-2,
+    PPM_C      = 1e2,
+    TERM_STYLE = SMD,
+    MAKEUP     = Thick Film,
+    CASE_CODE  = 1206,
+    DATASHEET  = https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf"#,
+            time = Config::default().format_timestamp(0)
+        );
+
+        assert_eq!(test_string, format!("{}", test_entry_0()));
+
+        // The config picks how the timestamps are shown
+        let config =
+            Config::from_toml("timestamp_format = \"%d/%m/%Y\"\ntimezone = \"UTC\"").unwrap();
+
+        assert!(test_entry_0()
+            .formatted(&config)
+            .contains("CREATED    = 01/01/1970,\n    MODIFIED   = 01/01/1970,"));
+    }
+
+    #[test]
+    fn test_db_entry_datetimes() {
+        let entry = Entry::new("RESISTOR", 0, "bazville", 10, 1_700_000_000, 1_700_000_060);
+
+        // Compare in UTC so the test doesn't depend on the local time zone
+        assert_eq!(
+            entry
+                .created_datetime()
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339(),
+            "2023-11-14T22:13:20+00:00"
+        );
+        assert_eq!(
+            entry
+                .modified_datetime()
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+                .to_rfc3339(),
+            "2023-11-14T22:14:20+00:00"
+        );
+
+        let created = DateTime::parse_from_rfc3339(&entry.created_rfc3339().unwrap()).unwrap();
+
+        assert_eq!(created.timestamp(), 1_700_000_000);
+        assert_eq!(entry.created_datetime().unwrap().timestamp(), entry.created);
+
+        // Out of range timestamps can't be a datetime
+        let entry = Entry::new("RESISTOR", 0, "bazville", 10, i64::MAX, i64::MIN);
+
+        assert_eq!(entry.created_datetime(), None);
+        assert_eq!(entry.modified_datetime(), None);
+        assert_eq!(entry.created_rfc3339(), None);
+    }
+
+    #[test]
+    fn test_db_search_catagory() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        assert_eq!(
+            db.search_catagory(
+                "RESISTOR",
+                &[Condition::new("OHMS", ConditionOperator::Equal, "8.2e6")],
+                None
+            )
+            .unwrap()[0],
+            test_entry_0()
+        );
+    }
+
+    #[test]
+    fn test_db_quoted_values() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        let mut entry = test_entry_0();
+        entry.location = "O'Brien's bin".to_owned();
+        entry.fields[0].value = "Bob's \"best\" resistor; DROP TABLE KEYS".to_owned();
+        db.add_entry(entry.clone()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        assert_eq!(db.grab_entry(0).unwrap(), entry);
+
+        let found = db
+            .search_catagory(
+                "RESISTOR",
+                &[Condition::new(
+                    "MPN",
+                    ConditionOperator::Equal,
+                    "Bob's \"best\" resistor; DROP TABLE KEYS",
+                )],
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(found, vec![entry]);
+
+        db.mod_entry(1, vec![EntryField::new("LOCATION", "Ma's drawer")])
+            .unwrap();
+
+        assert_eq!(db.grab_entry(1).unwrap().location, "Ma's drawer");
+    }
+
+    #[test]
+    fn test_db_blob_fields() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory).unwrap();
+
+        // Small blobs are given as hex and shown in full
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", "0x00ff10"));
+        db.add_entry(entry.clone()).unwrap();
+
+        assert_eq!(
+            db.grab_entry_blob(0, "PHOTO").unwrap(),
+            vec![0x00, 0xff, 0x10]
+        );
+        assert_eq!(db.grab_entry(0).unwrap(), entry);
+
+        // Paths are only read when asked to, never by add_entry
+        let payload: Vec<u8> = (0..40).collect();
+        let path = std::env::temp_dir().join("pinv_test_db_blob_fields.bin");
+        fs::write(&path, &payload).unwrap();
+
+        let mut entry = test_entry_1();
+        entry.add_field(EntryField::new("PHOTO", path.to_str().unwrap()));
+        db.add_entry(entry.clone()).unwrap_err();
+
+        // Larger blobs are only previewed
+        db.read_blob_files("RESISTOR", &mut entry.fields).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Other fields are left alone
+        assert_eq!(entry.fields[0], test_entry_1().fields[0]);
+
+        db.add_entry(entry).unwrap();
+
+        assert_eq!(db.grab_entry_blob(1, "PHOTO").unwrap(), payload);
+        assert!(db
+            .grab_entry(1)
+            .unwrap()
+            .fields
+            .last()
+            .unwrap()
+            .value
+            .starts_with("0x000102"));
+        assert!(db
+            .grab_entry(1)
+            .unwrap()
+            .fields
+            .last()
+            .unwrap()
+            .value
+            .ends_with("... (40 bytes)"));
+
+        // Bad hex is rejected, and only blob fields can be grabbed as blobs
+        db.mod_entry(0, vec![EntryField::new("PHOTO", "0xfg")])
+            .unwrap_err();
+        db.grab_entry_blob(0, "MPN").unwrap_err();
+    }
+
+    #[test]
+    fn test_db_export_blob_fields() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory.clone()).unwrap();
+
+        // Long enough to be cut short when shown
+        let payload: Vec<u8> = (0..40).collect();
+
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", &Db::blob_to_hex(&payload)));
+        db.add_entry(entry).unwrap();
+
+        let json = db.export_catagory_json("RESISTOR").unwrap();
+        let csv = db.export_catagory_csv("RESISTOR").unwrap();
+
+        assert!(!json.contains("bytes)"));
+        assert!(!csv.contains("bytes)"));
+
+        // Both read back into another database with the whole blob
+        let mut json_db = Db::_new_test();
+        json_db.add_catagory(catagory.clone()).unwrap();
+        json_db.import_catagory_json(&json).unwrap();
+
+        assert_eq!(json_db.grab_entry_blob(0, "PHOTO").unwrap(), payload);
+
+        let mut csv_db = Db::_new_test();
+        csv_db.add_catagory(catagory).unwrap();
+
+        csv_db
+            .add_entries(csv::csv_to_entries(&csv, "RESISTOR", ',').unwrap())
+            .unwrap();
+
+        assert_eq!(csv_db.grab_entry_blob(0, "PHOTO").unwrap(), payload);
+    }
+
+    #[test]
+    fn test_db_search_catagory_sorted() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        for (key, quantity) in [(0, 5), (1, 20), (2, 1), (3, 5)] {
+            let mut entry = test_entry_0();
+            entry.key = key;
+            entry.quantity = quantity;
+            db.add_entry(entry).unwrap();
+        }
+
+        let keys = |order| -> Vec<u64> {
+            db.search_catagory_sorted("RESISTOR", &[], None, Some(&Sort::new("quantity", order)))
+                .unwrap()
+                .iter()
+                .map(|entry| entry.key)
+                .collect()
+        };
+
+        assert_eq!(keys(SortOrder::Ascending), vec![2, 0, 3, 1]);
+        assert_eq!(keys(SortOrder::Descending), vec![1, 0, 3, 2]);
+
+        db.search_catagory_sorted(
+            "RESISTOR",
+            &[],
+            None,
+            Some(&Sort::new("FOO", SortOrder::Ascending)),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_db_search_catagory_page() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        for key in 0..50 {
+            let mut entry = test_entry_0();
+            entry.key = key;
+            db.add_entry(entry).unwrap();
+        }
+
+        let keys = |offset, limit| -> Vec<u64> {
+            db.search_catagory_page("RESISTOR", &[], None, offset, limit)
+                .unwrap()
+                .iter()
+                .map(|entry| entry.key)
+                .collect()
+        };
+
+        assert_eq!(keys(0, 20), (0..20).collect::<Vec<u64>>());
+        assert_eq!(keys(20, 20), (20..40).collect::<Vec<u64>>());
+        assert_eq!(keys(40, 20), (40..50).collect::<Vec<u64>>());
+        assert!(keys(50, 20).is_empty());
+
+        // Pages follow the sort order
+        let sort = Sort::new("KEY", SortOrder::Descending);
+        let page = db
+            .search_catagory_page("RESISTOR", &[], Some(&sort), 10, 5)
+            .unwrap();
+
+        assert_eq!(
+            page.iter().map(|entry| entry.key).collect::<Vec<u64>>(),
+            vec![39, 38, 37, 36, 35]
+        );
+
+        db.search_catagory_page("RESISTOR", &[], None, u64::MAX, 5)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_db_search_catagory_operators() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let search = |condition: Condition| -> Vec<Entry> {
+            db.search_catagory("RESISTOR", &[condition], None).unwrap()
+        };
+
+        let condition = Condition::new("OHMS", ConditionOperator::GreaterThan, "5e6");
+        assert_eq!(condition.to_string(), "OHMS>5e6");
+        assert_eq!(search(condition), vec![test_entry_0()]);
+
+        let condition = Condition::new("QUANTITY", ConditionOperator::LessThanEqual, "5");
+        assert_eq!(condition.to_string(), "QUANTITY<=5");
+        assert_eq!(search(condition), vec![test_entry_1()]);
+
+        // Keys are compared as numbers, but given in base64
+        let condition = Condition::new("KEY", ConditionOperator::NotEqual, "0");
+        assert_eq!(search(condition), vec![test_entry_1()]);
+    }
+
+    #[test]
+    fn test_db_search_catagory_connectors() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let ohms_39 = Condition::new("OHMS", ConditionOperator::Equal, "39");
+        let ohms_8m2 = Condition::new("OHMS", ConditionOperator::Equal, "8.2e6")
+            .with_connector(ConditionConnector::Or);
+        let plenty = Condition::new("QUANTITY", ConditionOperator::GreaterThan, "5");
+
+        let conditions = [ohms_39.clone(), ohms_8m2.clone()];
+        assert_eq!(
+            db.search_catagory("RESISTOR", &conditions, None).unwrap(),
+            vec![test_entry_0(), test_entry_1()]
+        );
+
+        // Conditions are joined left to right, so the AND applies to both
+        // sides of the OR
+        let conditions = [ohms_39.clone(), ohms_8m2.clone(), plenty.clone()];
+        assert_eq!(
+            Condition::join(&conditions, |condition| condition.to_string()),
+            "(OHMS=39 OR OHMS=8.2e6) AND QUANTITY>5"
+        );
+        assert_eq!(
+            db.search_catagory("RESISTOR", &conditions, None).unwrap(),
+            vec![test_entry_0()]
+        );
+
+        let ohms_39 = ohms_39.with_connector(ConditionConnector::Or);
+        let conditions = [
+            plenty,
+            ohms_8m2.with_connector(ConditionConnector::And),
+            ohms_39,
+        ];
+        assert_eq!(
+            db.search_catagory("RESISTOR", &conditions, None).unwrap(),
+            vec![test_entry_0(), test_entry_1()]
+        );
+
+        // Keys narrow down the whole group, not just the last condition
+        assert_eq!(
+            db.search_catagory("RESISTOR", &conditions, Some(&[1]))
+                .unwrap(),
+            vec![test_entry_1()]
+        );
+    }
+
+    #[test]
+    fn test_db_count_matching() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        assert_eq!(db.count_matching("RESISTOR", &[]).unwrap(), 0);
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let conditions = [Condition::new(
+            "OHMS",
+            ConditionOperator::GreaterThan,
+            "5e6",
+        )];
+
+        assert_eq!(db.count_matching("RESISTOR", &[]).unwrap(), 2);
+        assert_eq!(
+            db.count_matching("RESISTOR", &conditions).unwrap(),
+            db.search_catagory("RESISTOR", &conditions, None)
+                .unwrap()
+                .len() as u64
+        );
+        assert_eq!(db.count_matching("RESISTOR", &conditions).unwrap(), 1);
+
+        let conditions = [Condition::new(
+            "OHMS",
+            ConditionOperator::GreaterThan,
+            "1e9",
+        )];
+
+        assert_eq!(db.count_matching("RESISTOR", &conditions).unwrap(), 0);
+
+        db.count_matching("", &[]).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_count_by_location() {
+        let mut db = Db::_new_test();
+
+        assert!(db.count_by_location().unwrap().is_empty());
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        let mut entry_3 = test_entry_3();
+        entry_3.location = "shelf 4".to_owned();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+        db.add_entry(test_entry_2()).unwrap();
+        db.add_entry(entry_3).unwrap();
+
+        assert_eq!(
+            db.count_by_location().unwrap(),
+            vec![
+                ("barville".to_owned(), 1, 21),
+                ("bazville".to_owned(), 2, 12),
+                ("shelf 4".to_owned(), 1, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_db_search_all() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        let mut entry_2 = test_entry_2();
+        entry_2.location = "bazville".to_owned();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+        db.add_entry(entry_2).unwrap();
+        db.add_entry(test_entry_3()).unwrap();
+
+        let keys = |conditions: &[Condition]| -> Vec<u64> {
+            db.search_all(conditions)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.key)
+                .collect()
+        };
+
+        // Every catagory has a location, capacitors come first
+        let at_bazville = [Condition::new(
+            "LOCATION",
+            ConditionOperator::Equal,
+            "bazville",
+        )];
+
+        assert_eq!(keys(&at_bazville), vec![2, 0, 1]);
+
+        // Only capacitors have FARADS, so resistors aren't searched at all
+        let has_farads = [Condition::new(
+            "FARADS",
+            ConditionOperator::GreaterThan,
+            "0",
+        )];
+
+        assert_eq!(keys(&has_farads), vec![2, 3]);
+        assert_eq!(keys(&[]), vec![2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_db_low_stock() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        // Quantities of 10, 2, 21 and 100
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+        db.add_entry(test_entry_2()).unwrap();
+        db.add_entry(test_entry_3()).unwrap();
+
+        let low: Vec<(String, u64)> = db
+            .low_stock(21)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.catagory_id, entry.key))
+            .collect();
+
+        assert_eq!(
+            low,
+            vec![
+                ("CAPACITOR".to_owned(), 2),
+                ("RESISTOR".to_owned(), 1),
+                ("RESISTOR".to_owned(), 0),
+            ]
+        );
+
+        assert!(db.low_stock(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_db_sum_field() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        assert_eq!(db.sum_field("RESISTOR", "QUANTITY", &[]).unwrap(), 0.0);
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        assert_eq!(db.sum_field("RESISTOR", "QUANTITY", &[]).unwrap(), 12.0);
+        assert_eq!(db.sum_field("RESISTOR", "WATTS", &[]).unwrap(), 2.66);
+
+        let conditions = [Condition::new(
+            "OHMS",
+            ConditionOperator::GreaterThan,
+            "5e6",
+        )];
+
+        assert_eq!(
+            db.sum_field("RESISTOR", "QUANTITY", &conditions).unwrap(),
+            10.0
+        );
+
+        let error = db.sum_field("RESISTOR", "MPN", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Can't sum MPN, it's TEXT instead of a number!"
+        );
+
+        db.sum_field("RESISTOR", "FARADS", &[]).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_search_catagory_missing() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        let error = db.search_catagory("", &[], None).unwrap_err();
+        assert_eq!(error.to_string(), "Catagory name can't be empty!");
+
+        let error = db.search_catagory("CAPACITOR", &[], None).unwrap_err();
+        assert_eq!(error.to_string(), "Catagory CAPACITOR not found!");
+
+        db.grab_catagory_fields("").unwrap_err();
+        db.search_catagory_light("", &[], None).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_search_catagory_light() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory).unwrap();
+
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", "0x00ff10"));
+        db.add_entry(entry.clone()).unwrap();
+
+        // The light load leaves out the blob but keeps everything else
+        let light = db.search_catagory_light("RESISTOR", &[], None).unwrap();
+
+        assert_eq!(light, vec![test_entry_0()]);
+        assert!(!db
+            .light_fields("RESISTOR")
+            .unwrap()
+            .contains(&"PHOTO".to_owned()));
+
+        // The detail load fills it in
+        assert_eq!(db.grab_entry(light[0].key).unwrap(), entry);
+
+        // Light loads can be sorted too, like the entry view does
+        db.add_entry(test_entry_1()).unwrap();
+
+        let keys = |sort: Sort| -> Vec<u64> {
+            db.search_catagory_light("RESISTOR", &[], Some(&sort))
+                .unwrap()
+                .iter()
+                .map(|entry| entry.key)
+                .collect()
+        };
+
+        assert_eq!(keys(Sort::new("OHMS", SortOrder::Ascending)), vec![1, 0]);
+        assert_eq!(keys(Sort::new("key", SortOrder::Descending)), vec![1, 0]);
+        assert_eq!(
+            keys(Sort::new("QUANTITY", SortOrder::Descending)),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_db_search_catagory_contains() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let search = |condition: Condition| -> Vec<Entry> {
+            db.search_catagory("RESISTOR", &[condition], None).unwrap()
+        };
+
+        let condition = Condition::new("MPN", ConditionOperator::Contains, "ERJ");
+        assert_eq!(condition.to_string(), "MPN contains ERJ");
+        assert_eq!(search(condition), vec![test_entry_0()]);
+
+        let condition = Condition::new("MPN", ConditionOperator::StartsWith, "erj-pm");
+        assert_eq!(search(condition), vec![test_entry_0()]);
+
+        // Wildcards in the value are matched literally
+        let condition = Condition::new("MPN", ConditionOperator::Contains, "%");
+        assert!(search(condition).is_empty());
+        let condition = Condition::new("MPN", ConditionOperator::StartsWith, "ERJ_");
+        assert!(search(condition).is_empty());
+    }
+
+    #[test]
+    fn test_db_search_catagory_keys() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        for key in 0..5 {
+            let mut entry = test_entry_0();
+            entry.key = key;
+            db.add_entry(entry).unwrap();
+        }
+
+        let keys: Vec<u64> = db
+            .search_catagory("RESISTOR", &[], Some(&[3, 1, 4]))
+            .unwrap()
+            .iter()
+            .map(|entry| entry.key)
+            .collect();
+
+        assert_eq!(keys, vec![1, 3, 4]);
+
+        assert!(db
+            .search_catagory("RESISTOR", &[], Some(&[]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_db_export_catagory_csv() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        let mut entry = test_entry_0();
+        entry.location = "shelf 1, bin 2".to_owned();
+        db.add_entry(entry).unwrap();
+
+        let csv = db.export_catagory_csv("RESISTOR").unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next().unwrap(),
+            db.grab_catagory_fields("RESISTOR").unwrap().join(",")
+        );
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("0,\"shelf 1, bin 2\",10,0,0,ERJ-PM8F8204V,Panasonic,"));
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_db_export_catagory_csv_round_trip() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        let mut entry = test_entry_0();
+        entry.location = "shelf \"A\", bin 2".to_owned();
+        db.add_entry(entry).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let exported = db.export_catagory_csv("RESISTOR").unwrap();
+
+        let mut imported_db = Db::_new_test();
+        imported_db.add_catagory(test_catagory_a()).unwrap();
+        imported_db
+            .add_entries(csv::csv_to_entries(&exported, "RESISTOR", ',').unwrap())
+            .unwrap();
+
+        assert_eq!(
+            imported_db.search_catagory("RESISTOR", &[], None).unwrap(),
+            db.search_catagory("RESISTOR", &[], None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_db_unique_field() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.fields[0] = CatagoryField::new("MPN", DataType::TEXT).unique();
+
+        db.add_catagory(catagory).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        // Same MPN, different key
+        let mut entry = test_entry_0();
+        entry.key = 1;
+
+        let error = db.add_entry(entry).unwrap_err();
+
+        assert_eq!(error.to_string(), "MPN 'ERJ-PM8F8204V' already exists!");
+        // The key wasn't taken either
+        assert!(db.grab_catagory_from_key(1).is_err());
+
+        // Modifying into a taken MPN is turned away the same way
+        db.add_entry(test_entry_1()).unwrap();
+
+        let error = db
+            .mod_entry(1, vec![EntryField::new("MPN", "ERJ-PM8F8204V")])
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "MPN 'ERJ-PM8F8204V' already exists!");
+
+        db.add_catagory_field(
+            "RESISTOR",
+            CatagoryField::new("SKU", DataType::TEXT).unique(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_db_history() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        db.mod_entry(0, vec![EntryField::new("QUANTITY", "20")])
+            .unwrap();
+        db.mod_entry(1, vec![EntryField::new("QUANTITY", "0")])
+            .unwrap();
+        db.mod_entry(0, vec![EntryField::new("LOCATION", "fooville")])
+            .unwrap();
+        db.delete_entry(1).unwrap();
+
+        let history: Vec<(Operation, u64)> = db
+            .history(None)
+            .unwrap()
+            .into_iter()
+            .map(|record| (record.operation, record.key))
+            .collect();
+
+        assert_eq!(
+            history,
+            vec![
+                (Operation::Delete, 1),
+                (Operation::Modify, 0),
+                (Operation::Take, 1),
+                (Operation::Give, 0),
+                (Operation::Add, 1),
+                (Operation::Add, 0),
+            ]
+        );
+
+        let history = db.history(Some(2)).unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].operation, Operation::Delete);
+        assert_eq!(history[0].catagory_id, "RESISTOR");
+
+        // Failed changes aren't recorded
+        db.mod_entry(0, vec![EntryField::new("OHMS", "lots")])
+            .unwrap_err();
+
+        assert_eq!(db.history(Some(1)).unwrap(), history[..1]);
+    }
+
+    #[test]
+    fn test_db_trash() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let entry = db.grab_entry(0).unwrap();
+
+        db.trash_entry(0).unwrap();
+
+        // Gone from the catagory...
+        db.grab_entry(0).unwrap_err();
+        assert_eq!(
+            db.search_catagory("RESISTOR", &[], None).unwrap(),
+            vec![db.grab_entry(1).unwrap()]
+        );
+
+        // ...but it can be brought back just like it was
+        db.restore_trashed(0).unwrap();
+        assert_eq!(db.grab_entry(0).unwrap(), entry);
+
+        // Restoring takes it out of the trash
+        assert_eq!(
+            db.restore_trashed(0).unwrap_err().to_string(),
+            "No entry 0 in the trash!"
+        );
+
+        // Keys used since trashing can't be restored over
+        db.trash_entry(1).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+        assert_eq!(
+            db.restore_trashed(1).unwrap_err().to_string(),
+            "Key 1 is already in use!"
+        );
+
+        db.delete_entry(1).unwrap();
+        db.trash_entry(0).unwrap();
+
+        assert_eq!(db.empty_trash().unwrap(), 2);
+        db.restore_trashed(0).unwrap_err();
+        db.restore_trashed(1).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_trash_blob() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory).unwrap();
+
+        // Too long to be shown in full
+        let payload: Vec<u8> = (0..40).collect();
+
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", &Db::blob_to_hex(&payload)));
+        db.add_entry(entry).unwrap();
+
+        db.trash_entry(0).unwrap();
+        db.restore_trashed(0).unwrap();
+
+        assert_eq!(db.grab_entry_blob(0, "PHOTO").unwrap(), payload);
+    }
+
+    #[test]
+    fn test_db_history_diff() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        let before = db.history(None).unwrap().len();
+
+        db.mod_entry(0, vec![EntryField::new("QUANTITY", "9")])
+            .unwrap();
+
+        let history = db.history(None).unwrap();
+
+        assert_eq!(history.len(), before + 1);
+        assert_eq!(history[0].operation, Operation::Take);
+        assert_eq!(history[0].diff, "QUANTITY: 10 -> 9");
+
+        // Unchanged fields are left out of the diff
+        db.mod_entry(
+            0,
+            vec![
+                EntryField::new("LOCATION", "bazville"),
+                EntryField::new("MFCD_BY", "Yageo"),
             ],
-        }
+        )
+        .unwrap();
+
+        assert_eq!(
+            db.history(Some(1)).unwrap()[0].diff,
+            "MFCD_BY: Panasonic -> Yageo"
+        );
+
+        // Adds don't have a diff
+        assert_eq!(history.last().unwrap().diff, "");
     }
 
-    // Test creating a field from a string
     #[test]
-    fn test_db_create_field_from_string() {
-        let field = CatagoryField::from_str("foo:i").unwrap();
+    fn test_db_history_record_display() {
+        let record = HistoryRecord {
+            timestamp: i64::MAX,
+            operation: Operation::Modify,
+            catagory_id: "RESISTOR".to_owned(),
+            key: 10,
+            diff: "QUANTITY: 10 -> 9".to_owned(),
+        };
 
-        assert_eq!(field.datatype, DataType::INTEGER);
+        // Out of range timestamps are printed as they're stored
+        assert_eq!(
+            record.to_string(),
+            format!("{} MODIFY RESISTOR A QUANTITY: 10 -> 9", i64::MAX)
+        );
     }
 
-    // Test getting the sql type from a field
     #[test]
-    fn test_db_get_sql_type() {
-        let field = CatagoryField::from_str("foo:i").unwrap();
+    fn test_db_immutable_field() {
+        let mut db = Db::_new_test();
 
-        assert_eq!(field.sql_type(), "INTEGER");
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("LOT", DataType::TEXT).immutable());
+
+        db.add_catagory(catagory).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        assert_eq!(db.immutable_fields("RESISTOR").unwrap(), vec!["LOT"]);
+
+        // The first value can still be set...
+        db.mod_entry(0, vec![EntryField::new("LOT", "A1")]).unwrap();
+        // ...and set again to the same thing
+        db.mod_entry(0, vec![EntryField::new("LOT", "A1")]).unwrap();
+
+        let error = db
+            .mod_entry(
+                0,
+                vec![
+                    EntryField::new("LOCATION", "fooville"),
+                    EntryField::new("LOT", "B2"),
+                ],
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Field LOT can't be changed once it's set!"
+        );
+
+        // Nothing was changed, but other fields are still editable
+        let entry = db.grab_entry(0).unwrap();
+        assert_eq!(entry.location, "bazville");
+
+        db.mod_entry(0, vec![EntryField::new("LOCATION", "fooville")])
+            .unwrap();
+
+        let entry = db.grab_entry(0).unwrap();
+        assert_eq!(entry.location, "fooville");
+        assert_eq!(entry.fields.last().unwrap().value, "A1");
+
+        // Renamed fields stay immutable
+        db.rename_catagory_field("RESISTOR", "LOT", "LOT_CODE")
+            .unwrap();
+        assert_eq!(db.immutable_fields("RESISTOR").unwrap(), vec!["LOT_CODE"]);
+
+        db.mod_entry(0, vec![EntryField::new("LOT_CODE", "B2")])
+            .unwrap_err();
     }
 
-    // Test creating a catagory
     #[test]
-    fn test_db_new_catagory() {
-        let mut catagory = Catagory::new("resistor");
+    fn test_db_move_entry() {
+        let mut db = Db::_new_test();
 
-        catagory.add_field(CatagoryField::from_str("mpn:t").unwrap());
-        catagory.add_field(CatagoryField::from_str("mfcd_by:t").unwrap());
-        catagory.add_field(CatagoryField::from_str("ohms:r").unwrap());
-        catagory.add_field(CatagoryField::from_str("watts:r").unwrap());
-        catagory.add_field(CatagoryField::from_str("tolerance:r").unwrap());
-        catagory.add_field(CatagoryField::from_str("ppm_c:r").unwrap());
-        catagory.add_field(CatagoryField::from_str("term_style:t").unwrap());
-        catagory.add_field(CatagoryField::from_str("makeup:t").unwrap());
-        catagory.add_field(CatagoryField::from_str("case_code:t").unwrap());
-        catagory.add_field(CatagoryField::from_str("datasheet:t").unwrap());
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(Catagory::with_fields(
+            "RESISTOR_SMD",
+            vec![
+                CatagoryField::new("MPN", DataType::TEXT),
+                CatagoryField::new("OHMS", DataType::REAL),
+                CatagoryField::new("WATTS", DataType::REAL),
+                CatagoryField::new("REEL", DataType::INTEGER),
+            ],
+        ))
+        .unwrap();
 
-        assert_eq!(catagory, test_catagory_a());
+        db.add_entry(test_entry_0()).unwrap();
+
+        let dropped = db.move_entry(0, "RESISTOR_SMD").unwrap();
+
+        assert_eq!(
+            dropped,
+            vec![
+                "MFCD_BY",
+                "TOLERANCE",
+                "PPM_C",
+                "TERM_STYLE",
+                "MAKEUP",
+                "CASE_CODE",
+                "DATASHEET"
+            ]
+        );
+
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry.catagory_id, "RESISTOR_SMD");
+        assert_eq!(entry.location, "bazville");
+        assert_eq!(entry.quantity, 10);
+        assert_eq!(
+            entry.fields,
+            vec![
+                EntryField::new("MPN", "ERJ-PM8F8204V"),
+                EntryField::new("OHMS", "8.2e6"),
+                EntryField::new("WATTS", "6.6e-1"),
+                EntryField::new("REEL", ""),
+            ]
+        );
+        assert_eq!(db.count_matching("RESISTOR", &[]).unwrap(), 0);
+
+        db.move_entry(0, "RESISTOR_SMD").unwrap_err();
+        db.move_entry(0, "CAPACITOR").unwrap_err();
+        assert_eq!(db.grab_entry(0).unwrap(), entry);
     }
-    // Test adding a catagory to a database
+
     #[test]
-    fn test_db_add_catagory() {
+    fn test_db_move_entry_blob() {
         let mut db = Db::_new_test();
-        let catagory_a = test_catagory_a();
-        let catagory_b = test_catagory_b();
 
-        // Shouldn't fail the first time
-        db.add_catagory(catagory_a.clone()).unwrap();
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory).unwrap();
+        db.add_catagory(Catagory::with_fields(
+            "RESISTOR_SMD",
+            vec![
+                CatagoryField::new("PHOTO", DataType::BLOB),
+                CatagoryField::new("OHMS", DataType::TEXT),
+            ],
+        ))
+        .unwrap();
 
-        // Should fail the second time
-        db.add_catagory(catagory_a).unwrap_err();
+        // Too long to be shown in full
+        let payload: Vec<u8> = (0..40).collect();
 
-        // Test adding multiple catagories
-        db.add_catagory(catagory_b).unwrap();
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", &Db::blob_to_hex(&payload)));
+        db.add_entry(entry).unwrap();
+
+        db.move_entry(0, "RESISTOR_SMD").unwrap();
+
+        assert_eq!(db.grab_entry_blob(0, "PHOTO").unwrap(), payload);
+
+        // Fields that change type are converted
+        let entry = db.grab_entry(0).unwrap();
+
+        assert!(entry.fields.contains(&EntryField::new("OHMS", "8.2e6")));
     }
 
-    // Test creating an entry
     #[test]
-    fn test_db_new_entry() {
-        let mut entry = Entry::new("resistor", 0, "bazville", 10, 0, 0);
+    fn test_db_export_catagory_json() {
+        let mut db = Db::_new_test();
 
-        entry.add_field(EntryField::from_str("mpn=ERJ-PM8F8204V").unwrap());
-        entry.add_field(EntryField::from_str("mfcd_by=Panasonic").unwrap());
-        entry.add_field(EntryField::from_str("ohms=8.2e6").unwrap());
-        entry.add_field(EntryField::from_str("watts=6.6e-1").unwrap());
-        entry.add_field(EntryField::from_str("tolerance=1e-2").unwrap());
-        entry.add_field(EntryField::from_str("ppm_c=1e2").unwrap());
-        entry.add_field(EntryField::from_str("term_style=SMD").unwrap());
-        entry.add_field(EntryField::from_str("makeup=Thick Film").unwrap());
-        entry.add_field(EntryField::from_str("case_code=1206").unwrap());
-        entry.add_field(EntryField::from_str("datasheet=https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf").unwrap());
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
 
-        assert_eq!(entry, test_entry_0());
+        let json = db.export_catagory_json("RESISTOR").unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[0]["key"], "0");
+        assert_eq!(value[0]["created"], 0);
+        assert_eq!(value[0]["fields"]["MFCD_BY"], "Panasonic");
+        assert_eq!(value[0]["fields"]["OHMS"], 8.2e6);
+
+        // And back again
+        let mut imported = Db::_new_test();
+
+        imported.add_catagory(test_catagory_a()).unwrap();
+        assert_eq!(imported.import_catagory_json(&json).unwrap(), 2);
+
+        assert_eq!(imported.grab_entry(0).unwrap(), db.grab_entry(0).unwrap());
+        assert_eq!(imported.grab_entry(1).unwrap(), db.grab_entry(1).unwrap());
+        assert_eq!(imported.export_catagory_json("RESISTOR").unwrap(), json);
     }
+
     #[test]
-    fn test_db_add_entry() {
+    fn test_db_import_catagory_json() {
         let mut db = Db::_new_test();
 
+        db.add_catagory(test_catagory_a()).unwrap();
         db.add_catagory(test_catagory_b()).unwrap();
 
-        // test entry 0 belongs to catagory a, so this should return an error
-        db.add_entry(test_entry_0()).unwrap_err();
+        let json = r#"[
+            {
+                "catagory_id": "RESISTOR",
+                "key": "A",
+                "location": "bin 1",
+                "quantity": 10,
+                "created": 1,
+                "modified": 2,
+                "fields": {"MPN": "ERJ-PM8F8204V", "OHMS": 8.2e6, "TOLERANCE": null}
+            },
+            {
+                "catagory_id": "CAPACITOR",
+                "key": "B",
+                "location": "bin 2",
+                "quantity": 5,
+                "created": 3,
+                "modified": 4,
+                "fields": {}
+            }
+        ]"#;
 
-        // add catagory a and expect no errors
-        db.add_catagory(test_catagory_a()).unwrap();
+        assert_eq!(db.import_catagory_json(json).unwrap(), 2);
+
+        let mut expected = Entry::new("RESISTOR", 10, "bin 1", 10, 1, 2);
+        expected.add_field(EntryField::new("MPN", "ERJ-PM8F8204V"));
+        expected.add_field(EntryField::new("OHMS", "8.2e6"));
+
+        let mut entry = db.grab_entry(10).unwrap();
+        entry.fields.retain(|field| !field.value.is_empty());
+
+        assert_eq!(entry, expected);
+        assert_eq!(db.grab_catagory_from_key(11).unwrap(), "CAPACITOR");
+
+        // Importing again collides with the keys, so nothing is imported
+        let error = db.import_catagory_json(json).unwrap_err();
+
+        assert_eq!(error.to_string(), "Key A is already in use!");
+        assert_eq!(db.count_matching("RESISTOR", &[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_db_export_import_entry() {
+        let mut db = Db::_new_test();
+
+        let mut catagory = test_catagory_a();
+        catagory.fields[0].unique = true;
+        catagory.fields[1].immutable = true;
+
+        db.add_catagory(catagory.clone()).unwrap();
         db.add_entry(test_entry_0()).unwrap();
 
-        // try to add the same entry again and expect an error
-        db.add_entry(test_entry_0()).unwrap_err();
+        assert_eq!(db.grab_catagory("RESISTOR").unwrap(), catagory);
 
-        // add a new entry and expect no errors
-        db.add_entry(test_entry_1()).unwrap();
+        let json = db.export_entry(0).unwrap();
 
-        // Just change the catagory on the entry 0 and expect an error
-        let mut entry_0 = test_entry_0();
+        // The catagory is made from the one included with the entry
+        let mut fresh_db = Db::_new_test();
 
-        entry_0.catagory_id = "CAPACITOR".to_owned();
+        assert_eq!(fresh_db.import_entry(&json).unwrap(), 0);
+        assert_eq!(fresh_db.grab_entry(0).unwrap(), db.grab_entry(0).unwrap());
+        assert_eq!(fresh_db.grab_catagory("RESISTOR").unwrap(), catagory);
 
-        db.add_entry(entry_0).unwrap_err();
+        // Importing again collides with the key
+        let error = fresh_db.import_entry(&json).unwrap_err();
 
-        // Add a different type of entry to a different catagory
-        db.add_entry(test_entry_2()).unwrap();
+        assert_eq!(error.to_string(), "Key 0 is already in use!");
 
-        // Add another entry to catagory b
-        db.add_entry(test_entry_3()).unwrap();
+        // The catagory isn't left behind when the entry can't be added
+        let mut other_db = Db::_new_test();
 
-        // Change the key to one that exists in another catagory, expect an error
-        let mut entry_3 = test_entry_3();
+        other_db.add_catagory(test_catagory_b()).unwrap();
+        other_db.add_entry(test_entry_2()).unwrap();
 
-        entry_3.key = 0;
+        let json = other_db.export_entry(test_entry_2().key).unwrap();
 
-        db.add_entry(entry_3).unwrap_err();
+        fresh_db
+            .add_entry(Entry::new("RESISTOR", test_entry_2().key, "bin", 1, 0, 0))
+            .unwrap();
+        fresh_db.import_entry(&json).unwrap_err();
+
+        assert_eq!(fresh_db.list_catagories().unwrap(), vec!["RESISTOR"]);
     }
 
     #[test]
-    fn test_db_grab_by_key() {
+    fn test_db_export_import_entry_blob() {
         let mut db = Db::_new_test();
 
-        // Should fail
-        db.grab_entry(0).unwrap_err();
-        // Add the catagories and entries we need
-        db.add_catagory(test_catagory_a()).unwrap();
-        db.add_catagory(test_catagory_b()).unwrap();
+        let mut catagory = test_catagory_a();
+        catagory.add_field(CatagoryField::new("PHOTO", DataType::BLOB));
+        db.add_catagory(catagory).unwrap();
 
-        // Should still fail
-        db.grab_entry(0).unwrap_err();
+        let payload: Vec<u8> = (0..40).collect();
 
-        db.add_entry(test_entry_0()).unwrap();
-        db.add_entry(test_entry_1()).unwrap();
+        let mut entry = test_entry_0();
+        entry.add_field(EntryField::new("PHOTO", &Db::blob_to_hex(&payload)));
+        db.add_entry(entry).unwrap();
 
-        // Grab the first entry by it's key
+        let mut fresh_db = Db::_new_test();
 
-        let entry_0 = db.grab_entry(0).unwrap();
+        fresh_db.import_entry(&db.export_entry(0).unwrap()).unwrap();
 
-        assert_eq!(entry_0, test_entry_0());
+        assert_eq!(fresh_db.grab_entry_blob(0, "PHOTO").unwrap(), payload);
     }
 
     #[test]
-    fn test_db_delete_by_key() {
+    fn test_db_vacuum() {
         let mut db = Db::_new_test();
 
-        // Should fail
-        db.delete_entry(0).unwrap_err();
-        // Add catagories and entries needed
         db.add_catagory(test_catagory_a()).unwrap();
-        db.add_catagory(test_catagory_b()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
 
-        // Should still fail
-        db.delete_entry(0).unwrap_err();
+        db.vacuum().unwrap();
+
+        assert_eq!(db.grab_entry(0).unwrap(), test_entry_0());
+    }
+
+    #[test]
+    fn test_db_init_unwritable() {
+        // A file can't have a directory inside it, so the data directory
+        // can't be created no matter who's running the tests
+        let file = std::env::temp_dir().join(format!("pinv_init_{}", std::process::id()));
+        fs::write(&file, b"").unwrap();
+
+        let path = file.join("data");
+        let result = Db::init(Some(Arc::from(path.to_str().unwrap())));
+
+        fs::remove_file(&file).unwrap();
+
+        let error = result.err().unwrap();
+
+        assert!(error
+            .to_string()
+            .starts_with("Couldn't create the data directory"));
+    }
+
+    #[test]
+    fn test_db_init_at() {
+        let dir = std::env::temp_dir().join(format!("pinv_init_at_{}", std::process::id()));
+
+        let mut home_db = Db::init_at(&dir.join("home.db3")).unwrap();
+        let mut lab_db = Db::init_at(&dir.join("lab.db3")).unwrap();
+
+        home_db.add_catagory(test_catagory_a()).unwrap();
+        home_db.add_entry(test_entry_0()).unwrap();
+        lab_db.add_catagory(test_catagory_b()).unwrap();
+        lab_db.add_entry(test_entry_2()).unwrap();
+
+        // Opening the file again finds what was added to it and nothing else
+        let home_db = Db::init_at(&dir.join("home.db3")).unwrap();
+        let lab_db = Db::init_at(&dir.join("lab.db3")).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(home_db.list_catagories().unwrap(), vec!["RESISTOR"]);
+        assert_eq!(lab_db.list_catagories().unwrap(), vec!["CAPACITOR"]);
+        assert_eq!(home_db.grab_entry(0).unwrap(), test_entry_0());
+        lab_db.grab_entry(0).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_concurrent_access() {
+        let dir = std::env::temp_dir().join(format!("pinv_concurrent_{}", std::process::id()));
+        let path = dir.join("pinv.db3");
+
+        let mut db = Db::init_at(&path).unwrap();
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        // Both stay open, like the TUI and a CLI add from a cron job
+        let mut other_db = Db::init_at(&path).unwrap();
+
+        let journal_mode: String = db
+            .connection
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(journal_mode, "wal");
 
         db.add_entry(test_entry_0()).unwrap();
-        db.add_entry(test_entry_1()).unwrap();
+        other_db.add_entry(test_entry_1()).unwrap();
 
-        // Delete the first entry
-        db.delete_entry(0).unwrap();
+        assert_eq!(db.grab_entry(1).unwrap(), test_entry_1());
+        assert_eq!(other_db.grab_entry(0).unwrap(), test_entry_0());
 
-        // Try to grab it(should fail!)
-        db.grab_entry(0).unwrap_err();
+        // Reopened connections wait on each other too
+        let reopened = db.reopen().unwrap();
+
+        let busy_timeout: u64 = reopened
+            .connection
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(busy_timeout, BUSY_TIMEOUT.as_millis() as u64);
+
+        drop(reopened);
+        drop(db);
+        drop(other_db);
+        fs::remove_dir_all(&dir).unwrap();
     }
 
-    #[test]
-    fn test_db_format_entry() {
-        // Entries should be formatted a certian way, alike the comments above
-        //
-        //  ENTRY {key}, CATAGORY {catagory}:
-        //      LOCATION     = {location},
-        //      QUANTITY     = {quantity},
-        //      {field_1_id} = {field_1_val},
-        //      {field_2_id} = {field_2_val},
-        //      ...
-        //      {field_x_id} = {field_x_val}
+    #[test]
+    fn test_db_backup_restore() {
+        let path = std::env::temp_dir().join(format!("pinv_backup_{}.db3", std::process::id()));
+
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        db.backup_to(&path).unwrap();
+
+        let mut restored = Db::_new_test();
+        restored.restore_from(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.grab_entry(0).unwrap(), db.grab_entry(0).unwrap());
+        assert_eq!(restored.grab_entry(1).unwrap(), db.grab_entry(1).unwrap());
+        assert_eq!(restored.list_catagories().unwrap(), vec!["RESISTOR"]);
 
-        let test_string: String = format!(
-            r#"ENTRY 0, CATAGORY RESISTOR:
-    LOCATION   = bazville,
-    QUANTITY   = 10,
-    CREATED    = {time},
-    MODIFIED   = {time},
-    MPN        = ERJ-PM8F8204V,
-    MFCD_BY    = Panasonic,
-    OHMS       = 8.2e6,
-    WATTS      = 6.6e-1,
-    TOLERANCE  = 1e-2,
-    PPM_C      = 1e2,
-    TERM_STYLE = SMD,
-    MAKEUP     = Thick Film,
-    CASE_CODE  = 1206,
-    DATASHEET  = https://www.mouser.com/datasheet/2/315/Panasonic_Resistor_ERJ_P_PA_PM_Series_022422-2933625.pdf"#,
-            time = Local.timestamp_opt(0, 0).unwrap().to_string()
-        );
+        // Anything that isn't a pinv database is turned away
+        let other = Connection::open(&path).unwrap();
+        other.execute("CREATE TABLE FOO (BAR TEXT)", []).unwrap();
+        drop(other);
 
-        assert_eq!(test_string, format!("{}", test_entry_0()));
+        restored.restore_from(&path).unwrap_err();
+        assert_eq!(restored.grab_entry(0).unwrap(), db.grab_entry(0).unwrap());
+
+        fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn test_db_search_catagory() {
+    fn test_db_rekey_entry() {
         let mut db = Db::_new_test();
 
         db.add_catagory(test_catagory_a()).unwrap();
@@ -1721,14 +6080,21 @@ pub mod tests {
         db.add_entry(test_entry_0()).unwrap();
         db.add_entry(test_entry_1()).unwrap();
 
-        assert_eq!(
-            db.search_catagory(
-                "RESISTOR",
-                &vec![Condition::new("OHMS", ConditionOperator::Equal, "8.2e6")]
-            )
-            .unwrap()[0],
-            test_entry_0()
-        );
+        db.rekey_entry(0, 64).unwrap();
+
+        assert_eq!(db.grab_entry(64).unwrap().location, "bazville");
+        assert!(db.grab_entry(0).is_err());
+
+        let record = &db.history(Some(1)).unwrap()[0];
+
+        assert_eq!(record.operation, Operation::Modify);
+        assert_eq!(record.key, 0);
+        assert_eq!(record.diff, "KEY: 0 -> 10");
+
+        // Can't take a key that's already in use
+        assert!(db.rekey_entry(64, 1).is_err());
+        assert_eq!(db.grab_entry(64).unwrap().key, 64);
+        assert_eq!(db.grab_entry(1).unwrap(), test_entry_1());
     }
 
     #[test]
@@ -1784,6 +6150,62 @@ pub mod tests {
         db.grab_entry(1).unwrap();
     }
 
+    #[test]
+    fn test_db_modify_key_collision() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let error = db
+            .mod_entry(
+                0,
+                vec![
+                    EntryField::from_str("quantity=9").unwrap(),
+                    EntryField::from_str("key=1").unwrap(),
+                ],
+            )
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Key 1 is already in use!");
+
+        // Neither entry was touched
+        assert_eq!(db.grab_entry(0).unwrap(), test_entry_0());
+        assert_eq!(db.grab_entry(1).unwrap(), test_entry_1());
+        assert_eq!(db.grab_catagory_from_key(0).unwrap(), "RESISTOR");
+
+        // Setting the key to itself is fine
+        db.mod_entry(0, vec![EntryField::from_str("key=0").unwrap()])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_db_modify_unknown_field() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        let error = db
+            .mod_entry(
+                0,
+                vec![
+                    EntryField::from_str("quantity=9").unwrap(),
+                    EntryField::from_str("farads=1").unwrap(),
+                ],
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "No such field FARADS in catagory RESISTOR!"
+        );
+
+        // Nothing was changed
+        assert_eq!(db.grab_entry(0).unwrap(), test_entry_0());
+    }
+
     #[test]
     fn test_db_string_format_id_test() {
         let good_id_1 = "FOO";
@@ -1834,6 +6256,110 @@ pub mod tests {
         Db::check_value_string(bad_number_2, DataType::INTEGER).unwrap_err();
         Db::check_value_string(bad_number_2, DataType::REAL).unwrap_err();
         Db::check_value_string(bad_number_3, DataType::INTEGER).unwrap_err();
+
+        // NULL is written as a blank value
+        Db::check_value_string("", DataType::NULL).unwrap();
+        Db::check_value_string("NULL", DataType::NULL).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_value_from_string_integer_range() {
+        assert_eq!(
+            Db::value_from_string("1e3", DataType::INTEGER).unwrap(),
+            Value::Integer(1000)
+        );
+        assert_eq!(
+            Db::value_from_string("-9e18", DataType::INTEGER).unwrap(),
+            Value::Integer(-9_000_000_000_000_000_000)
+        );
+
+        // Too big for an i64, instead of being clamped to i64::MAX
+        assert_eq!(
+            Db::value_from_string("1e30", DataType::INTEGER)
+                .unwrap_err()
+                .to_string(),
+            "1e30 is too big for an integer!"
+        );
+        Db::value_from_string("-1e19", DataType::INTEGER).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_null_values() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        let is_null = |db: &Db, field_id: &str| -> bool {
+            db.connection
+                .query_row(
+                    &format!("SELECT {} IS NULL FROM CAPACITOR WHERE KEY=2", field_id),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+
+        // A blank value is stored as a genuine NULL, as is a field left out
+        let mut entry = test_entry_2();
+        entry.add_field(EntryField::new("VOLTAGE_AC", ""));
+        db.add_entry(entry).unwrap();
+
+        assert!(is_null(&db, "VOLTAGE_AC"));
+        assert!(is_null(&db, "HOURS"));
+
+        // Modifying a field to blank turns it back into NULL
+        db.mod_entry(2, vec![EntryField::new("VOLTAGE_AC", "2.5e1")])
+            .unwrap();
+
+        assert!(!is_null(&db, "VOLTAGE_AC"));
+
+        db.mod_entry(2, vec![EntryField::new("VOLTAGE_AC", "")])
+            .unwrap();
+
+        assert!(is_null(&db, "VOLTAGE_AC"));
+
+        // Fields left out of a modification keep their value
+        db.mod_entry(2, vec![EntryField::new("QUANTITY", "5")])
+            .unwrap();
+
+        assert!(!is_null(&db, "FARADS"));
+
+        // NULL reads back as a blank value, never the text "NULL"
+        let entry = db.grab_entry(2).unwrap();
+        let voltage_ac = entry
+            .fields
+            .iter()
+            .find(|field| field.id == "VOLTAGE_AC")
+            .unwrap();
+
+        assert_eq!(voltage_ac.value, "");
+    }
+
+    #[test]
+    fn test_db_list_catagories_sorted() {
+        let mut db = Db::_new_test();
+
+        for id in ["BOLT", "RESISTOR", "CAPACITOR", "NUT"] {
+            db.add_catagory(Catagory::with_fields(
+                id,
+                vec![CatagoryField::new("SIZE", DataType::TEXT)],
+            ))
+            .unwrap();
+        }
+
+        // Made outside of pinv, so it isn't uppercase
+        db.connection
+            .execute("CREATE TABLE Diode (KEY INTEGER NOT NULL PRIMARY KEY)", [])
+            .unwrap();
+
+        assert_eq!(
+            db.list_catagories().unwrap(),
+            vec!["BOLT", "CAPACITOR", "Diode", "NUT", "RESISTOR"]
+        );
+        assert_eq!(
+            db.list_catagories_sorted(SortOrder::Descending).unwrap(),
+            vec!["RESISTOR", "NUT", "Diode", "CAPACITOR", "BOLT"]
+        );
     }
 
     #[test]
@@ -1861,4 +6387,250 @@ pub mod tests {
         let catagories = db.list_catagories().unwrap();
         assert_eq!(catagories.len(), 1);
     }
+
+    #[test]
+    fn test_db_dedupe_keys() {
+        let mut entry_0 = test_entry_1();
+        entry_0.key = 0;
+
+        let entries = vec![test_entry_0(), test_entry_1(), entry_0.clone()];
+
+        // Should fail, key 0 is in there twice
+        let error = Db::dedupe_keys(entries.clone(), false).unwrap_err();
+        assert_eq!(error.to_string(), "Duplicate keys in import: 0!");
+
+        // Should pass, with the last entry taking key 0's place
+        let deduped = Db::dedupe_keys(entries, true).unwrap();
+        assert_eq!(deduped, vec![test_entry_1(), entry_0]);
+
+        // Should pass untouched when there are no duplicates
+        let entries = vec![test_entry_0(), test_entry_1()];
+        assert_eq!(Db::dedupe_keys(entries.clone(), false).unwrap(), entries);
+    }
+
+    #[test]
+    fn test_db_get_catagory_fields_opts() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+
+        let all_fields = db.grab_catagory_fields_opts("RESISTOR", true).unwrap();
+        let user_fields = db.grab_catagory_fields_opts("RESISTOR", false).unwrap();
+
+        assert_eq!(all_fields, db.grab_catagory_fields("RESISTOR").unwrap());
+        assert_eq!(all_fields[5..], user_fields[..]);
+        assert_eq!(
+            user_fields,
+            test_catagory_a()
+                .fields
+                .iter()
+                .map(|field| field.id.clone())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_db_label_substitutions() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let template = "<a>FOO!</a><b>FOO!</b>";
+
+        let labels = db.label_substitutions(template).unwrap();
+
+        // Keys 0 and 1 are taken so the labels should get 2 and 3
+        assert_eq!(
+            labels,
+            vec![
+                vec![EntryField::new("KEY", "000002")],
+                vec![EntryField::new("KEY", "000003")]
+            ]
+        );
+
+        assert_eq!(
+            db.fill_svg_template(template).unwrap(),
+            "<a>000002</a><b>000003</b>"
+        );
+    }
+
+    #[test]
+    fn test_db_fill_svg_template_with_keys() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let template = "<a>FOO!</a><b>FOO!</b><c>FOO!</c>";
+
+        // Keys are filled in the order given, the last label is left blank
+        assert_eq!(
+            db.fill_svg_template_with_keys(template, &[1, 0]).unwrap(),
+            "<a>000001</a><b>000000</b><c></c>"
+        );
+
+        let labels = db.label_substitutions_for_keys(template, &[0]).unwrap();
+
+        assert_eq!(labels[0][0], EntryField::new("KEY", "000000"));
+        assert_eq!(labels[0][1].id, "LOCATION");
+
+        // Should fail, there's no room for a fourth label
+        db.fill_svg_template_with_keys(template, &[0, 1, 0, 1])
+            .unwrap_err();
+        // Should fail, key 2 isn't in use
+        db.fill_svg_template_with_keys(template, &[2]).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_fill_svg_entry() {
+        let entry = Entry::new("BOLT", 11, "Drawer \"A&B\"", 4, 0, 0);
+
+        assert_eq!(
+            Db::fill_svg_entry("<t>{{KEY}}</t><t>{{LOCATION}}</t><t>{{MPN}}</t>", &entry),
+            "<t>00000B</t><t>Drawer &quot;A&amp;B&quot;</t><t></t>"
+        );
+    }
+
+    #[test]
+    fn test_db_set_timestamps() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_entry(test_entry_0()).unwrap();
+
+        db.set_created(0, 1234567890).unwrap();
+        db.set_modified(0, 1234567899).unwrap();
+
+        let entry = db.grab_entry(0).unwrap();
+
+        assert_eq!(entry.created, 1234567890);
+        assert_eq!(entry.modified, 1234567899);
+
+        // Should fail, no such key
+        db.set_created(1, 1234567890).unwrap_err();
+    }
+
+    #[test]
+    fn test_db_check_catagory_id() {
+        // Should pass
+        Db::check_catagory_id("RESISTOR").unwrap();
+
+        // Should fail
+        assert_eq!(
+            Db::check_catagory_id("").unwrap_err().to_string(),
+            "Catagory name can't be empty!"
+        );
+        assert_eq!(
+            Db::check_catagory_id("resistor").unwrap_err().to_string(),
+            "Catagory name resistor must be uppercase!"
+        );
+        Db::check_catagory_id("RESISTOR+").unwrap_err();
+    }
+
+    #[test]
+    fn test_db_check_new_catagory() {
+        // Should pass
+        Db::check_new_catagory(&test_catagory_a()).unwrap();
+
+        // Should fail
+        let mut catagory = test_catagory_a();
+        catagory.id = "".to_owned();
+
+        assert_eq!(
+            Db::check_new_catagory(&catagory).unwrap_err().to_string(),
+            "Catagory name can't be empty!"
+        );
+
+        assert_eq!(
+            Db::check_new_catagory(&Catagory::new("RESISTOR"))
+                .unwrap_err()
+                .to_string(),
+            "No fields in catagory RESISTOR!"
+        );
+
+        let mut catagory = test_catagory_a();
+        catagory.fields[1].id = "".to_owned();
+
+        assert_eq!(
+            Db::check_new_catagory(&catagory).unwrap_err().to_string(),
+            "Field name can't be empty!"
+        );
+
+        catagory.fields[1].id = "MPN".to_owned();
+
+        assert_eq!(
+            Db::check_new_catagory(&catagory).unwrap_err().to_string(),
+            "Field MPN is in the catagory twice!"
+        );
+
+        catagory.fields[1].id = "LOCATION".to_owned();
+
+        assert_eq!(
+            Db::check_new_catagory(&catagory).unwrap_err().to_string(),
+            "Every catagory already has a LOCATION field!"
+        );
+    }
+
+    #[test]
+    fn test_db_catagory_stats() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(test_catagory_a()).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        db.add_entry(test_entry_0()).unwrap();
+        db.add_entry(test_entry_1()).unwrap();
+
+        let stats = serde_json::to_value(db.catagory_stats().unwrap()).unwrap();
+
+        assert_eq!(
+            stats,
+            serde_json::json!([
+                {"catagory": "CAPACITOR", "entries": 0, "total_quantity": 0},
+                {"catagory": "RESISTOR", "entries": 2, "total_quantity": 12},
+            ])
+        );
+
+        assert_eq!(
+            db.stat_catagories().unwrap(),
+            vec![vec!["CAPACITOR", "0"], vec!["RESISTOR", "2"]]
+        );
+    }
+
+    #[test]
+    fn test_db_total_value() {
+        let mut db = Db::_new_test();
+
+        let mut catagory_a = test_catagory_a();
+        catagory_a.add_field(CatagoryField::new("PRICE", DataType::REAL));
+
+        db.add_catagory(catagory_a).unwrap();
+        db.add_catagory(test_catagory_b()).unwrap();
+
+        let mut entry_0 = test_entry_0();
+        entry_0.add_field(EntryField::new("PRICE", "2.5e-1"));
+        let mut entry_1 = test_entry_1();
+        entry_1.add_field(EntryField::new("PRICE", "1.5"));
+
+        db.add_entry(entry_0).unwrap();
+        db.add_entry(entry_1).unwrap();
+        db.add_entry(test_entry_2()).unwrap();
+
+        // 10 * 0.25 + 2 * 1.5
+        assert_eq!(db.total_value("RESISTOR").unwrap(), Some(5.5));
+
+        // Capacitors don't have a price
+        assert_eq!(db.total_value("CAPACITOR").unwrap(), None);
+
+        assert_eq!(
+            db.total_value_all().unwrap(),
+            vec![
+                ("CAPACITOR".to_string(), None),
+                ("RESISTOR".to_string(), Some(5.5))
+            ]
+        );
+    }
 }
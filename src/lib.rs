@@ -18,6 +18,12 @@ pub static ORGANISATION: &str = "Open Ape Shop";
 pub static APPLICATION: &str = "pinv";
 
 pub mod b64;
+pub mod cli;
+pub mod config;
+pub mod csv;
 pub mod db;
+pub mod state;
+pub mod table;
+pub mod task;
 pub mod templates;
 pub mod tui;
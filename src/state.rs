@@ -0,0 +1,113 @@
+//! State the TUI keeps between sessions, saved in the config directory.
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+use crate::config;
+use serde::Deserialize;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Name of the state file within the config directory
+static STATE_FILENAME: &str = "state.toml";
+
+/// What the TUI was looking at when it was closed, so the next session can
+/// pick up from there.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Catagory last opened, empty if none was
+    pub catagory: String,
+    /// Key of the entry last selected in that catagory
+    pub key: u64,
+}
+
+impl State {
+    /// Load the state file from the config directory, or the default state if
+    /// there is no state file.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        Self::load_from(&Self::path()?)
+    }
+
+    /// Load the state from a file, or the default state if the file doesn't
+    /// exist.
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)?;
+
+        Ok(toml::from_str(&data)?)
+    }
+
+    /// Save the state file to the config directory, creating the directory if
+    /// it doesn't exist.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        self.save_to(&Self::path()?)
+    }
+
+    /// Save the state to a file
+    pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, toml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Get the path to the state file
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = config::config_dir()?;
+        path.push(STATE_FILENAME);
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_save_load() {
+        let dir = std::env::temp_dir().join(format!("pinv_state_{}", std::process::id()));
+        let path = dir.join(STATE_FILENAME);
+
+        // Nothing saved yet, so the default state
+        assert_eq!(State::load_from(&path).unwrap(), State::default());
+
+        let state = State {
+            catagory: "RESISTOR".to_owned(),
+            key: 42,
+        };
+
+        state.save_to(&path).unwrap();
+
+        assert_eq!(State::load_from(&path).unwrap(), state);
+
+        // Should fail
+        fs::write(&path, "key = \"forty two\"").unwrap();
+        State::load_from(&path).unwrap_err();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! Helpers for laying out tables of text, shared by the CLI and the TUI.
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+use std::cmp;
+
+/// Padding put after every column
+pub static COLUMN_PADDING: &str = " | ";
+
+/// Width of the column padding
+pub const COLUMN_PADDING_LEN: usize = 3;
+
+/// Marks a value that was cut short to fit its column
+static TRUNCATION_MARK: char = '~';
+
+/// Grab the width of the terminal, if there is one
+pub fn terminal_width() -> Option<usize> {
+    crossterm::terminal::size()
+        .ok()
+        .map(|(columns, _)| columns as usize)
+}
+
+/// Converts a table into strings that mimic an excel table, or something
+/// alike that. If max_width is given, the widest columns are narrowed and
+/// their values truncated until every row fits in it.
+pub fn columnator(
+    headers: Vec<String>,
+    table: Vec<Vec<String>>,
+    max_width: Option<usize>,
+) -> Vec<String> {
+    // First calculate the widths of each column
+    let mut column_widths = Vec::<usize>::with_capacity(headers.len());
+
+    for (i, header) in headers.iter().enumerate() {
+        let mut width = header.chars().count();
+
+        for row in &table {
+            width = cmp::max(width, row[i].chars().count());
+        }
+
+        column_widths.push(width);
+    }
+
+    if let Some(max_width) = max_width {
+        fit_widths(&mut column_widths, max_width);
+    }
+
+    let out_string_size: usize = column_widths
+        .iter()
+        .map(|width| width + COLUMN_PADDING_LEN)
+        .sum();
+
+    // Next generate strings of each row with padding to make each column the same width
+    // starting with the headers
+    let mut out_strings = Vec::<String>::with_capacity(table.len() + 1);
+
+    for row in std::iter::once(&headers).chain(table.iter()) {
+        let mut out_string = String::with_capacity(out_string_size);
+
+        for (i, column) in row.iter().enumerate() {
+            out_string.push_str(&format!(
+                "{:<width$}{}",
+                truncate(column, column_widths[i]),
+                COLUMN_PADDING,
+                width = column_widths[i]
+            ));
+        }
+
+        out_strings.push(out_string);
+    }
+
+    out_strings
+}
+
+/// Narrow the widest column one character at a time until the row, padding
+/// included, fits in max_width. Columns are never narrowed below one character.
+fn fit_widths(column_widths: &mut [usize], max_width: usize) {
+    let mut total: usize = column_widths
+        .iter()
+        .map(|width| width + COLUMN_PADDING_LEN)
+        .sum();
+
+    while total > max_width {
+        let widest = match column_widths.iter_mut().max() {
+            Some(widest) if *widest > 1 => widest,
+            _ => break,
+        };
+
+        *widest -= 1;
+        total -= 1;
+    }
+}
+
+/// Cut a value down to width characters, marking it if anything was removed
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_owned();
+    }
+
+    let mut truncated: String = value.chars().take(width.saturating_sub(1)).collect();
+    truncated.push(TRUNCATION_MARK);
+
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wide_table() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = vec!["KEY".to_string(), "DESCRIPTION".to_string()];
+        let table = vec![
+            vec!["0".to_string(), "A".repeat(100)],
+            vec!["1".to_string(), "short".to_string()],
+        ];
+
+        (headers, table)
+    }
+
+    #[test]
+    fn test_columnator() {
+        let (headers, table) = wide_table();
+
+        let rows = columnator(headers, table, None);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], format!("KEY | {:<100} | ", "DESCRIPTION"));
+        assert_eq!(rows[2], format!("1   | {:<100} | ", "short"));
+    }
+
+    #[test]
+    fn test_columnator_max_width() {
+        let (headers, table) = wide_table();
+
+        let rows = columnator(headers, table, Some(40));
+
+        for row in &rows {
+            assert!(row.chars().count() <= 40);
+        }
+
+        assert_eq!(rows[1], format!("0   | {}~ | ", "A".repeat(30)));
+        assert_eq!(rows[2], format!("1   | {:<31} | ", "short"));
+    }
+
+    #[test]
+    fn test_columnator_max_width_too_narrow() {
+        let (headers, table) = wide_table();
+
+        // Columns stop shrinking at one character
+        let rows = columnator(headers, table, Some(1));
+
+        assert_eq!(rows[0], "~ | ~ | ");
+    }
+}
@@ -0,0 +1,130 @@
+//! Tracks the progress of long running operations, like vacuuming the
+//! database, so the TUI can show they're still working.
+
+// Copyright (c) 2023 Charles M. Thompson
+//
+// This file is part of pinv.
+//
+// pinv is free software: you can redistribute it and/or modify it under
+// the terms only of version 3 of the GNU General Public License as published
+// by the Free Software Foundation
+//
+// pinv is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS
+// FOR A PARTICULAR PURPOSE. See the GNU General Public License
+// for more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// pinv(in a file named COPYING).
+// If not, see <https://www.gnu.org/licenses/>.
+
+/// Frames of the spinner shown while a task is running
+static SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// State of a task
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskState {
+    /// Still working, with the number of times the task has been ticked
+    Running(usize),
+    /// Finished without any problems
+    Done,
+    /// Finished with an error
+    Failed(String),
+}
+
+/// A long running operation and its state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Task {
+    /// What the task is doing, e.g. "Vacuuming database"
+    pub description: String,
+    /// Current state of the task
+    pub state: TaskState,
+}
+
+impl Task {
+    /// Create a new running task
+    pub fn new(description: &str) -> Self {
+        Self {
+            description: description.to_owned(),
+            state: TaskState::Running(0),
+        }
+    }
+
+    /// Advance the spinner. Does nothing once the task has finished.
+    pub fn tick(&mut self) {
+        if let TaskState::Running(ticks) = self.state {
+            self.state = TaskState::Running(ticks.wrapping_add(1));
+        }
+    }
+
+    /// Mark the task as finished with the result of the operation. A task
+    /// can only finish once, later results are ignored.
+    pub fn finish(&mut self, result: Result<(), String>) {
+        if !self.is_running() {
+            return;
+        }
+
+        self.state = match result {
+            Ok(_) => TaskState::Done,
+            Err(error) => TaskState::Failed(error),
+        };
+    }
+
+    /// Check if the task is still running
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, TaskState::Running(_))
+    }
+
+    /// Line of text describing the state of the task
+    pub fn status(&self) -> String {
+        match &self.state {
+            TaskState::Running(ticks) => {
+                format!("{} {}...", SPINNER[ticks % SPINNER.len()], self.description)
+            }
+            TaskState::Done => format!("{}... done!", self.description),
+            TaskState::Failed(error) => format!("{}... failed: {}", self.description, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_tick() {
+        let mut task = Task::new("Vacuuming database");
+
+        assert_eq!(task.status(), "| Vacuuming database...");
+
+        task.tick();
+        assert_eq!(task.status(), "/ Vacuuming database...");
+
+        for _ in 0..3 {
+            task.tick();
+        }
+        assert_eq!(task.status(), "| Vacuuming database...");
+    }
+
+    #[test]
+    fn test_task_finish() {
+        let mut task = Task::new("Vacuuming database");
+
+        task.finish(Ok(()));
+        assert!(!task.is_running());
+        assert_eq!(task.status(), "Vacuuming database... done!");
+
+        // Finished tasks don't tick or finish again
+        task.tick();
+        task.finish(Err("database is locked".to_owned()));
+        assert_eq!(task.state, TaskState::Done);
+
+        let mut task = Task::new("Vacuuming database");
+
+        task.finish(Err("database is locked".to_owned()));
+        assert_eq!(
+            task.status(),
+            "Vacuuming database... failed: database is locked"
+        );
+    }
+}
@@ -16,8 +16,26 @@
 // You should have received a copy of the GNU General Public License along with
 // pinv(in a file named COPYING).
 // If not, see <https://www.gnu.org/licenses/>.
-use libflate::gzip::Decoder;
-use std::io::Read;
+use crate::db::EntryField;
+use directories::ProjectDirs;
+use libflate::gzip::{Decoder, Encoder};
+use qrcodegen::{QrCode, QrCodeEcc};
+use simple_error::bail;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use svg2pdf::usvg;
+use svg2pdf::usvg::fontdb::{Family, Query};
+use svg2pdf::{ConversionOptions, PageOptions};
+
+/// The key marker used when a template doesn't declare its own
+pub const DEFAULT_KEY_MARKER: &str = "FOO!";
+
+/// Templates declare their own key marker with a comment like
+/// <!-- pinv-key-marker: @KEY@ -->
+const KEY_MARKER_DECLARATION: &str = "<!-- pinv-key-marker:";
 
 pub struct Template {
     pub id: &'static str,
@@ -35,6 +53,380 @@ impl Template {
     }
 }
 
+/// Get the directory user templates are kept in
+pub fn template_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let qualifier = "org";
+    let organisation = crate::ORGANISATION;
+    let application = crate::APPLICATION;
+
+    let dirs = match ProjectDirs::from(qualifier, organisation, application) {
+        Some(dirs) => dirs,
+        None => bail!("Couldn't find a home directory to keep templates in!"),
+    };
+
+    let mut template_dir = dirs.data_dir().to_owned();
+    template_dir.push("templates");
+
+    Ok(template_dir)
+}
+
+/// List the template files in a template directory, sorted by name. A
+/// directory that doesn't exist yet has no templates.
+pub fn user_templates(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+
+    if !dir.exists() {
+        return Ok(paths);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if !path.is_dir() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Gzip a template the way template files are stored
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut encoder = Encoder::new(Vec::new())?;
+
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish().into_result()?)
+}
+
+/// Unzip a template file
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut decoder = Decoder::new(data)?;
+    let mut out: Vec<u8> = Vec::new();
+
+    decoder.read_to_end(&mut out)?;
+
+    Ok(out)
+}
+
+/// Compress an SVG template into a template directory as ID.svg.gz, returning
+/// where it was written. The id can't be taken by a built-in template or an
+/// existing file.
+pub fn add_template(dir: &Path, id: &str, data: &[u8]) -> Result<PathBuf, Box<dyn Error>> {
+    if id.is_empty() || id.contains(['/', '\\']) {
+        bail!("Template id {} isn't a valid file name!", id);
+    }
+
+    if TEMPLATES
+        .iter()
+        .any(|template| template.id.eq_ignore_ascii_case(id))
+    {
+        bail!("Template id {} is taken by a built-in template!", id);
+    }
+
+    let data = match std::str::from_utf8(data) {
+        Ok(data) => data,
+        Err(_) => bail!("The template isn't valid UTF-8!"),
+    };
+
+    check_template(data)?;
+
+    let path = dir.join(format!("{}.svg.gz", id));
+
+    if path.exists() {
+        bail!("Template {} already exists!", path.display());
+    }
+
+    fs::create_dir_all(dir)?;
+    fs::write(&path, compress(data.as_bytes())?)?;
+
+    Ok(path)
+}
+
+/// Split the key marker declaration out of a template, returning the key
+/// marker and the template without the declaration
+fn key_marker(data: &str) -> (String, String) {
+    if let Some(start) = data.find(KEY_MARKER_DECLARATION) {
+        let after = start + KEY_MARKER_DECLARATION.len();
+
+        if let Some(len) = data[after..].find("-->") {
+            let marker = data[after..after + len].trim().to_owned();
+            let body = format!("{}{}", &data[..start], &data[after + len + 3..]);
+
+            return (marker, body);
+        }
+    }
+
+    (DEFAULT_KEY_MARKER.to_owned(), data.to_owned())
+}
+
+/// Make sure a template has a usable key marker and at least one label
+pub fn check_template(data: &str) -> Result<(), Box<dyn Error>> {
+    let (marker, _) = key_marker(data);
+
+    if marker.is_empty() {
+        bail!("The template's key marker can't be empty!");
+    }
+
+    if label_count(data) == 0 {
+        bail!(
+            "The template doesn't contain its key marker {}, {{{{KEY}}}}, {{{{QR}}}} or {{{{BARCODE}}}}!",
+            marker
+        );
+    }
+
+    Ok(())
+}
+
+/// Find the next placeholder in a template, returning where it starts, where
+/// it ends and the field it's filled with. Placeholders are written like
+/// {{LOCATION}}, the key marker is the same as {{KEY}}.
+fn next_placeholder(data: &str, key_marker: &str) -> Option<(usize, usize, String)> {
+    let mut from = 0;
+
+    loop {
+        let marker = match key_marker.is_empty() {
+            true => None,
+            false => data[from..].find(key_marker).map(|i| i + from),
+        };
+        let brace = data[from..].find("{{").map(|i| i + from);
+
+        match (marker, brace) {
+            (Some(marker), Some(brace)) if marker > brace => {}
+            (Some(marker), _) => {
+                return Some((marker, marker + key_marker.len(), "KEY".to_owned()));
+            }
+            _ => {}
+        }
+
+        match brace {
+            Some(brace) => {
+                let inner = &data[brace + 2..];
+
+                if let Some(len) = inner.find("}}") {
+                    let field = &inner[..len];
+
+                    if !field.is_empty()
+                        && field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Some((brace, brace + len + 4, field.to_uppercase()));
+                    }
+                }
+
+                // Not a placeholder, like the braces of some CSS
+                from = brace + 2;
+            }
+            None => return None,
+        }
+    }
+}
+
+/// Count the labels in a template. Key, {{QR}} and {{BARCODE}} placeholders
+/// are each filled from the first label onwards, so a template has as many
+/// labels as it has of whichever one it uses most.
+pub fn label_count(data: &str) -> usize {
+    let (marker, data) = key_marker(data);
+    let mut counts = HashMap::<String, usize>::new();
+    let mut rest = data.as_str();
+
+    while let Some((_, end, field)) = next_placeholder(rest, &marker) {
+        if matches!(field.as_str(), "KEY" | "QR" | "BARCODE") {
+            *counts.entry(field).or_insert(0) += 1;
+        }
+
+        rest = &rest[end..];
+    }
+
+    counts.into_values().max().unwrap_or(0)
+}
+
+/// Fill the placeholders of a template with the fields of the labels. The
+/// first {{LOCATION}} is filled from the first label, the second from the
+/// second label, and so on. Placeholders without a label or a matching field
+/// are left blank. {{QR}} and {{BARCODE}} are filled with a QR code and a
+/// barcode of the label's key.
+pub fn fill_labels(data: &str, labels: &[Vec<EntryField>]) -> String {
+    let (marker, data) = key_marker(data);
+    let mut out = String::with_capacity(data.len());
+    let mut seen = HashMap::<String, usize>::new();
+    let mut rest = data.as_str();
+
+    while let Some((start, end, field_id)) = next_placeholder(rest, &marker) {
+        out.push_str(&rest[..start]);
+
+        let i = seen.entry(field_id.clone()).or_insert(0);
+
+        let id = match field_id.as_str() {
+            "QR" | "BARCODE" => "KEY",
+            id => id,
+        };
+
+        let value = labels
+            .get(*i)
+            .and_then(|label| label.iter().find(|field| field.id == id));
+
+        match value {
+            Some(field) if field_id == "QR" => out.push_str(&qr_svg(&field.value)),
+            Some(field) if field_id == "BARCODE" => out.push_str(&code128_svg(&field.value)),
+            Some(field) => out.push_str(&escape_xml(&field.value)),
+            None => {}
+        }
+
+        *i += 1;
+        rest = &rest[end..];
+    }
+
+    out.push_str(rest);
+
+    out
+}
+
+/// Draw a QR code as an SVG path, one unit per module with no border, so it
+/// can be placed and scaled with a transform
+pub fn qr_svg(text: &str) -> String {
+    let qr = match QrCode::encode_text(text, QrCodeEcc::Medium) {
+        Ok(qr) => qr,
+        Err(_) => return String::new(),
+    };
+
+    let mut path = String::new();
+
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.get_module(x, y) {
+                path.push_str(&format!("M{},{}h1v1h-1z", x, y));
+            }
+        }
+    }
+
+    format!("<path d=\"{}\" fill=\"#000000\"/>", path)
+}
+
+/// Bar and space widths of every Code128 symbol, the last being the stop
+static CODE128_PATTERNS: [&str; 107] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232", "2331112",
+];
+
+const CODE128_START_B: usize = 104;
+const CODE128_STOP: usize = 106;
+const CODE128_QUIET_ZONE: usize = 10;
+
+/// Draw a Code128 barcode as an SVG that stretches to fill whatever it's
+/// placed in, quiet zone included. Only printable ASCII can be encoded.
+pub fn code128_svg(text: &str) -> String {
+    let mut symbols = vec![CODE128_START_B];
+
+    for c in text.chars() {
+        match c {
+            ' '..='\x7f' => symbols.push(c as usize - ' ' as usize),
+            _ => return String::new(),
+        }
+    }
+
+    let checksum = symbols
+        .iter()
+        .enumerate()
+        .map(|(i, symbol)| i.max(1) * symbol)
+        .sum::<usize>()
+        % 103;
+
+    symbols.push(checksum);
+    symbols.push(CODE128_STOP);
+
+    let mut bars = String::new();
+    let mut x = CODE128_QUIET_ZONE;
+
+    for symbol in symbols {
+        for (i, width) in CODE128_PATTERNS[symbol].bytes().enumerate() {
+            let width = (width - b'0') as usize;
+
+            // Bars and spaces alternate, starting with a bar
+            if i % 2 == 0 {
+                bars.push_str(&format!(
+                    "<rect x=\"{}\" y=\"0\" width=\"{}\" height=\"1\"/>",
+                    x, width
+                ));
+            }
+
+            x += width;
+        }
+    }
+
+    format!(
+        "<svg viewBox=\"0 0 {} 1\" preserveAspectRatio=\"none\" fill=\"#000000\">{}</svg>",
+        x + CODE128_QUIET_ZONE,
+        bars
+    )
+}
+
+/// Convert a filled template to a PDF the same size as the sheet, using the
+/// system fonts for any text
+pub fn svg_to_pdf(svg: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut options = usvg::Options::default();
+    let fontdb = options.fontdb_mut();
+    fontdb.load_system_fonts();
+
+    // Text that can't find its font is drawn with the serif font, which is
+    // Times New Roman unless told otherwise. Use any font there is instead of
+    // leaving the keys off of the labels.
+    let serif = Query {
+        families: &[Family::Serif],
+        ..Query::default()
+    };
+
+    if fontdb.query(&serif).is_none() {
+        let fallback = fontdb
+            .faces()
+            .find_map(|face| face.families.first())
+            .map(|(family, _)| family.clone());
+
+        if let Some(fallback) = fallback {
+            fontdb.set_serif_family(fallback);
+        }
+    }
+
+    let tree = usvg::Tree::from_str(svg, &options)?;
+
+    // usvg works in CSS pixels, which are 96 to the inch
+    let page_options = PageOptions { dpi: 96.0 };
+
+    match svg2pdf::to_pdf(&tree, ConversionOptions::default(), page_options) {
+        Ok(pdf) => Ok(pdf),
+        Err(error) => bail!("Couldn't convert the template to a PDF: {}", error),
+    }
+}
+
+/// Escape the characters that mean something in XML
+pub fn escape_xml(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
 pub static TEMPLATES: [Template; 2] = [
     Template {
         id: "Avery 5160",
@@ -45,3 +437,152 @@ pub static TEMPLATES: [Template; 2] = [
         data_compressed: include_bytes!("../templates/avery_5195.svg.gz"),
     },
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_labels() {
+        let template = "<a>FOO!</a><b>{{location}}</b><a>{{KEY}}</a><b>{{LOCATION}}</b>";
+        let labels = vec![
+            vec![
+                EntryField::new("KEY", "1"),
+                EntryField::new("LOCATION", "Bin <3>"),
+            ],
+            vec![EntryField::new("KEY", "2")],
+        ];
+
+        assert_eq!(label_count(template), 2);
+        assert_eq!(
+            fill_labels(template, &labels),
+            "<a>1</a><b>Bin &lt;3&gt;</b><a>2</a><b></b>"
+        );
+
+        // Braces that aren't placeholders are left alone
+        let style = "<style>a {{ fill: red }}</style>";
+
+        assert_eq!(label_count(style), 0);
+        assert_eq!(fill_labels(style, &labels), style);
+    }
+
+    #[test]
+    fn test_fill_labels_key_marker() {
+        let template = "<!-- pinv-key-marker: @KEY@ --><a>@KEY@</a><b>FOO!</b><a>@KEY@</a>";
+        let labels = vec![
+            vec![EntryField::new("KEY", "1")],
+            vec![EntryField::new("KEY", "2")],
+        ];
+
+        check_template(template).unwrap();
+        assert_eq!(label_count(template), 2);
+        assert_eq!(
+            fill_labels(template, &labels),
+            "<a>1</a><b>FOO!</b><a>2</a>"
+        );
+
+        // The marker has to be in the template, and can't be empty
+        check_template("<!-- pinv-key-marker: @KEY@ --><a>FOO!</a>").unwrap_err();
+        check_template("<!-- pinv-key-marker: --><a>FOO!</a>").unwrap_err();
+        check_template("<a></a>").unwrap_err();
+        check_template("<a>{{KEY}}</a>").unwrap();
+    }
+
+    #[test]
+    fn test_fill_labels_qr_only() {
+        let template = "<g>{{QR}}</g><text>{{LOCATION}}</text><g>{{QR}}</g>";
+        let labels = vec![
+            vec![EntryField::new("KEY", "1")],
+            vec![EntryField::new("KEY", "2")],
+        ];
+
+        check_template(template).unwrap();
+        assert_eq!(label_count(template), 2);
+        assert_eq!(
+            fill_labels(template, &labels),
+            format!("<g>{}</g><text></text><g>{}</g>", qr_svg("1"), qr_svg("2"))
+        );
+
+        // A key next to each code is still one label each
+        assert_eq!(label_count("{{KEY}}{{BARCODE}}{{KEY}}{{BARCODE}}"), 2);
+    }
+
+    #[test]
+    fn test_add_template() {
+        let dir = std::env::temp_dir().join(format!("pinv_templates_{}", std::process::id()));
+        let template = "<svg><text>FOO!</text><text>{{LOCATION}}</text></svg>";
+
+        let path = add_template(&dir, "Shelf", template.as_bytes()).unwrap();
+
+        assert_eq!(path, dir.join("Shelf.svg.gz"));
+        assert_eq!(
+            decompress(&fs::read(&path).unwrap()).unwrap(),
+            template.as_bytes()
+        );
+
+        // Taken, built-in, or not a template
+        add_template(&dir, "Shelf", template.as_bytes()).unwrap_err();
+        add_template(&dir, "avery 5160", template.as_bytes()).unwrap_err();
+        add_template(&dir, "Blank", b"<svg></svg>").unwrap_err();
+        add_template(&dir, "../Shelf", template.as_bytes()).unwrap_err();
+
+        add_template(&dir, "Bin", template.as_bytes()).unwrap();
+        fs::create_dir(dir.join("old")).unwrap();
+
+        assert_eq!(
+            user_templates(&dir).unwrap(),
+            vec![dir.join("Bin.svg.gz"), dir.join("Shelf.svg.gz")]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_svg_to_pdf() {
+        let svg = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"2in\" height=\"1in\">\
+                   <rect x=\"10\" y=\"10\" width=\"50\" height=\"20\"/></svg>";
+
+        let pdf = svg_to_pdf(svg).unwrap();
+
+        assert!(pdf.starts_with(b"%PDF-"));
+
+        // 2 by 1 inches is 144 by 72 points
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/MediaBox [0 0 144 72]"));
+
+        svg_to_pdf("<not svg").unwrap_err();
+    }
+
+    #[test]
+    fn test_qr_svg() {
+        let labels = vec![vec![EntryField::new("KEY", "BC")]];
+        let filled = fill_labels("<g>{{QR}}</g>", &labels);
+
+        assert_eq!(filled, format!("<g>{}</g>", qr_svg("BC")));
+        assert!(filled.starts_with("<g><path d=\"M0,0h1v1h-1z"));
+        assert!(filled.ends_with("\" fill=\"#000000\"/></g>"));
+
+        // A version 1 code is 21 modules wide, so nothing past 20
+        assert!(!filled.contains("M21,"));
+    }
+
+    #[test]
+    fn test_code128_svg() {
+        // Every symbol is 11 modules wide, the stop is 13
+        for (i, pattern) in CODE128_PATTERNS.iter().enumerate() {
+            let width: u32 = pattern.bytes().map(|width| (width - b'0') as u32).sum();
+
+            assert_eq!(width, if i == CODE128_STOP { 13 } else { 11 });
+        }
+
+        // Start, two characters and the checksum have three bars each, the
+        // stop has four
+        let svg = code128_svg("BC");
+
+        assert_eq!(svg.matches("<rect").count(), 16);
+        assert!(svg.starts_with("<svg viewBox=\"0 0 77 1\""));
+        assert!(svg.contains("<rect x=\"10\" y=\"0\" width=\"2\" height=\"1\"/>"));
+
+        assert_eq!(code128_svg("é"), "");
+    }
+}
@@ -17,21 +17,35 @@
 // pinv(in a file named COPYING).
 // If not, see <https://www.gnu.org/licenses/>.
 use crate::b64;
+use crate::config::Config;
+use crate::config::Confirmation;
 use crate::db;
 use crate::db::Catagory;
 use crate::db::CatagoryField;
 use crate::db::Condition;
+use crate::db::ConditionConnector;
 use crate::db::ConditionOperator;
 use crate::db::Db;
 use crate::db::Entry;
 use crate::db::EntryField;
+use crate::db::Sort;
+use crate::db::SortOrder;
+use crate::state::State;
+use crate::table;
+use crate::task::Task;
 use crate::templates;
-use chrono::{Local, TimeZone};
+use chrono::Local;
 use cursive::event::Event;
 use cursive::event::Key;
+use cursive::theme::BaseColor;
+use cursive::theme::Color;
+use cursive::theme::ColorStyle;
+use cursive::theme::Style;
+use cursive::utils::markup::StyledString;
 use cursive::view::Nameable;
 use cursive::view::Resizable;
 use cursive::views::Button;
+use cursive::views::Checkbox;
 use cursive::views::Dialog;
 use cursive::views::EditView;
 use cursive::views::LinearLayout;
@@ -43,24 +57,21 @@ use cursive::views::TextView;
 use cursive::views::ViewRef;
 use cursive::Cursive;
 use cursive::CursiveExt;
-use directories::ProjectDirs;
-use libflate::gzip::Decoder;
 use simple_error::bail;
-use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
-use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 // ID of the list view
 static TUI_LIST_ID: &str = "list";
 
-// Column Padding
-static TUI_COLUMN_PADDING: &str = " | ";
-
-// Column Padding Width
-const TUI_COLUMN_PADDING_LEN: usize = 3;
-
 // Field Entry Width
 const TUI_FIELD_ENTRY_WIDTH: usize = 16;
 
@@ -74,6 +85,8 @@ static TUI_CATAGORY_NAME_ID: &str = "catagory_name";
 
 // ID of the type select view
 static TUI_TYPE_MENU_ID: &str = "type_menu";
+// ID of the checkbox marking a new field unique
+static TUI_UNIQUE_ID: &str = "unique";
 
 static TUI_FIND_KEY_ID: &str = "find_key";
 
@@ -91,7 +104,24 @@ static TUI_FIELD_SELECT_ID: &str = "field_select";
 
 static TUI_OP_SELECT_ID: &str = "op_select";
 
+static TUI_CONNECTOR_SELECT_ID: &str = "connector_select";
+
+static TUI_SORT_ORDER_SELECT_ID: &str = "sort_order_select";
+
+static TUI_SEARCH_EDIT_ID: &str = "search_edit";
+
 static TUI_VIEW_ID: &str = "view";
+/// ID of the field list in the edit catagory dialog
+static TUI_EDIT_FIELD_LIST_ID: &str = "edit_field_list";
+/// ID of the text listing the fields that aren't valid in the add entry dialog
+static TUI_INVALID_FIELDS_ID: &str = "invalid_fields";
+/// ID of the edit view used to retype the number of entries affected
+static TUI_CONFIRM_EDIT_ID: &str = "confirm_edit";
+
+static TUI_TASK_STATUS_ID: &str = "task_status";
+
+// How often the spinner of a running task is updated
+const TUI_TASK_TICK: Duration = Duration::from_millis(100);
 
 /// Enum used when loading templates to determin if it's a built in or a file
 enum TemplateType {
@@ -108,40 +138,100 @@ enum LayerType {
     Dialog(OnEventView<Dialog>),
 }
 
+/// Function used to build a layer, also used as the escape action
+type LayerInit = fn(&mut Cursive) -> Result<LayerType, Box<dyn Error>>;
+
+/// A key bound to an action in a view. The help dialogs are built from the
+/// same bindings the views use, so they can't get out of sync.
+struct Binding {
+    /// Name of what the binding does, used to give it another key in the
+    /// config
+    name: &'static str,
+    /// Key to press by default, a single character or "Del"
+    key: &'static str,
+    /// What the key does, shown in the help dialog
+    help: &'static str,
+    /// Called when the key is pressed
+    action: fn(&mut Cursive),
+}
+
 /// Struct used for interfacing with the TUI. Uses the Cursive library.
 pub struct Tui {
     cursive: Cursive,
+    /// Problems with the config, shown once the TUI starts
+    warnings: Vec<String>,
 }
 
 impl Tui {
     /// Create a new TUI instance with a database.
     pub fn new(db: Db) -> Result<Self, Box<dyn Error>> {
+        // A broken config shouldn't keep the TUI from starting, warn about it
+        // and use the defaults instead
+        let (mut config, mut warnings) = match Config::load() {
+            Ok(config) => (config, Vec::new()),
+            Err(error) => (
+                Config::default(),
+                vec![format!(
+                    "Couldn't load the config, using the defaults: {}",
+                    error
+                )],
+            ),
+        };
+
+        let (keys, key_warnings) = Self::check_keys(&config.keys);
+        warnings.extend(key_warnings);
+        warnings.extend(config.check_timestamps());
+
+        let state = match State::load() {
+            Ok(state) => state,
+            Err(error) => {
+                warnings.push(format!("Couldn't load where the TUI left off: {}", error));
+                State::default()
+            }
+        };
+
         let mut tui = Self {
             cursive: Cursive::new(),
+            warnings,
         };
 
         // Initialize all important paths
-        let qualifier = "org";
-        let organisation = crate::ORGANISATION;
-        let application = crate::APPLICATION;
-
-        let dirs = ProjectDirs::from(qualifier, organisation, application).unwrap();
-
-        let mut template_dir = dirs.data_dir().to_owned();
-        template_dir.push("templates");
+        let template_dir = templates::template_dir()?;
         // Create directory if it doesn't exist
         if !template_dir.exists() {
-            fs::create_dir_all(template_dir.as_path()).unwrap();
+            if let Err(error) = fs::create_dir_all(template_dir.as_path()) {
+                bail!(
+                    "Couldn't create the template directory {}: {}",
+                    template_dir.display(),
+                    error
+                );
+            }
         }
 
+        // Pick up where the last session left off, unless the catagory is gone
+        let (selected_catagory, selected_key) =
+            match db.list_catagories()?.contains(&state.catagory) {
+                true => (state.catagory, state.key),
+                false => (String::new(), 0),
+            };
+
         let tui_cache = TuiCache {
             db,
             template_dir,
             edited_ids: Vec::new(),
             constraints: Vec::new(),
             escape_action: Vec::new(),
-            selected_catagory: String::new(),
-            selected_key: 0,
+            selected_catagory,
+            selected_key,
+            catagories_reversed: false,
+            sort: None,
+            keys,
+            config,
+            marked: Vec::new(),
+            clipboard: None,
+            search: String::new(),
+            entry_rows: Vec::new(),
+            task: None,
         };
 
         tui.cursive.set_user_data(tui_cache);
@@ -153,26 +243,46 @@ impl Tui {
     /// Run the TUI instance
     pub fn run(&mut self) {
         Self::push_layer(&mut self.cursive, Self::catagory_view);
+
+        if !self.warnings.is_empty() {
+            Self::info_dialog(&mut self.cursive, &self.warnings.join("\n"));
+        }
         self.cursive.run_crossterm().unwrap();
-    }
 
-    /// Call to add a layer
-    fn push_layer(
-        cursive: &mut Cursive,
-        init: fn(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>>,
-    ) {
-        let cache = cursive.user_data::<TuiCache>().unwrap();
+        let cache = self.cursive.user_data::<TuiCache>().unwrap();
 
-        cache.escape_action.push(init);
+        let state = State {
+            catagory: cache.selected_catagory.clone(),
+            key: cache.selected_key,
+        };
+
+        if let Err(error) = state.save() {
+            eprintln!("Couldn't save where the TUI left off: {}", error);
+        }
+    }
 
+    /// Call to add a layer
+    fn push_layer(cursive: &mut Cursive, init: LayerInit) {
         let layer = match init(cursive) {
             Ok(layer) => layer,
             Err(error) => {
+                let cache = cursive.user_data::<TuiCache>().unwrap();
+
+                // A layer that failed to open can't be escaped back to, but
+                // the base layer always needs to be there to rebuild
+                if cache.escape_action.is_empty() {
+                    cache.escape_action.push(init);
+                }
+
                 Self::error_dialog(cursive, error);
                 return;
             }
         };
 
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        cache.escape_action.push(init);
+
         match layer {
             LayerType::View(view) => {
                 cursive.pop_layer();
@@ -228,7 +338,7 @@ impl Tui {
 
         cache.escape_action.clear();
 
-        while let Some(_) = cursive.pop_layer() {}
+        while cursive.pop_layer().is_some() {}
 
         Self::push_layer(cursive, escape_action);
     }
@@ -237,83 +347,327 @@ impl Tui {
     fn prime(&mut self) {
         // Bind esc to do whatever is at the top of the escape action stack
         self.cursive
-            .set_on_post_event(Event::Key(Key::Esc), |cursive| Self::pop_layer(cursive));
+            .set_on_post_event(Event::Key(Key::Esc), Self::pop_layer);
     }
 
-    /// Bindings for all views
-    fn prime_view(view: &mut OnEventView<LinearLayout>) {
-        // Bind f to find mode
-        view.set_on_event(Event::Char('f'), |cursive| {
-            Self::push_layer(cursive, Self::find_dialog)
-        });
+    /// Bindings every view has, see Binding
+    const VIEW_BINDINGS: &'static [Binding] = &[
+        Binding {
+            name: "find",
+            key: "f",
+            help: "find an entry by key",
+            action: |cursive| Self::push_layer(cursive, Self::find_dialog),
+        },
+        Binding {
+            name: "fill_template",
+            key: "p",
+            help: "fill an svg template",
+            action: |cursive| Self::push_layer(cursive, Self::fill_template_dialog),
+        },
+        Binding {
+            name: "refresh",
+            key: "R",
+            help: "reload from the database",
+            action: Self::refresh,
+        },
+    ];
 
-        // Bind p to fill template mode
-        view.set_on_event(Event::Char('p'), |cursive| {
-            Self::push_layer(cursive, Self::fill_template_dialog)
-        });
+    /// Bindings for catagory view
+    const CATAGORY_VIEW_BINDINGS: &'static [Binding] = &[
+        Binding {
+            name: "help",
+            key: "?",
+            help: "show this help",
+            action: |cursive| Self::push_layer(cursive, Self::catagory_help_dialog),
+        },
+        Binding {
+            name: "add_catagory",
+            key: "a",
+            help: "add a catagory",
+            action: |cursive| Self::push_layer(cursive, Self::add_catagory_dialog),
+        },
+        Binding {
+            name: "alter_catagory",
+            key: "A",
+            help: "add a field to the catagory",
+            action: |cursive| Self::push_layer(cursive, Self::alter_catagory_dialog),
+        },
+        Binding {
+            name: "edit_catagory",
+            key: "e",
+            help: "add and rename the catagory's fields",
+            action: |cursive| Self::push_layer(cursive, Self::edit_catagory_dialog),
+        },
+        Binding {
+            name: "delete_catagory",
+            key: "Del",
+            help: "delete the catagory",
+            action: |cursive| Self::push_layer(cursive, Self::delete_catagory_dialog),
+        },
+        Binding {
+            name: "vacuum",
+            key: "V",
+            help: "vacuum the database",
+            action: |cursive| Self::push_layer(cursive, Self::vacuum_dialog),
+        },
+        Binding {
+            name: "reverse",
+            key: "r",
+            help: "reverse the order of the catagories",
+            action: |cursive| {
+                let cache = cursive.user_data::<TuiCache>().unwrap();
+
+                cache.catagories_reversed = !cache.catagories_reversed;
+
+                Self::base_layer(cursive)
+            },
+        },
+    ];
+
+    /// Bindings for entry view
+    const ENTRY_VIEW_BINDINGS: &'static [Binding] = &[
+        Binding {
+            name: "help",
+            key: "?",
+            help: "show this help",
+            action: |cursive| Self::push_layer(cursive, Self::entry_help_dialog),
+        },
+        Binding {
+            name: "add_entry",
+            key: "a",
+            help: "add an entry",
+            action: |cursive| Self::push_layer(cursive, Self::add_entry_dialog),
+        },
+        Binding {
+            name: "give",
+            key: "+",
+            help: "give to the entry",
+            action: |cursive| Self::push_layer(cursive, Self::give_dialog),
+        },
+        Binding {
+            name: "take",
+            key: "-",
+            help: "take from the entry",
+            action: |cursive| Self::push_layer(cursive, Self::take_dialog),
+        },
+        Binding {
+            name: "modify",
+            key: "m",
+            help: "modify the entry",
+            action: |cursive| Self::push_layer(cursive, Self::mod_entry_dialog),
+        },
+        Binding {
+            name: "copy_key",
+            key: "k",
+            help: "copy the entry's key to the clipboard",
+            action: Self::copy_key,
+        },
+        Binding {
+            name: "yank",
+            key: "y",
+            help: "yank the entry",
+            action: |cursive| Self::push_layer(cursive, Self::yank_entry_dialog),
+        },
+        Binding {
+            name: "filter",
+            key: "F",
+            help: "filter the entries",
+            action: |cursive| Self::push_layer(cursive, Self::filter_dialog),
+        },
+        Binding {
+            name: "search",
+            key: "/",
+            help: "search the entries listed",
+            action: |cursive| Self::push_layer(cursive, Self::search_dialog),
+        },
+        Binding {
+            name: "sort",
+            key: "s",
+            help: "sort the entries",
+            action: |cursive| Self::push_layer(cursive, Self::sort_dialog),
+        },
+        Binding {
+            name: "pop_constraint",
+            key: "c",
+            help: "clear the last constraint",
+            action: |cursive| Self::push_layer(cursive, Self::pop_constraint),
+        },
+        Binding {
+            name: "clear_constraints",
+            key: "C",
+            help: "clear all constraints",
+            action: |cursive| Self::push_layer(cursive, Self::clear_constraints),
+        },
+        Binding {
+            name: "mark",
+            key: "Space",
+            help: "mark the entry, or unmark it",
+            action: Self::toggle_mark,
+        },
+        Binding {
+            name: "delete_marked",
+            key: "D",
+            help: "delete the marked entries",
+            action: |cursive| Self::push_layer(cursive, Self::delete_marked_dialog),
+        },
+        Binding {
+            name: "export",
+            key: "x",
+            help: "export the entries listed to a CSV file",
+            action: |cursive| Self::push_layer(cursive, Self::export_dialog),
+        },
+        Binding {
+            name: "delete_entry",
+            key: "Del",
+            help: "move the entry to the trash",
+            action: |cursive| Self::push_layer(cursive, Self::delete_entry_dialog),
+        },
+    ];
+
+    /// Bindings of each view, checked against each other for keys bound
+    /// twice
+    const VIEWS: [[&'static [Binding]; 2]; 2] = [
+        [Self::VIEW_BINDINGS, Self::CATAGORY_VIEW_BINDINGS],
+        [Self::VIEW_BINDINGS, Self::ENTRY_VIEW_BINDINGS],
+    ];
+
+    /// Bind every key in bindings on a view, using the keys from the config
+    /// where there are any
+    fn prime_bindings(
+        view: &mut OnEventView<LinearLayout>,
+        bindings: &[Binding],
+        keys: &HashMap<String, String>,
+    ) {
+        for binding in bindings {
+            view.set_on_event(
+                key_event(binding_key(binding, keys)).unwrap(),
+                binding.action,
+            );
+        }
     }
 
     /// Bindings for catagory view
-    fn prime_catagory_view(view: &mut OnEventView<LinearLayout>) {
-        Self::prime_view(view);
+    fn prime_catagory_view(view: &mut OnEventView<LinearLayout>, keys: &HashMap<String, String>) {
+        Self::prime_bindings(view, Self::VIEW_BINDINGS, keys);
+        Self::prime_bindings(view, Self::CATAGORY_VIEW_BINDINGS, keys);
+    }
 
-        // Bind a to add_catagory mode
-        view.set_on_event(Event::Char('a'), |cursive| {
-            Self::push_layer(cursive, Self::add_catagory_dialog)
-        });
+    /// Bindings for entry view
+    fn prime_entry_view(view: &mut OnEventView<LinearLayout>, keys: &HashMap<String, String>) {
+        Self::prime_bindings(view, Self::VIEW_BINDINGS, keys);
+        Self::prime_bindings(view, Self::ENTRY_VIEW_BINDINGS, keys);
+    }
 
-        // Bind Del to the delete catagory dialog
-        view.set_on_event(Event::Key(Key::Del), |cursive| {
-            Self::push_layer(cursive, Self::delete_catagory_dialog)
-        });
+    /// Dialog listing the keys of catagory view
+    fn catagory_help_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let help = format!(
+            "{}{}Enter - open the catagory\nEsc - exit",
+            bindings_help(Self::CATAGORY_VIEW_BINDINGS, &cache.keys),
+            bindings_help(Self::VIEW_BINDINGS, &cache.keys)
+        );
+
+        Ok(Self::help_dialog(&help))
     }
 
-    /// Bindings for entry view
-    fn prime_entry_view(view: &mut OnEventView<LinearLayout>) {
-        Self::prime_view(view);
+    /// Dialog listing the keys of entry view
+    fn entry_help_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        // Bind a to add_entry mode
-        view.set_on_event(Event::Char('a'), |cursive| {
-            Self::push_layer(cursive, Self::add_entry_dialog)
-        });
+        let help = format!(
+            "{}{}Esc - go back to catagory view",
+            bindings_help(Self::ENTRY_VIEW_BINDINGS, &cache.keys),
+            bindings_help(Self::VIEW_BINDINGS, &cache.keys)
+        );
 
-        // Bind + and - to give and take mode
-        view.set_on_event(Event::Char('+'), |cursive| {
-            Self::push_layer(cursive, Self::give_dialog)
-        });
-        view.set_on_event(Event::Char('-'), |cursive| {
-            Self::push_layer(cursive, Self::take_dialog)
-        });
+        Ok(Self::help_dialog(&help))
+    }
 
-        // Bind m to modify mode
-        view.set_on_event(Event::Char('m'), |cursive| {
-            Self::push_layer(cursive, Self::mod_entry_dialog)
-        });
+    /// Check the keys the config gives bindings, by binding name. Returns the
+    /// keys that can be used along with a warning for each one that can't.
+    /// Unknown bindings and keys are left out, and so are keys that would end
+    /// up bound twice in a view, so those bindings keep their defaults.
+    fn check_keys(config_keys: &HashMap<String, String>) -> (HashMap<String, String>, Vec<String>) {
+        let mut keys = HashMap::<String, String>::new();
+        let mut warnings = Vec::<String>::new();
 
-        // Bind y to yank_entry mode
-        view.set_on_event(Event::Char('y'), |cursive| {
-            Self::push_layer(cursive, Self::yank_entry_dialog)
-        });
+        // Sorted so the warnings come out in the same order every time
+        let mut config_keys: Vec<(&String, &String)> = config_keys.iter().collect();
+        config_keys.sort();
 
-        // Bind f to filter mode
-        view.set_on_event(Event::Char('F'), |cursive| {
-            Self::push_layer(cursive, Self::filter_dialog)
-        });
+        for (name, key) in config_keys {
+            let known = Self::VIEWS
+                .iter()
+                .flatten()
+                .flat_map(|bindings| bindings.iter())
+                .any(|binding| binding.name == name);
+
+            if !known {
+                warnings.push(format!("There is no key binding named {}!", name));
+            } else if key_event(key).is_none() {
+                warnings.push(format!("Can't bind {} to \"{}\"!", name, key));
+            } else {
+                keys.insert(name.to_owned(), key.to_owned());
+            }
+        }
 
-        // Bind c to clear last constraint
-        view.set_on_event(Event::Char('c'), |cursive| {
-            Self::push_layer(cursive, Self::pop_constraint)
-        });
+        // Giving a binding its default back can clash with another binding
+        // given a new key, so keep going until nothing clashes
+        loop {
+            let mut clashing = Vec::<&str>::new();
 
-        // Bind C to clear all constraints
-        view.set_on_event(Event::Char('C'), |cursive| {
-            Self::push_layer(cursive, Self::clear_constraints)
-        });
+            for view in Self::VIEWS {
+                let bindings: Vec<&Binding> =
+                    view.iter().flat_map(|bindings| bindings.iter()).collect();
 
-        // Bind Del to the delete dialog
-        view.set_on_event(Event::Key(Key::Del), |cursive| {
-            Self::push_layer(cursive, Self::delete_entry_dialog)
-        });
+                for (i, binding) in bindings.iter().enumerate() {
+                    let key = binding_key(binding, &keys);
+
+                    for other in &bindings[..i] {
+                        if binding_key(other, &keys) != key {
+                            continue;
+                        }
+
+                        let warning = format!(
+                            "{} and {} are both bound to {}, using their default keys!",
+                            other.name, binding.name, key
+                        );
+
+                        // Bindings every view has would be warned about twice
+                        if !warnings.contains(&warning) {
+                            warnings.push(warning);
+                        }
+
+                        clashing.push(other.name);
+                        clashing.push(binding.name);
+                    }
+                }
+            }
+
+            if clashing.is_empty() {
+                break;
+            }
+
+            for name in clashing {
+                keys.remove(name);
+            }
+        }
+
+        (keys, warnings)
+    }
+
+    /// Read-only dialog showing help text
+    fn help_dialog(help: &str) -> LayerType {
+        let dialog = Dialog::around(ScrollView::new(TextView::new(help)))
+            .button("Ok", Self::pop_layer)
+            .title("Keys");
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        LayerType::Dialog(dialog)
     }
 
     /// Bindings for all dialog views
@@ -347,16 +701,33 @@ impl Tui {
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        let catagories = cache.db.list_catagories()?;
+        let mut catagories = cache.db.list_catagories()?;
 
-        let catagory_table = cache.db.stat_catagories()?;
+        let mut catagory_table = cache.db.stat_catagories()?;
+
+        // Both are sorted A to Z, flip them if need be
+        if cache.catagories_reversed {
+            catagories.reverse();
+            catagory_table.reverse();
+        }
 
         let headers = vec!["NAME".to_string(), "ENTRIES".to_string()];
 
-        let columnated_catagories = Self::columnator(headers, catagory_table);
+        let catagory_count = catagories.len();
+
+        // Select the catagory last opened
+        let catagory_selected = catagories
+            .iter()
+            .position(|catagory| *catagory == cache.selected_catagory)
+            .unwrap_or(0);
+
+        let columnated_catagories = table::columnator(headers, catagory_table, None);
 
         // Ensure there are no remaining constraints as this can cause errors...
         cache.constraints.clear();
+        cache.sort = None;
+        cache.search.clear();
+        cache.marked.clear();
 
         let status_header = TextView::new("CATAGORY VIEW").center().full_width();
         let list_view_header = TextView::new(&columnated_catagories[0]).full_width();
@@ -367,11 +738,16 @@ impl Tui {
                     .enumerate()
                     .map(move |(i, catagory)| (columnated_catagories[i + 1].clone(), catagory)),
             )
+            .selected(catagory_selected)
             .on_submit(|cursive, catagory: &str| {
                 let cache = cursive.user_data::<TuiCache>().unwrap();
 
-                cache.selected_catagory = catagory.to_string();
-                cache.selected_key = 0;
+                // Going back into the same catagory selects the same entry
+                if cache.selected_catagory != catagory {
+                    cache.selected_catagory = catagory.to_string();
+                    cache.selected_key = 0;
+                }
+
                 Self::push_layer(cursive, Self::entry_view)
             })
             .with_name(TUI_LIST_ID)
@@ -386,14 +762,20 @@ impl Tui {
 
         let list_layout_scroll = ScrollView::new(list_layout).scroll_x(true).scroll_y(false);
 
-        let layout = LinearLayout::vertical()
-            .child(status_header)
-            .child(list_layout_scroll);
+        let mut layout = LinearLayout::vertical().child(status_header);
+
+        let add_key = binding_key(&Self::CATAGORY_VIEW_BINDINGS[1], &cache.keys);
+
+        if let Some(message) = empty_list_message("catagories", add_key, catagory_count, false) {
+            layout.add_child(TextView::new(message).center().full_width());
+        }
+
+        layout.add_child(list_layout_scroll);
 
         // Make keys bindable to this view
         let mut layout = OnEventView::new(layout);
 
-        Self::prime_catagory_view(&mut layout);
+        Self::prime_catagory_view(&mut layout, &cache.keys);
 
         let layout = layout.with_name(TUI_VIEW_ID);
         // Clear all and add the layout to cursive
@@ -411,68 +793,103 @@ impl Tui {
         let catagory_name = cache.selected_catagory.clone();
         let key = cache.selected_key;
 
-        let entries = cache
-            .db
-            .search_catagory(&catagory_name, &cache.constraints)?;
+        // Only load what the list shows, dialogs grab the full entry
+        let entries = cache.db.search_catagory_light(
+            &catagory_name,
+            &cache.constraints,
+            cache.sort.as_ref(),
+        )?;
 
         // Grab the catagory's field headers
-        let headers = cache.db.grab_catagory_fields(&catagory_name)?;
+        let headers = cache.db.light_fields(&catagory_name)?;
 
         // Convert the entries into a table
-        let mut entry_table = Vec::<Vec<String>>::with_capacity(entries.len());
+        let entry_table: Vec<Vec<String>> = entries
+            .iter()
+            .map(|entry| entry_cells(entry, &cache.config))
+            .collect();
 
-        let mut entry_selected: usize = 0;
+        // Columnate the entries
+        let columnated_entries = table::columnator(headers, entry_table, None);
 
-        for (i, entry) in entries.iter().enumerate() {
-            let created_str = Local.timestamp_opt(entry.created, 0).unwrap().to_string();
-            let modified_str = Local.timestamp_opt(entry.modified, 0).unwrap().to_string();
+        // Keep every row around so the search can narrow them down without
+        // going back to the database
+        cache.entry_rows = columnated_entries[1..]
+            .iter()
+            .cloned()
+            .zip(entries)
+            .collect();
 
-            // If the key is equal to the one specified, select it
-            if entry.key == key {
-                entry_selected = i;
-            }
+        let rows = shown_rows(
+            &cache.entry_rows,
+            &cache.search,
+            &cache.config,
+            &cache.marked,
+        );
 
-            let mut entry_row = Vec::<String>::with_capacity(headers.len());
+        let entry_count = rows.len();
 
-            // Push the key, location, quantity, created, and modified
-            entry_row.push(b64::from_u64(entry.key));
-            entry_row.push(entry.location.clone());
-            entry_row.push(entry.quantity.to_string());
-            entry_row.push(created_str);
-            entry_row.push(modified_str);
+        // If the key is equal to the one specified, select it
+        let entry_selected = rows
+            .iter()
+            .position(|(_, entry)| entry.key == key)
+            .unwrap_or(0);
+
+        // Count the entries without loading them, so this still works if the
+        // view only loads some of them
+        let total = cache.db.count_matching(&catagory_name, &[])?;
+        let count_string = match cache.constraints.is_empty() {
+            true => format!("{} entries", total),
+            false => format!(
+                "{} of {} entries",
+                cache
+                    .db
+                    .count_matching(&catagory_name, &cache.constraints)?,
+                total
+            ),
+        };
 
-            // Push the rest of the fields
-            for field in &entry.fields {
-                entry_row.push(field.value.clone());
-            }
+        // Set the status to inform the user that they're in entry view
+        let sort_string = match &cache.sort {
+            Some(sort) => format!(", SORTED BY {} {}", sort.field_id, sort.order.to_sql()),
+            None => String::new(),
+        };
 
-            // Push the entry to the entry table
-            entry_table.push(entry_row);
-        }
+        let search_string = match cache.search.is_empty() {
+            true => String::new(),
+            false => format!(", {} SHOWN FOR \"{}\"", entry_count, cache.search),
+        };
 
-        // Columnate the entries
-        let columnated_entries = Self::columnator(headers, entry_table);
+        let marked_string = match cache.marked.is_empty() {
+            true => String::new(),
+            false => format!(", {} MARKED", cache.marked.len()),
+        };
 
-        // Set the status to inform the user that they're in entry view
-        let mut status_string = format!("ENTRY VIEW (CATAGORY={})\n", catagory_name);
+        let mut status_string = format!(
+            "ENTRY VIEW (CATAGORY={}, {}{}{}{})\n",
+            catagory_name, count_string, sort_string, search_string, marked_string
+        );
         // Add the constraints to the status message
-        for (i, constraint) in cache.constraints.iter().enumerate() {
-            if i > 0 {
-                status_string.push_str(", ");
-            }
-            status_string.push_str(&constraint.to_string());
-        }
+        status_string.push_str(&Condition::join(&cache.constraints, |constraint| {
+            constraint.to_string()
+        }));
 
         let status_header = TextView::new(status_string).center().full_width();
-        let list_view_header = TextView::new(&columnated_entries[0]).full_width();
+        // Make room for the marks in front of the rows
+        let header = match cache.marked.is_empty() {
+            true => columnated_entries[0].clone(),
+            false => format!("  {}", columnated_entries[0]),
+        };
+
+        let list_view_header = TextView::new(header).full_width();
         let list_view = SelectView::new()
-            .with_all(
-                entries
-                    .into_iter()
-                    .enumerate()
-                    .map(move |(i, entry)| (columnated_entries[i + 1].clone(), entry)),
-            )
+            .with_all(rows)
             .selected(entry_selected)
+            .on_select(|cursive, entry: &Entry| {
+                let cache = cursive.user_data::<TuiCache>().unwrap();
+
+                cache.selected_key = entry.key;
+            })
             .with_name(TUI_LIST_ID)
             .full_width();
 
@@ -485,13 +902,21 @@ impl Tui {
 
         let list_layout_scroll = ScrollView::new(list_layout).scroll_x(true).scroll_y(false);
 
-        let layout = LinearLayout::vertical()
-            .child(status_header)
-            .child(list_layout_scroll);
+        let mut layout = LinearLayout::vertical().child(status_header);
+
+        let filtered = !cache.constraints.is_empty() || !cache.search.is_empty();
+
+        let add_key = binding_key(&Self::ENTRY_VIEW_BINDINGS[1], &cache.keys);
+
+        if let Some(message) = empty_list_message("entries", add_key, entry_count, filtered) {
+            layout.add_child(TextView::new(message).center().full_width());
+        }
+
+        layout.add_child(list_layout_scroll);
 
         // Make keys bindable to this view
         let mut layout = OnEventView::new(layout);
-        Self::prime_entry_view(&mut layout);
+        Self::prime_entry_view(&mut layout, &cache.keys);
         let layout = layout.with_name(TUI_VIEW_ID);
 
         Ok(LayerType::View(layout))
@@ -508,7 +933,7 @@ impl Tui {
         let find_row = LinearLayout::horizontal().child(find_view).child(find_edit);
 
         let dialog = Dialog::around(find_row)
-            .button("Find", |cursive| Self::find_dialog_submit(cursive))
+            .button("Find", Self::find_dialog_submit)
             .title("Find Entry");
 
         // Prime the default dialog bindings
@@ -526,7 +951,7 @@ impl Tui {
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
         let key_str = find_edit.get_content();
-        let key = match b64::to_u64(&key_str) {
+        let key = match b64::parse_key(&key_str) {
             Ok(key) => key,
             Err(error) => {
                 Self::error_dialog(cursive, error);
@@ -545,7 +970,6 @@ impl Tui {
             }
         };
 
-        drop(cache);
         Self::base_layer(cursive);
 
         let cache = cursive.user_data::<TuiCache>().unwrap();
@@ -600,7 +1024,9 @@ impl Tui {
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        let catagory_name = catagory_name_view.get_content();
+        // Catagory ids are case insensitive, so only the uppercase version
+        // needs to be valid
+        let catagory_name = catagory_name_view.get_content().to_uppercase();
 
         let fields = field_list_view
             .iter()
@@ -613,6 +1039,11 @@ impl Tui {
 
         let catagory = Catagory::with_fields(&catagory_name, fields);
 
+        if let Err(error) = Db::check_new_catagory(&catagory) {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
         match cache.db.add_catagory(catagory) {
             Ok(_) => {}
             Err(error) => {
@@ -626,6 +1057,23 @@ impl Tui {
 
     /// Dialog used to add a field to a catagory.
     fn add_catagory_field_dialog(_: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let layout = Self::catagory_field_layout(true);
+
+        let dialog = Dialog::around(layout).button("Add Field", |cursive| {
+            Self::add_catagory_field_submit(cursive)
+        });
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Name and type rows used to describe a new catagory field, see
+    /// field_from_layout. The field can be marked unique if allow_unique is
+    /// set.
+    fn catagory_field_layout(allow_unique: bool) -> LinearLayout {
         let name_view = TextView::new("Name: ");
         let name_edit = EditView::new()
             .with_name(TUI_FIELD_NAME_ID)
@@ -637,32 +1085,29 @@ impl Tui {
             .popup()
             .item("INTEGER", db::DataType::INTEGER)
             .item("REAL", db::DataType::REAL)
-            .item("TEXT", db::DataType::TEXT);
+            .item("TEXT", db::DataType::TEXT)
+            .item("BLOB", db::DataType::BLOB);
         let type_row = LinearLayout::horizontal()
             .child(type_view)
             .child(type_menu.with_name(TUI_TYPE_MENU_ID));
 
-        let layout = LinearLayout::vertical().child(name_row).child(type_row);
+        let mut layout = LinearLayout::vertical().child(name_row).child(type_row);
 
-        let dialog = Dialog::around(layout).button("Add Field", |cursive| {
-            Self::add_catagory_field_submit(cursive)
-        });
+        if allow_unique {
+            let unique_row = LinearLayout::horizontal()
+                .child(TextView::new("Unique: "))
+                .child(Checkbox::new().with_name(TUI_UNIQUE_ID));
 
-        // Prime the default dialog bindings
-        let mut dialog = OnEventView::new(dialog);
-        Self::prime_dialog(&mut dialog);
+            layout.add_child(unique_row);
+        }
 
-        Ok(LayerType::Dialog(dialog))
+        layout
     }
 
-    /// Function called when the submit button is pressed in the add catagory
-    /// field dialog.
-    fn add_catagory_field_submit(cursive: &mut Cursive) {
-        // Grab the views we need
+    /// Grab the field described in the rows made by catagory_field_layout
+    fn field_from_layout(cursive: &mut Cursive) -> CatagoryField {
         let type_menu_view: ViewRef<SelectView<db::DataType>> =
             cursive.find_name(TUI_TYPE_MENU_ID).unwrap();
-        let mut field_list_view: ViewRef<SelectView<CatagoryField>> =
-            cursive.find_name(TUI_FIELD_LIST_ID).unwrap();
         let field_name_view: ViewRef<EditView> = cursive.find_name(TUI_FIELD_NAME_ID).unwrap();
 
         let field = CatagoryField::new(
@@ -670,73 +1115,61 @@ impl Tui {
             *type_menu_view.selection().unwrap(),
         );
 
-        field_list_view.add_item(field.to_string(), field);
-
-        Self::pop_layer(cursive);
+        match cursive.find_name::<Checkbox>(TUI_UNIQUE_ID) {
+            Some(unique_checkbox) if unique_checkbox.is_checked() => field.unique(),
+            _ => field,
+        }
     }
 
-    /// Dialog used to add an entry to the database.
-    fn add_entry_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
-        // Grab the cache
-        let cache = cursive.user_data::<TuiCache>().unwrap();
-
-        let mut layout = LinearLayout::vertical();
-
-        let fields = cache.db.grab_catagory_fields(&cache.selected_catagory)?;
+    /// Function called when the submit button is pressed in the add catagory
+    /// field dialog.
+    fn add_catagory_field_submit(cursive: &mut Cursive) {
+        let field = Self::field_from_layout(cursive);
 
-        // Remove created and modified because they are autogenerated
-        let fields_a: Vec<String> = fields[..3].into();
-        let fields_b: Vec<String> = fields[5..].into();
-        let fields = [fields_a, fields_b].concat();
+        let mut field_list_view: ViewRef<SelectView<CatagoryField>> =
+            cursive.find_name(TUI_FIELD_LIST_ID).unwrap();
 
-        // First find the largest field name
-        let mut max_size: usize = 0;
+        let others: Vec<CatagoryField> = field_list_view
+            .iter()
+            .map(|(_, field)| field.clone())
+            .collect();
 
-        for field in &fields {
-            max_size = cmp::max(max_size, field.len())
+        if let Err(error) = Db::check_catagory_field(&field, &others) {
+            Self::error_dialog(cursive, error);
+            return;
         }
 
-        for (i, field) in fields.iter().enumerate() {
-            let field_id_str = format!("{}:", field);
-            let field_id = TextView::new(format!(
-                "{:<width$}",
-                field_id_str.clone(),
-                width = max_size + 2
-            ));
-
-            let mut field_entry = EditView::new().on_edit(move |cursive, _, _| {
-                let cache = cursive.user_data::<TuiCache>().unwrap();
-
-                // If the id hasn't been edited it, add it to the list of edited ids
-                if !cache.edited_ids.contains(&i)
-                {
-                    cache.edited_ids.push(i);
-                }
-            });
+        let label = match field.unique {
+            true => format!("{} UNIQUE", field),
+            false => field.to_string(),
+        };
 
-            if field_id_str == "KEY:" {
-                field_entry.set_content(b64::from_u64(cache.db.grab_next_available_key(0)?));
-                
-                // Since we are pre-adding the key, the key has technically ben pre-edited.
-                cache.edited_ids.push(i);
-            }
+        field_list_view.add_item(label, field);
 
-            let field_entry = field_entry
-                .with_name(format!("{}{}", TUI_MOD_FIELD_EDIT, i))
-                .fixed_width(TUI_FIELD_ENTRY_WIDTH);
+        Self::pop_layer(cursive);
+    }
 
-            let row = LinearLayout::horizontal()
-                .child(field_id)
-                .child(field_entry);
+    /// Dialog used to add a field to the selected catagory, which is already
+    /// in the database.
+    fn alter_catagory_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let list_view: ViewRef<SelectView> = cursive.find_name(TUI_LIST_ID).unwrap();
 
-            layout.add_child(row);
-        }
+        // Get the catagory to add the field to
+        let catagory = match list_view.selection() {
+            Some(catagory) => catagory,
+            None => {
+                bail!("No catagory to operate on!");
+            }
+        };
 
-        cache.edited_ids.clear();
+        // Unique fields can't be added to catagories that already exist
+        let layout = Self::catagory_field_layout(false);
 
         let dialog = Dialog::around(layout)
-            .title(format!("Add entry to {}...", cache.selected_catagory))
-            .button("Add", |cursive| Self::add_entry_submit(cursive));
+            .title(format!("Add Field To {}", catagory))
+            .button("Add Field", move |cursive| {
+                Self::alter_catagory_dialog_submit(cursive, &catagory)
+            });
 
         // Prime the default dialog bindings
         let mut dialog = OnEventView::new(dialog);
@@ -745,49 +1178,376 @@ impl Tui {
         Ok(LayerType::Dialog(dialog))
     }
 
-    /// Function called when the submit button is pressed in the add entry
-    /// dialog.
-    fn add_entry_submit(cursive: &mut Cursive) {
+    /// Adds the field to the catagory when the submit button is pressed in the
+    /// alter catagory dialog.
+    fn alter_catagory_dialog_submit(cursive: &mut Cursive, catagory: &str) {
+        let field = Self::field_from_layout(cursive);
+
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        let edited_ids = cache.edited_ids.clone();
-
-        let field_ids = match cache.db.grab_catagory_fields(&cache.selected_catagory) {
-            Ok(ids) => ids,
+        match cache.db.add_catagory_field(catagory, field) {
+            Ok(_) => {}
             Err(error) => {
                 Self::error_dialog(cursive, error);
                 return;
             }
-        };
+        }
 
-        // Remove created and modified because they are autogenerated
-        let fields_a: Vec<String> = field_ids[..3].into();
-        let fields_b: Vec<String> = field_ids[5..].into();
-        let field_ids = [fields_a, fields_b].concat();
+        Self::pop_layer(cursive);
 
-        let catagory = cache.selected_catagory.clone();
+        // Dialogs aren't rebuilt, so show the new field if this was opened
+        // from the edit catagory dialog
+        if let Err(error) = Self::refill_edit_field_list(cursive, catagory) {
+            Self::error_dialog(cursive, error);
+        }
+    }
 
-        // Drop the cache so we can get the edit views we need...
-        drop(cache);
+    /// Dialog used to look over the fields of the selected catagory, add new
+    /// ones and rename them. The builtin fields are listed but can't be
+    /// renamed.
+    fn edit_catagory_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let list_view: ViewRef<SelectView> = cursive.find_name(TUI_LIST_ID).unwrap();
 
-        let mut fields: Vec<EntryField> = Vec::with_capacity(edited_ids.len());
-        for id in edited_ids {
-            let edit_view: ViewRef<EditView> = cursive
-                .find_name(&format!("{}{}", TUI_MOD_FIELD_EDIT, id))
-                .unwrap();
+        let catagory = match list_view.selection() {
+            Some(catagory) => catagory,
+            None => {
+                bail!("No catagory to operate on!");
+            }
+        };
 
-            let field_id = &field_ids[id];
-            let field_value = edit_view.get_content();
+        let cache = cursive.user_data::<TuiCache>().unwrap();
 
-            let field = EntryField::new(field_id, &field_value);
+        let field_list = SelectView::<String>::new()
+            .with_all(Self::edit_field_rows(&cache.db, &catagory)?)
+            .on_submit(|cursive, _: &String| Self::push_layer(cursive, Self::rename_field_dialog))
+            .with_name(TUI_EDIT_FIELD_LIST_ID);
 
-            fields.push(field);
-        }
+        let dialog = Dialog::around(ScrollView::new(field_list))
+            .title(format!("Edit Catagory {}", catagory))
+            .button("Add Field", |cursive| {
+                Self::push_layer(cursive, Self::alter_catagory_dialog)
+            })
+            .button("Rename Field", |cursive| {
+                Self::push_layer(cursive, Self::rename_field_dialog)
+            });
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Rows of the field list in the edit catagory dialog, labeled like
+    /// "NAME:t UNIQUE" with the builtin fields marked
+    fn edit_field_rows(db: &Db, catagory: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let fields = db.grab_catagory_fields(catagory)?;
+        let types = db.grab_catagory_types(catagory)?;
+        let unique = db.unique_fields(catagory)?;
+
+        let rows = fields
+            .into_iter()
+            .zip(types)
+            .map(|(id, datatype)| {
+                let label = match (
+                    db::BUILTIN_FIELDS.contains(&id.as_str()),
+                    unique.contains(&id),
+                ) {
+                    (true, _) => format!("{} (builtin)", id),
+                    (false, true) => format!("{}:{} UNIQUE", id, datatype.get_char()),
+                    (false, false) => format!("{}:{}", id, datatype.get_char()),
+                };
+
+                (label, id)
+            })
+            .collect();
+
+        Ok(rows)
+    }
+
+    /// Refill the field list of the edit catagory dialog, if it's open
+    fn refill_edit_field_list(cursive: &mut Cursive, catagory: &str) -> Result<(), Box<dyn Error>> {
+        let rows = {
+            let cache = cursive.user_data::<TuiCache>().unwrap();
+
+            Self::edit_field_rows(&cache.db, catagory)?
+        };
+
+        if let Some(mut field_list) = cursive.find_name::<SelectView>(TUI_EDIT_FIELD_LIST_ID) {
+            let selected = field_list.selected_id().unwrap_or(0);
+
+            field_list.clear();
+            field_list.add_all(rows);
+            field_list.set_selection(selected);
+        }
+
+        Ok(())
+    }
+
+    /// Dialog used to rename the field selected in the edit catagory dialog
+    fn rename_field_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let field_list: ViewRef<SelectView> = cursive.find_name(TUI_EDIT_FIELD_LIST_ID).unwrap();
+
+        let field = match field_list.selection() {
+            Some(field) => field,
+            None => {
+                bail!("No field to operate on!");
+            }
+        };
+
+        if db::BUILTIN_FIELDS.contains(&field.as_str()) {
+            bail!("Builtin fields can't be renamed!");
+        }
+
+        let name_edit = EditView::new()
+            .content(field.as_str())
+            .with_name(TUI_FIELD_NAME_ID)
+            .fixed_width(TUI_FIELD_ENTRY_WIDTH);
+
+        let layout = LinearLayout::horizontal()
+            .child(TextView::new("Name: "))
+            .child(name_edit);
+
+        let dialog = Dialog::around(layout)
+            .title(format!("Rename Field {}", field))
+            .button("Rename", move |cursive| {
+                Self::rename_field_dialog_submit(cursive, &field)
+            });
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Renames the field when the submit button is pressed in the rename field
+    /// dialog.
+    fn rename_field_dialog_submit(cursive: &mut Cursive, field: &str) {
+        let list_view: ViewRef<SelectView> = cursive.find_name(TUI_LIST_ID).unwrap();
+        let name_view: ViewRef<EditView> = cursive.find_name(TUI_FIELD_NAME_ID).unwrap();
+
+        let catagory = list_view.selection().unwrap();
+        let new_field = name_view.get_content().to_uppercase();
+
+        // The views are looked up again when the dialog closes
+        drop(list_view);
+        drop(name_view);
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        if let Err(error) = cache.db.rename_catagory_field(&catagory, field, &new_field) {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
+        Self::pop_layer(cursive);
+
+        if let Err(error) = Self::refill_edit_field_list(cursive, &catagory) {
+            Self::error_dialog(cursive, error);
+        }
+    }
+
+    /// Dialog used to add an entry to the database.
+    fn add_entry_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let mut layout = LinearLayout::vertical();
+
+        let fields = Self::editable_fields(&cache.db, &cache.selected_catagory)?;
+        let labels = Self::field_labels(&cache.db, &cache.selected_catagory, &fields)?;
+
+        // The fields are checked on every key press, so their types are only
+        // looked up once
+        let all_fields = cache.db.grab_catagory_fields(&cache.selected_catagory)?;
+        let all_types = cache.db.grab_catagory_types(&cache.selected_catagory)?;
+
+        let field_types: Arc<[(String, db::DataType)]> = fields
+            .iter()
+            .map(|field| {
+                let datatype = match all_fields.iter().position(|id| id == field) {
+                    Some(i) => all_types[i],
+                    None => db::DataType::TEXT,
+                };
+
+                (field.clone(), datatype)
+            })
+            .collect();
+
+        let mut key_id: Option<usize> = None;
+
+        for (i, (field, label)) in fields.iter().zip(labels).enumerate() {
+            let field_id = TextView::new(label);
+
+            let edit_types = field_types.clone();
+
+            let mut field_entry = EditView::new().on_edit(move |cursive, _, _| {
+                let cache = cursive.user_data::<TuiCache>().unwrap();
+
+                // If the id hasn't been edited it, add it to the list of edited ids
+                if !cache.edited_ids.contains(&i) {
+                    cache.edited_ids.push(i);
+                }
+
+                Self::add_entry_check(cursive, &edit_types);
+            });
+
+            if field == "KEY" {
+                field_entry.set_content(b64::from_u64(cache.db.grab_next_available_key(0)?));
+
+                // Since we are pre-adding the key, the key has technically ben pre-edited.
+                cache.edited_ids.push(i);
+                key_id = Some(i);
+            }
+
+            let field_entry = field_entry
+                .with_name(format!("{}{}", TUI_MOD_FIELD_EDIT, i))
+                .fixed_width(TUI_FIELD_ENTRY_WIDTH);
+
+            let row = LinearLayout::horizontal()
+                .child(field_id)
+                .child(field_entry);
+
+            layout.add_child(row);
+        }
+
+        cache.edited_ids.clear();
+
+        layout.add_child(TextView::new("").with_name(TUI_INVALID_FIELDS_ID));
+
+        let submit_types = field_types.clone();
+
+        let mut dialog = Dialog::around(layout)
+            .title(format!("Add entry to {}...", cache.selected_catagory))
+            .button("Add", move |cursive| {
+                Self::add_entry_submit(cursive, &submit_types)
+            });
+
+        // Let the user pick between reusing a deleted key and a brand new one
+        if let Some(key_id) = key_id {
+            let gap_types = field_types.clone();
+
+            dialog.add_button("Fill Gap", move |cursive| {
+                Self::add_entry_set_key(cursive, key_id, false, &gap_types)
+            });
+            dialog.add_button("New Key", move |cursive| {
+                Self::add_entry_set_key(cursive, key_id, true, &field_types)
+            });
+        }
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Function called when the fill gap or new key buttons are pressed in
+    /// the add entry dialog. Fills in the first unused key, or a key that's
+    /// never been used if fresh is set.
+    fn add_entry_set_key(
+        cursive: &mut Cursive,
+        key_id: usize,
+        fresh: bool,
+        field_types: &[(String, db::DataType)],
+    ) {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let key = match fresh {
+            true => cache.db.grab_max_key_plus_one(),
+            false => cache.db.grab_next_available_key(0),
+        };
+
+        let key = match key {
+            Ok(key) => key,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+        };
+
+        let mut edit_view: ViewRef<EditView> = cursive
+            .find_name(&format!("{}{}", TUI_MOD_FIELD_EDIT, key_id))
+            .unwrap();
+
+        edit_view.set_content(b64::from_u64(key));
+
+        drop(edit_view);
+
+        Self::add_entry_check(cursive, field_types);
+    }
+
+    /// Check every field of the add entry dialog, coloring the ones that
+    /// aren't valid red and listing them under the fields. Returns the fields
+    /// that aren't valid. field_types are the fields of the dialog in order,
+    /// along with their types.
+    fn add_entry_check(
+        cursive: &mut Cursive,
+        field_types: &[(String, db::DataType)],
+    ) -> Vec<String> {
+        let mut invalid = Vec::new();
+
+        for (i, (field, datatype)) in field_types.iter().enumerate() {
+            let name = format!("{}{}", TUI_MOD_FIELD_EDIT, i);
+
+            cursive.call_on_name(&name, |edit_view: &mut EditView| {
+                match valid_value(field, &edit_view.get_content(), *datatype) {
+                    true => edit_view.set_style(ColorStyle::secondary()),
+                    false => {
+                        edit_view.set_style(ColorStyle::front(Color::Dark(BaseColor::Red)));
+                        invalid.push(field.clone());
+                    }
+                }
+            });
+        }
+
+        let message = match invalid.is_empty() {
+            true => String::new(),
+            false => format!("\nNot valid: {}", invalid.join(", ")),
+        };
+
+        cursive.call_on_name(TUI_INVALID_FIELDS_ID, |text_view: &mut TextView| {
+            text_view.set_content(message)
+        });
+
+        invalid
+    }
+
+    /// Function called when the submit button is pressed in the add entry
+    /// dialog.
+    fn add_entry_submit(cursive: &mut Cursive, field_types: &[(String, db::DataType)]) {
+        // The fields that aren't valid are already listed in the dialog
+        if !Self::add_entry_check(cursive, field_types).is_empty() {
+            return;
+        }
+
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let edited_ids = cache.edited_ids.clone();
+
+        let catagory = cache.selected_catagory.clone();
+
+        let mut fields: Vec<EntryField> = Vec::with_capacity(edited_ids.len());
+        for id in edited_ids {
+            let edit_view: ViewRef<EditView> = cursive
+                .find_name(&format!("{}{}", TUI_MOD_FIELD_EDIT, id))
+                .unwrap();
+
+            let field_id = &field_types[id].0;
+            let field_value = edit_view.get_content();
+
+            let field = EntryField::new(field_id, &field_value);
+
+            fields.push(field);
+        }
 
         // Create the entry from the aquired fields
         // This is ugly
-        let key = match b64::to_u64(
+        let key = match b64::parse_key(
             &fields
                 .iter()
                 .find(|field| field.id == "KEY")
@@ -842,6 +1602,15 @@ impl Tui {
 
         // Set the selected key
         cache.selected_key = entry.key;
+
+        if let Err(error) = cache
+            .db
+            .read_blob_files(&entry.catagory_id, &mut entry.fields)
+        {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
         match cache.db.add_entry(entry) {
             Ok(_) => {}
             Err(error) => {
@@ -855,45 +1624,39 @@ impl Tui {
 
     /// Dialog used to modify entries
     fn mod_entry_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
-        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
-        // Grab the cache
-        let mut cache = cursive.user_data::<TuiCache>().unwrap();
-
         // Get the entry
-        let entry = match list_view.selection() {
-            Some(entry) => entry,
-            None => {
-                bail!("No entry to operate on!");
-            }
-        };
+        let entry = Self::selected_entry(cursive)?;
+
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
 
         // Set the selected key
         cache.selected_key = entry.key;
+        // Immutable fields that are already set can't be edited
+        let immutable_fields = cache.db.immutable_fields(&entry.catagory_id)?;
         // Build fields based on what the entry has
         let key = EntryField::new("KEY", &b64::from_u64(entry.key));
-        let location = EntryField::new("LOCATION", &format!("{}", entry.location));
+        let location = EntryField::new("LOCATION", &entry.location.to_string());
         let quantity = EntryField::new("QUANTITY", &entry.quantity.to_string());
         let mut fields: Vec<EntryField> = vec![key, location, quantity];
 
         fields.extend_from_slice(&entry.fields);
 
+        let field_ids: Vec<String> = fields.iter().map(|field| field.id.clone()).collect();
+        let labels = Self::field_labels(&cache.db, &entry.catagory_id, &field_ids)?;
+
         // Generate rows in the dialog to reflect the fields to be modified
         let mut layout = LinearLayout::vertical();
-        // First find the largest field name(for padding reasons)
-        let mut max_size: usize = 0;
 
-        for field in &fields {
-            max_size = cmp::max(max_size, field.id.len())
-        }
-
-        for (i, field) in fields.iter().enumerate() {
-            let field_id = format!("{}:", field.id);
-            let field_id = TextView::new(format!("{:<width$}", field_id, width = max_size + 2));
+        for (i, (field, label)) in fields.iter().zip(labels).enumerate() {
+            let field_id = TextView::new(label);
 
             let field_value = field.value.clone();
+            let editable = field_value.is_empty() || !immutable_fields.contains(&field.id);
 
             let field_entry = EditView::new()
                 .content(field_value)
+                .with_enabled(editable)
                 .on_edit(move |cursive, _, _| {
                     let cache = cursive.user_data::<TuiCache>().unwrap();
 
@@ -911,8 +1674,7 @@ impl Tui {
 
         cache.edited_ids.clear();
 
-        let dialog = Dialog::around(layout)
-            .button("Modify!", |cursive| Self::mod_entry_dialog_submit(cursive));
+        let dialog = Dialog::around(layout).button("Modify!", Self::mod_entry_dialog_submit);
 
         // Prime the default dialog bindings
         let mut dialog = OnEventView::new(dialog);
@@ -923,17 +1685,17 @@ impl Tui {
 
     /// Called when the modify button is selected
     fn mod_entry_dialog_submit(cursive: &mut Cursive) {
-        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
-        // Grab the cache
-        let cache = cursive.user_data::<TuiCache>().unwrap();
-
-        let entry = match list_view.selection() {
-            Some(entry) => entry,
-            None => {
+        let entry = match Self::selected_entry(cursive) {
+            Ok(entry) => entry,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
                 return;
             }
         };
 
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
         let edited_ids = cache.edited_ids.clone();
 
         // Get all of the field ids(minus creation and mod time)
@@ -943,9 +1705,6 @@ impl Tui {
             field_ids.push(field.id.clone());
         }
 
-        // Drop the cache so we can get the edit views we need...
-        drop(cache);
-
         let mut fields: Vec<EntryField> = Vec::with_capacity(edited_ids.len());
         for id in edited_ids {
             let edit_view: ViewRef<EditView> = cursive
@@ -963,6 +1722,11 @@ impl Tui {
         // Get the cache again
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
+        if let Err(error) = cache.db.read_blob_files(&entry.catagory_id, &mut fields) {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
         match cache.db.mod_entry(entry.key, fields) {
             Ok(types) => types,
             Err(error) => {
@@ -976,41 +1740,31 @@ impl Tui {
 
     /// Dialog used to yank an entry
     fn yank_entry_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
-        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
-        // Grab the cache
-        let mut cache = cursive.user_data::<TuiCache>().unwrap();
+        // Get the entry to yank
+        let entry = Self::selected_entry(cursive)?;
 
-        // Get the entry to give or take from
-        let entry = match list_view.selection() {
-            Some(entry) => entry,
-            None => {
-                bail!("No entry to operate on!");
-            }
-        };
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
 
         // Set the selected key
         cache.selected_key = entry.key;
         // Build fields based on what the entry has
         // require only a new key be specified
         let key = EntryField::new("KEY", "");
-        let location = EntryField::new("LOCATION", &format!("{}", entry.location));
+        let location = EntryField::new("LOCATION", &entry.location.to_string());
         let quantity = EntryField::new("QUANTITY", &entry.quantity.to_string());
         let mut fields: Vec<EntryField> = vec![key, location, quantity];
 
         fields.extend_from_slice(&entry.fields);
 
+        let field_ids: Vec<String> = fields.iter().map(|field| field.id.clone()).collect();
+        let labels = Self::field_labels(&cache.db, &entry.catagory_id, &field_ids)?;
+
         // Generate rows in the dialog to reflect the fields to be modified
         let mut layout = LinearLayout::vertical();
-        // First find the largest field name(for padding reasons)
-        let mut max_size: usize = 0;
-
-        for field in &fields {
-            max_size = cmp::max(max_size, field.id.len())
-        }
 
-        for (i, field) in fields.iter().enumerate() {
-            let field_id = format!("{}:", field.id);
-            let field_id = TextView::new(format!("{:<width$}", field_id, width = max_size + 2));
+        for (i, (field, label)) in fields.iter().zip(labels).enumerate() {
+            let field_id = TextView::new(label);
 
             let field_value = field.value.clone();
 
@@ -1046,17 +1800,17 @@ impl Tui {
 
     /// Called when the "Yank & Add!" button is selected
     fn yank_entry_dialog_submit(cursive: &mut Cursive) {
-        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
-        // Grab the cache
-        let cache = cursive.user_data::<TuiCache>().unwrap();
-
-        let original_entry = match list_view.selection() {
-            Some(entry) => entry,
-            None => {
+        let original_entry = match Self::selected_entry(cursive) {
+            Ok(entry) => entry,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
                 return;
             }
         };
 
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
         let catagory = cache.selected_catagory.clone();
 
         let edited_ids = cache.edited_ids.clone();
@@ -1068,9 +1822,6 @@ impl Tui {
             field_ids.push(field.id.clone());
         }
 
-        // Drop the cache so we can get the edit views we need...
-        drop(cache);
-
         let mut fields: Vec<EntryField> = Vec::with_capacity(edited_ids.len());
         for id in edited_ids {
             let edit_view: ViewRef<EditView> = cursive
@@ -1087,7 +1838,7 @@ impl Tui {
 
         // Create the entry from the aquired fields
         // This is ugly
-        let key = match b64::to_u64(
+        let key = match b64::parse_key(
             &fields
                 .iter()
                 .find(|field| field.id == "KEY")
@@ -1145,12 +1896,11 @@ impl Tui {
                 .fields
                 .clone()
                 .into_iter()
-                .filter(move |field| !match fields_copy
-                    .iter()
-                    .find(move |new_field| &field == new_field)
-                {
-                    Some(_) => true,
-                    None => false,
+                .filter(move |field| {
+                    fields_copy
+                        .iter()
+                        .find(move |new_field| &field == new_field)
+                        .is_none()
                 })
                 .collect::<Vec<EntryField>>(),
         );
@@ -1158,6 +1908,14 @@ impl Tui {
         // Get the cache again
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
+        if let Err(error) = cache
+            .db
+            .read_blob_files(&entry.catagory_id, &mut entry.fields)
+        {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
         match cache.db.add_entry(entry) {
             Ok(types) => types,
             Err(error) => {
@@ -1173,12 +1931,7 @@ impl Tui {
     fn filter_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        let fields = cache.db.grab_catagory_fields(&cache.selected_catagory)?;
-
-        // Remove created and modified because they are autogenerated
-        let fields_a: Vec<String> = fields[..3].into();
-        let fields_b: Vec<String> = fields[5..].into();
-        let fields = [fields_a, fields_b].concat();
+        let fields = Self::editable_fields(&cache.db, &cache.selected_catagory)?;
 
         // Fields the user can select from
         let mut field_select_list = SelectView::new().popup();
@@ -1198,6 +1951,8 @@ impl Tui {
                 ConditionOperator::GreaterThan,
                 ConditionOperator::LessThanEqual,
                 ConditionOperator::GreaterThanEqual,
+                ConditionOperator::Contains,
+                ConditionOperator::StartsWith,
             ]
             .into_iter()
             .map(|x| (format!("{}", x), x)),
@@ -1211,13 +1966,28 @@ impl Tui {
             .fixed_width(TUI_FIELD_ENTRY_WIDTH);
 
         // Lay it all out horizontally
-        let layout = LinearLayout::horizontal()
+        let mut layout = LinearLayout::horizontal();
+
+        // Only ask how to join the constraint if there's something to join
+        // it to
+        if !cache.constraints.is_empty() {
+            let mut connector_select_list = SelectView::<ConditionConnector>::new().popup();
+
+            connector_select_list.add_all(
+                vec![ConditionConnector::And, ConditionConnector::Or]
+                    .into_iter()
+                    .map(|x| (format!("{}", x), x)),
+            );
+
+            layout.add_child(connector_select_list.with_name(TUI_CONNECTOR_SELECT_ID));
+        }
+
+        let layout = layout
             .child(field_select_list)
             .child(operator_select_list)
             .child(constraint_edit_view);
 
-        let dialog =
-            Dialog::around(layout).button("Filter!", |cursive| Self::filter_dialog_submit(cursive));
+        let dialog = Dialog::around(layout).button("Filter!", Self::filter_dialog_submit);
 
         // Prime the default dialog bindings
         let mut dialog = OnEventView::new(dialog);
@@ -1236,27 +2006,187 @@ impl Tui {
         let constraint_edit_view: ViewRef<EditView> =
             cursive.find_name(TUI_CONSTRAINT_EDIT_ID).unwrap();
 
-        let cache = cursive.user_data::<TuiCache>().unwrap();
-
         let field_id = field_select_list.selection().unwrap();
         let operator = operator_select_list.selection().unwrap();
         // Format the constraint value according to it's type
         let constraint_value = constraint_edit_view.get_content();
 
-        let constraint = Condition::new(&field_id, *operator, &constraint_value);
+        // The connector is only asked for when there are other constraints
+        let connector = cursive
+            .find_name::<SelectView<ConditionConnector>>(TUI_CONNECTOR_SELECT_ID)
+            .and_then(|list| list.selection())
+            .map(|connector| *connector)
+            .unwrap_or_default();
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let constraint =
+            Condition::new(&field_id, *operator, &constraint_value).with_connector(connector);
 
         cache.constraints.push(constraint);
 
         Self::pop_layer(cursive);
     }
 
+    /// Copy the key of the selected entry to the clipboard. If there's no
+    /// clipboard, like over SSH, the key is shown instead.
+    fn copy_key(cursive: &mut Cursive) {
+        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
+
+        let key = match list_view.selection() {
+            Some(entry) => b64::from_u64(entry.key),
+            None => {
+                Self::error_dialog(cursive, "No entry to operate on!".into());
+                return;
+            }
+        };
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        // The clipboard is kept around, on some systems what was copied goes
+        // away with it
+        if cache.clipboard.is_none() {
+            cache.clipboard = arboard::Clipboard::new().ok();
+        }
+
+        let copied = match &mut cache.clipboard {
+            Some(clipboard) => clipboard.set_text(key.clone()).is_ok(),
+            None => false,
+        };
+
+        let message = match copied {
+            true => format!("Copied key {} to the clipboard", key),
+            false => format!("Couldn't reach the clipboard, the key is {}", key),
+        };
+
+        Self::info_dialog(cursive, &message);
+    }
+
+    /// Dialog used to narrow down the rows of entry view as you type. Unlike
+    /// the filter dialog it only looks at the rows already loaded. Escape
+    /// clears the search.
+    fn search_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let search_edit = EditView::new()
+            .content(cache.search.clone())
+            .on_edit(|cursive, search, _| Self::search_dialog_edit(cursive, search))
+            .on_submit(|cursive, _| Self::pop_layer(cursive))
+            .with_name(TUI_SEARCH_EDIT_ID)
+            .fixed_width(TUI_FIELD_ENTRY_WIDTH * 2);
+
+        let dialog = Dialog::around(search_edit)
+            .button("Done", Self::pop_layer)
+            .title("Search");
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        dialog.set_on_event(Key::Esc, |cursive| {
+            let cache = cursive.user_data::<TuiCache>().unwrap();
+
+            cache.search.clear();
+
+            Self::pop_layer(cursive);
+        });
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Called whenever the text in the search dialog changes, narrows the
+    /// list down to the rows that match
+    fn search_dialog_edit(cursive: &mut Cursive, search: &str) {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        cache.search = search.to_owned();
+
+        let rows = shown_rows(&cache.entry_rows, search, &cache.config, &cache.marked);
+
+        let mut list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
+
+        list_view.clear();
+        list_view.add_all(rows);
+    }
+
+    /// Dialog used to pick the column entry view is sorted by
+    fn sort_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        // Any column shown in entry view can be sorted by
+        let mut field_select_list = SelectView::new().popup();
+
+        field_select_list.add_all_str(cache.db.light_fields(&cache.selected_catagory)?);
+
+        let mut order_select_list = SelectView::<SortOrder>::new().popup();
+
+        order_select_list.add_all(vec![
+            ("Ascending", SortOrder::Ascending),
+            ("Descending", SortOrder::Descending),
+        ]);
+
+        // Start from the current sort, if there is one
+        if let Some(sort) = &cache.sort {
+            let field_index = field_select_list
+                .iter()
+                .position(|(_, field_id)| *field_id == sort.field_id);
+
+            if let Some(field_index) = field_index {
+                field_select_list.set_selection(field_index);
+            }
+
+            if sort.order == SortOrder::Descending {
+                order_select_list.set_selection(1);
+            }
+        }
+
+        let layout = LinearLayout::horizontal()
+            .child(field_select_list.with_name(TUI_FIELD_SELECT_ID))
+            .child(order_select_list.with_name(TUI_SORT_ORDER_SELECT_ID));
+
+        let dialog = Dialog::around(layout)
+            .button("Unsort", |cursive| {
+                let cache = cursive.user_data::<TuiCache>().unwrap();
+
+                cache.sort = None;
+
+                Self::pop_layer(cursive);
+            })
+            .button("Sort!", Self::sort_dialog_submit);
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Called when the "Sort!" button is selected
+    fn sort_dialog_submit(cursive: &mut Cursive) {
+        let field_select_list: ViewRef<SelectView> =
+            cursive.find_name(TUI_FIELD_SELECT_ID).unwrap();
+        let order_select_list: ViewRef<SelectView<SortOrder>> =
+            cursive.find_name(TUI_SORT_ORDER_SELECT_ID).unwrap();
+
+        let sort = Sort::new(
+            &field_select_list.selection().unwrap(),
+            *order_select_list.selection().unwrap(),
+        );
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        cache.sort = Some(sort);
+
+        Self::pop_layer(cursive);
+    }
+
     /// Remove last applied constraint
     fn pop_constraint(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
         // Return if no constraints are found
-        if cache.constraints.len() == 0 {
+        if cache.constraints.is_empty() {
             bail!("No constraints to remove!");
         }
 
@@ -1269,7 +2199,7 @@ impl Tui {
             "Remove constraint {}?",
             cache.constraints.last().unwrap()
         ))
-        .button("No...", |cursive| Self::pop_layer(cursive))
+        .button("No...", Self::pop_layer)
         .button("Yes!", move |cursive| {
             let cache = cursive.user_data::<TuiCache>().unwrap();
 
@@ -1291,7 +2221,7 @@ impl Tui {
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
         // Return if no constraints are found
-        if cache.constraints.len() == 0 {
+        if cache.constraints.is_empty() {
             bail!("No constraints to remove!");
         }
 
@@ -1436,13 +2366,7 @@ impl Tui {
         let quantity: u64 = match give {
             true => entry.quantity + give_take_amt,
 
-            false => {
-                if entry.quantity > give_take_amt {
-                    entry.quantity - give_take_amt
-                } else {
-                    0
-                }
-            }
+            false => entry.quantity.saturating_sub(give_take_amt),
         };
 
         new_quantity_view.set_content(format!("New Quantity: {}", quantity));
@@ -1475,13 +2399,7 @@ impl Tui {
         let quantity: u64 = match give {
             true => entry.quantity + give_take_amt,
 
-            false => {
-                if entry.quantity > give_take_amt {
-                    entry.quantity - give_take_amt
-                } else {
-                    0
-                }
-            }
+            false => entry.quantity.saturating_sub(give_take_amt),
         };
 
         match cache.db.mod_entry(
@@ -1498,8 +2416,80 @@ impl Tui {
         Self::pop_layer(cursive);
     }
 
-    /// Dialog that confirms if you wish to delete an entry, and if so, deletes
-    /// the entry.
+    /// Dialog used to export what entry view lists, constraints, sort and
+    /// search included, to a CSV file
+    fn export_dialog(_: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let out_file_view = TextView::new("Out File: ");
+        let out_file_edit = EditView::new()
+            .with_name(TUI_OUT_FILE_ID)
+            .fixed_width(TUI_FIELD_ENTRY_WIDTH);
+        let out_file_row = LinearLayout::horizontal()
+            .child(out_file_view)
+            .child(out_file_edit);
+
+        let dialog = Dialog::around(out_file_row)
+            .title("Export To CSV")
+            .button("Export!", Self::export_dialog_submit);
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Writes the CSV if the "Export!" button is selected
+    fn export_dialog_submit(cursive: &mut Cursive) {
+        let out_file_edit: ViewRef<EditView> = cursive.find_name(TUI_OUT_FILE_ID).unwrap();
+
+        let out_path = out_file_edit.get_content();
+
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let entries = match cache.db.search_catagory_sorted(
+            &cache.selected_catagory,
+            &cache.constraints,
+            None,
+            cache.sort.as_ref(),
+        ) {
+            Ok(entries) => entries,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+        };
+
+        let entries: Vec<Entry> = entries
+            .into_iter()
+            .filter(|entry| entry_matches(entry, &cache.search, &cache.config))
+            .collect();
+
+        let count = entries.len();
+
+        let out_data = match cache.db.entries_to_csv(&cache.selected_catagory, entries) {
+            Ok(out_data) => out_data,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+        };
+
+        if let Err(error) = fs::write(out_path.as_ref(), out_data) {
+            Self::error_dialog(cursive, Box::new(error));
+            return;
+        }
+
+        Self::pop_layer(cursive);
+
+        Self::info_dialog(
+            cursive,
+            &format!("Exported {} entries to {}", count, out_path),
+        );
+    }
+
+    /// Dialog that confirms if you wish to delete an entry, and if so, moves
+    /// the entry to the trash.
     fn delete_entry_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
         let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
 
@@ -1518,11 +2508,14 @@ impl Tui {
         cache.selected_key = entry.key;
 
         // Create the dialog
-        let dialog = Dialog::text(format!("Delete entry {}?", b64::from_u64(entry.key)))
-            .button("No...", |cursive| Self::pop_layer(cursive))
-            .button("Yes!", move |cursive| {
-                Self::delete_entry_dialog_submit(cursive, entry.key);
-            });
+        let dialog = Dialog::text(format!(
+            "Move entry {} to the trash?\n\nIt can be restored with pinv untrash.",
+            b64::from_u64(entry.key)
+        ))
+        .button("No...", Self::pop_layer)
+        .button("Yes!", move |cursive| {
+            Self::delete_entry_dialog_submit(cursive, entry.key);
+        });
 
         // Prime the default dialog bindings
         let mut dialog = OnEventView::new(dialog);
@@ -1531,12 +2524,12 @@ impl Tui {
         Ok(LayerType::Dialog(dialog))
     }
 
-    /// Deletes the entry if "Yes" is selected on the delete dialog.
+    /// Trashes the entry if "Yes" is selected on the delete dialog.
     fn delete_entry_dialog_submit(cursive: &mut Cursive, key: u64) {
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        match cache.db.delete_entry(key) {
+        match cache.db.trash_entry(key) {
             Ok(_) => {}
             Err(error) => {
                 Self::error_dialog(cursive, error);
@@ -1547,6 +2540,148 @@ impl Tui {
         Self::pop_layer(cursive);
     }
 
+    /// Mark the selected entry if it isn't, otherwise unmark it
+    fn toggle_mark(cursive: &mut Cursive) {
+        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
+
+        let key = match list_view.selection() {
+            Some(entry) => entry.key,
+            None => {
+                Self::error_dialog(cursive, "No entry to operate on!".into());
+                return;
+            }
+        };
+
+        drop(list_view);
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        match cache.marked.iter().position(|marked| *marked == key) {
+            Some(i) => {
+                cache.marked.remove(i);
+            }
+            None => cache.marked.push(key),
+        }
+
+        cache.selected_key = key;
+
+        Self::refresh_view(cursive);
+    }
+
+    /// Reload the view from the database, for when something else changed it.
+    /// The selected catagory or entry stays selected if it's still there.
+    fn refresh(cursive: &mut Cursive) {
+        // Catagory view only keeps track of the catagory once it's opened
+        let catagory = cursive
+            .find_name::<SelectView>(TUI_LIST_ID)
+            .and_then(|list_view| list_view.selection());
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        if let Some(catagory) = catagory {
+            if cache.selected_catagory != *catagory {
+                cache.selected_catagory = catagory.to_string();
+                cache.selected_key = 0;
+            }
+        }
+
+        Self::refresh_view(cursive);
+    }
+
+    /// Rebuild the view on top, keeping the dialogs the same
+    fn refresh_view(cursive: &mut Cursive) {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let init = *cache.escape_action.last().unwrap();
+
+        match init(cursive) {
+            Ok(LayerType::View(view)) => {
+                cursive.pop_layer();
+                cursive.add_fullscreen_layer(view);
+            }
+            Ok(LayerType::Dialog(_)) => {}
+            Err(error) => Self::error_dialog(cursive, error),
+        }
+    }
+
+    /// Dialog that confirms if you wish to delete the marked entries. If more
+    /// entries are marked than the config allows, the number of entries has
+    /// to be typed in to confirm.
+    fn delete_marked_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let count = cache.marked.len();
+
+        if count == 0 {
+            bail!("No entries are marked!");
+        }
+
+        let text = format!(
+            "Delete {} marked entries?\n\nTHEY CAN'T BE RESTORED FROM THE TRASH!",
+            count
+        );
+
+        let dialog = match cache.config.confirmation(count) {
+            Confirmation::Normal => Dialog::text(text),
+            Confirmation::Strong => {
+                let confirm_row = LinearLayout::horizontal()
+                    .child(TextView::new(format!("Type {} to confirm: ", count)))
+                    .child(
+                        EditView::new()
+                            .with_name(TUI_CONFIRM_EDIT_ID)
+                            .fixed_width(TUI_FIELD_ENTRY_WIDTH),
+                    );
+
+                let layout = LinearLayout::vertical()
+                    .child(TextView::new(format!("{}\n\n", text)))
+                    .child(confirm_row);
+
+                Dialog::around(layout)
+            }
+        };
+
+        let dialog = dialog
+            .button("No...", Self::pop_layer)
+            .button("Yes!", Self::delete_marked_dialog_submit);
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Deletes the marked entries if "Yes" is selected on the delete marked
+    /// dialog.
+    fn delete_marked_dialog_submit(cursive: &mut Cursive) {
+        let typed = cursive
+            .find_name::<EditView>(TUI_CONFIRM_EDIT_ID)
+            .map(|confirm_edit| confirm_edit.get_content());
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let count = cache.marked.len().to_string();
+
+        if let Some(typed) = typed {
+            if typed.trim() != count {
+                Self::error_dialog(
+                    cursive,
+                    format!("Type {} to delete the entries!", count).into(),
+                );
+                return;
+            }
+        }
+
+        if let Err(error) = cache.db.delete_entries(&cache.marked) {
+            Self::error_dialog(cursive, error);
+            return;
+        }
+
+        cache.marked.clear();
+
+        Self::pop_layer(cursive);
+    }
+
     /// Dialog that confirms if you wish to delete a catagory, and if so, deletes
     /// the catagory.
     fn delete_catagory_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
@@ -1562,7 +2697,7 @@ impl Tui {
 
         // Create the dialog
         let dialog = Dialog::text(format!("Delete catagory {}?", catagory))
-            .button("No...", |cursive| Self::pop_layer(cursive))
+            .button("No...", Self::pop_layer)
             .button("Yes!", move |cursive| {
                 Self::delete_catagory_dialog_submit(cursive, &catagory);
             });
@@ -1574,11 +2709,26 @@ impl Tui {
         Ok(LayerType::Dialog(dialog))
     }
 
-    /// Deletes the entry if "Yes" is selected on the delete dialog.
+    /// Deletes the catagory if "Yes" is selected on the delete dialog. If the
+    /// catagory still has entries, the user is asked again before they're
+    /// deleted along with it.
     fn delete_catagory_dialog_submit(cursive: &mut Cursive, name: &str) {
         // Grab the cache
         let cache = cursive.user_data::<TuiCache>().unwrap();
 
+        let entries = match cache.db.count_matching(name, &[]) {
+            Ok(entries) => entries,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+        };
+
+        if entries > 0 {
+            Self::push_layer(cursive, Self::delete_catagory_cascade_dialog);
+            return;
+        }
+
         match cache.db.delete_empty_catagory(name) {
             Ok(_) => {}
             Err(error) => {
@@ -1590,10 +2740,178 @@ impl Tui {
         Self::pop_layer(cursive);
     }
 
+    /// Second dialog shown when deleting a catagory that still has entries,
+    /// which warns that the entries will be deleted too.
+    fn delete_catagory_cascade_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let list_view: ViewRef<SelectView> = cursive.find_name(TUI_LIST_ID).unwrap();
+
+        let catagory = match list_view.selection() {
+            Some(catagory) => catagory,
+            None => {
+                bail!("No catagory to operate on!");
+            }
+        };
+
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let entries = cache.db.count_matching(&catagory, &[])?;
+
+        // Create the dialog
+        let dialog = Dialog::text(format!(
+            "Catagory {} still has {} entries! Delete them along with the catagory?\n\n\
+             ONCE THE ENTRIES ARE DELETED, THEY CANNOT BE RECOVERED",
+            catagory, entries
+        ))
+        .button("No...", Self::base_layer)
+        .button("Yes!", move |cursive| {
+            let cache = cursive.user_data::<TuiCache>().unwrap();
+
+            if let Err(error) = cache.db.delete_catagory_cascade(&catagory) {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+
+            Self::base_layer(cursive);
+        });
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Dialog that confirms if you wish to vacuum the database
+    fn vacuum_dialog(_: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let dialog = Dialog::text(
+            "Vacuum the database? This frees up unused space but can take a while on big databases.",
+        )
+        .button("No...", Self::pop_layer)
+        .button("Yes!", Self::vacuum_dialog_submit);
+
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
+
+        Ok(LayerType::Dialog(dialog))
+    }
+
+    /// Vacuums the database in the background if "Yes" is selected on the
+    /// vacuum dialog.
+    fn vacuum_dialog_submit(cursive: &mut Cursive) {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        // The vacuum gets its own connection so the TUI can keep drawing
+        let db = match cache.db.reopen() {
+            Ok(db) => db,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
+                return;
+            }
+        };
+
+        Self::run_task(cursive, "Vacuuming database", move || {
+            db.vacuum().map_err(|error| error.to_string())
+        });
+    }
+
+    /// Run a long operation on another thread, showing a spinner until it's
+    /// done. The dialog that started the task is popped once it finishes,
+    /// which refreshes the view underneath.
+    fn run_task<F>(cursive: &mut Cursive, description: &str, job: F)
+    where
+        F: FnOnce() -> Result<(), String> + Send + 'static,
+    {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let task = Task::new(description);
+        let status = TextView::new(task.status()).with_name(TUI_TASK_STATUS_ID);
+
+        cache.task = Some(task);
+
+        // Swallow escape so the task can't be dismissed while it's running
+        let dialog = OnEventView::new(Dialog::around(status).title("Working..."))
+            .on_pre_event(Event::Key(Key::Esc), |_| {});
+
+        cursive.add_layer(dialog);
+
+        let cb_sink = cursive.cb_sink().clone();
+
+        thread::spawn(move || {
+            let (sender, receiver) = mpsc::channel();
+
+            thread::spawn(move || {
+                // The receiver only goes away if the TUI has closed
+                let _ = sender.send(job());
+            });
+
+            loop {
+                match receiver.recv_timeout(TUI_TASK_TICK) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        if cb_sink.send(Box::new(Self::tick_task)).is_err() {
+                            break;
+                        }
+                    }
+                    result => {
+                        let result =
+                            result.unwrap_or_else(|_| Err("Task stopped unexpectedly!".to_owned()));
+
+                        let _ = cb_sink
+                            .send(Box::new(move |cursive| Self::finish_task(cursive, result)));
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Update the spinner of the running task
+    fn tick_task(cursive: &mut Cursive) {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let status = match cache.task.as_mut() {
+            Some(task) => {
+                task.tick();
+                task.status()
+            }
+            None => {
+                return;
+            }
+        };
+
+        if let Some(mut status_view) = cursive.find_name::<TextView>(TUI_TASK_STATUS_ID) {
+            status_view.set_content(status);
+        }
+    }
+
+    /// Close the progress dialog and the dialog that started the task, then
+    /// report how the task went
+    fn finish_task(cursive: &mut Cursive, result: Result<(), String>) {
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let mut task = match cache.task.take() {
+            Some(task) => task,
+            None => {
+                return;
+            }
+        };
+
+        task.finish(result);
+
+        cursive.pop_layer();
+        Self::pop_layer(cursive);
+
+        Self::info_dialog(cursive, &task.status());
+    }
+
     /// Dialog used to confirm that a used wishes to exit the program.
     fn exit_dialog(_: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
         let exit_dialog = Dialog::text("Are You Sure You Want To Exit?")
-            .button("No...", |cursive| Self::pop_layer(cursive))
+            .button("No...", Self::pop_layer)
             .button("Yes!", |cursive| cursive.quit());
 
         Ok(LayerType::Dialog(OnEventView::new(exit_dialog)))
@@ -1616,19 +2934,13 @@ impl Tui {
             template_list.add_item(template_id.clone(), TemplateType::BuiltIn(template_id));
         }
         // List the template files
-        let template_paths = fs::read_dir(cache.template_dir.as_path())?;
-
-        for entry in template_paths {
-            let path = entry?.path();
+        for path in templates::user_templates(&cache.template_dir)? {
+            let template_name = path.file_name().unwrap().to_str().unwrap().to_string();
 
-            if !path.is_dir() {
-                let template_name = path.file_name().unwrap().to_str().unwrap().to_string();
-
-                template_list.add_item(
-                    template_name,
-                    TemplateType::File(path.to_str().unwrap().to_string()),
-                );
-            }
+            template_list.add_item(
+                template_name,
+                TemplateType::File(path.to_str().unwrap().to_string()),
+            );
         }
 
         let template_list = template_list.with_name(TUI_TEMPLATE_LIST_ID);
@@ -1646,8 +2958,20 @@ impl Tui {
             .child(template_list)
             .child(out_file_row);
 
+        // Marked entries get labels with their own keys
+        let title = match cache.marked.len() {
+            0 => "Fill Out Printable SVG Template".to_owned(),
+            count => format!(
+                "Fill Out Printable SVG Template For {} Marked Entries",
+                count
+            ),
+        };
+
         let dialog = Dialog::around(layout)
-            .title("Fill Out Printable SVG Template")
+            .title(title)
+            .button("Preview", |cursive| {
+                Self::push_layer(cursive, Self::label_preview_dialog)
+            })
             .button("Fill!", |cursive| {
                 Self::fill_template_dialog_submit(cursive)
             });
@@ -1671,43 +2995,15 @@ impl Tui {
 
         let selection = template_list.selection().unwrap();
 
-        let in_data = match selection.as_ref() {
-            TemplateType::BuiltIn(template_id) => templates::TEMPLATES
-                .iter()
-                .find(|template| template.id == template_id)
-                .expect("Template not found!")
-                .get_data(),
-            TemplateType::File(filename) => {
-                let filedata = match fs::read(filename) {
-                    Ok(data) => data,
-                    Err(error) => {
-                        Self::error_dialog(cursive, Box::new(error));
-                        return;
-                    }
-                };
-
-                let mut decoder = match Decoder::new(&filedata[..]) {
-                    Ok(decoder) => decoder,
-                    Err(error) => {
-                        Self::error_dialog(cursive, Box::new(error));
-                        return;
-                    }
-                };
-
-                let mut data: Vec<u8> = Vec::new();
-
-                match decoder.read_to_end(&mut data) {
-                    Ok(_) => {}
-                    Err(error) => {
-                        Self::error_dialog(cursive, Box::new(error));
-                        return;
-                    }
-                };
+        if let TemplateType::NS = selection.as_ref() {
+            Self::info_dialog(cursive, "You need to select a template!");
+            return;
+        }
 
-                data
-            }
-            TemplateType::NS => {
-                Self::info_dialog(cursive, "You need to select a template!");
+        let in_data = match Self::load_template(&selection) {
+            Ok(data) => data,
+            Err(error) => {
+                Self::error_dialog(cursive, error);
                 return;
             }
         };
@@ -1716,7 +3012,21 @@ impl Tui {
 
         let in_string = String::from_utf8_lossy(&in_data);
 
-        let out_data = match cache.db.fill_svg_template(&in_string) {
+        let out_data = match cache.marked.is_empty() {
+            true => cache.db.fill_svg_template(&in_string),
+            false => cache
+                .db
+                .fill_svg_template_with_keys(&in_string, &cache.marked),
+        };
+
+        // Files ending in .pdf are converted, anything else is an SVG
+        let out_data =
+            out_data.and_then(|out_data| match out_path.to_lowercase().ends_with(".pdf") {
+                true => templates::svg_to_pdf(&out_data),
+                false => Ok(out_data.into_bytes()),
+            });
+
+        let out_data = match out_data {
             Ok(out_data) => out_data,
             Err(error) => {
                 Self::error_dialog(cursive, error);
@@ -1735,69 +3045,170 @@ impl Tui {
         Self::pop_layer(cursive);
     }
 
-    /// Converts a table into strings that mimic an excel table, or something
-    /// alike that.
-    fn columnator(headers: Vec<String>, table: Vec<Vec<String>>) -> Vec<String> {
-        // First calculate the widths of each column
-        let mut column_widths = Vec::<usize>::with_capacity(headers.len());
-        let mut out_string_size: usize = 0;
+    /// Dialog showing what the first label of the selected template will be
+    /// filled with
+    fn label_preview_dialog(cursive: &mut Cursive) -> Result<LayerType, Box<dyn Error>> {
+        let template_list: ViewRef<SelectView<TemplateType>> =
+            cursive.find_name(TUI_TEMPLATE_LIST_ID).unwrap();
+
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
+
+        let selection = template_list.selection().unwrap();
 
-        for (i, header) in headers.iter().enumerate() {
-            let mut width = header.len();
+        let in_data = Self::load_template(&selection)?;
+        let in_string = String::from_utf8_lossy(&in_data);
+
+        let labels = match cache.marked.is_empty() {
+            true => cache.db.label_substitutions(&in_string)?,
+            false => cache
+                .db
+                .label_substitutions_for_keys(&in_string, &cache.marked)?,
+        };
 
-            for row in &table {
-                width = cmp::max(width, row[i].len());
+        let label = match labels.first() {
+            Some(label) => label,
+            None => {
+                bail!("Template has no labels to fill!");
             }
+        };
 
-            column_widths.push(width);
-            out_string_size += width + TUI_COLUMN_PADDING_LEN;
-        }
+        let dialog = Dialog::around(TextView::new(Self::label_preview(label)))
+            .title(format!("Label 1 of {}", labels.len()))
+            .button("Ok", Self::pop_layer);
 
-        // Next generate strings of each row with padding to make each column the same width
-        // starting with the headers
-        let mut out_strings = Vec::<String>::with_capacity(table.len() + 1);
+        // Prime the default dialog bindings
+        let mut dialog = OnEventView::new(dialog);
+        Self::prime_dialog(&mut dialog);
 
-        let mut out_string = String::with_capacity(out_string_size);
+        Ok(LayerType::Dialog(dialog))
+    }
 
-        for (i, header) in headers.iter().enumerate() {
-            out_string.push_str(&format!(
-                "{:<width$}{}",
-                header,
-                TUI_COLUMN_PADDING,
-                width = column_widths[i]
-            ));
+    /// Load and decompress the data of a selected template
+    fn load_template(selection: &TemplateType) -> Result<Vec<u8>, Box<dyn Error>> {
+        let data = match selection {
+            TemplateType::BuiltIn(template_id) => match templates::TEMPLATES
+                .iter()
+                .find(|template| template.id == template_id)
+            {
+                Some(template) => template.get_data(),
+                None => {
+                    bail!("Template {} not found!", template_id);
+                }
+            },
+            TemplateType::File(filename) => {
+                let filedata = fs::read(filename)?;
+
+                templates::decompress(&filedata)?
+            }
+            TemplateType::NS => {
+                bail!("You need to select a template!");
+            }
+        };
+
+        Ok(data)
+    }
+
+    /// Render what a label will be filled with as a box of text
+    fn label_preview(label: &[EntryField]) -> String {
+        let lines: Vec<String> = label
+            .iter()
+            .map(|field| format!("{} = {}", field.id, field.value))
+            .collect();
+
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let border = format!("+{}+", "-".repeat(width + 2));
+
+        let mut out = border.clone();
+
+        for line in lines {
+            out.push_str(&format!("\n| {:<width$} |", line, width = width));
         }
 
-        out_strings.push(out_string);
+        out.push('\n');
+        out.push_str(&border);
+
+        out
+    }
 
-        for row in table {
-            let mut out_string = String::with_capacity(out_string_size);
+    /// Grab the full version of the entry selected in the list view, which
+    /// only holds the light version of each entry.
+    fn selected_entry(cursive: &mut Cursive) -> Result<Entry, Box<dyn Error>> {
+        let list_view: ViewRef<SelectView<Entry>> = cursive.find_name(TUI_LIST_ID).unwrap();
 
-            for (i, column) in row.iter().enumerate() {
-                out_string.push_str(&format!(
-                    "{:<width$}{}",
-                    column,
-                    TUI_COLUMN_PADDING,
-                    width = column_widths[i]
-                ));
+        let key = match list_view.selection() {
+            Some(entry) => entry.key,
+            None => {
+                bail!("No entry to operate on!");
             }
+        };
 
-            out_strings.push(out_string);
-        }
+        // Grab the cache
+        let cache = cursive.user_data::<TuiCache>().unwrap();
 
-        out_strings
+        cache.db.grab_entry(key)
+    }
+
+    /// Grab the fields of a catagory that the user can fill out, which
+    /// excludes created and modified because they are autogenerated
+    fn editable_fields(db: &Db, catagory: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut fields: Vec<String> = db::BUILTIN_FIELDS[..3]
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+
+        fields.extend(db.display_order(catagory)?);
+
+        Ok(fields)
+    }
+
+    /// Labels for the rows of the entry dialogs, like "OHMS (REAL):", padded
+    /// to the same width. The builtin fields are labeled without a type.
+    fn field_labels(
+        db: &Db,
+        catagory_id: &str,
+        fields: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let catagory = db.grab_catagory(catagory_id)?;
+
+        let labels: Vec<String> = fields
+            .iter()
+            .map(
+                |id| match catagory.fields.iter().find(|field| field.id == *id) {
+                    Some(field) => format!("{} ({}):", id, field.sql_type()),
+                    None => format!("{}:", id),
+                },
+            )
+            .collect();
+
+        let width = labels.iter().map(String::len).max().unwrap_or(0) + 1;
+
+        Ok(labels
+            .into_iter()
+            .map(|label| format!("{:<width$}", label, width = width))
+            .collect())
     }
 
     /// Dialog presenting a non-fatal error
     fn info_dialog(cursive: &mut Cursive, string: &str) {
         let dialog = Dialog::info(string).title("Info:");
 
+        // Escape only closes the info, like with errors
+        let dialog = OnEventView::new(dialog).on_event(Key::Esc, |cursive| {
+            cursive.pop_layer();
+        });
+
         cursive.add_layer(dialog)
     }
     /// Dialog presenting a non-fatal error
     fn error_dialog(cursive: &mut Cursive, error: Box<dyn Error>) {
         let dialog = Dialog::info(format!("{}", error)).title("Error!");
 
+        // Escape only closes the error, not the dialog it came from
+        let dialog = OnEventView::new(dialog).on_event(Key::Esc, |cursive| {
+            cursive.pop_layer();
+        });
+
         cursive.add_layer(dialog)
     }
 
@@ -1811,6 +3222,121 @@ impl Tui {
     }
 }
 
+/// Event sent when a key is pressed, None if the key isn't one bindings can
+/// use
+fn key_event(key: &str) -> Option<Event> {
+    let mut chars = key.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => Some(Event::Char(character)),
+        _ => match key {
+            "Del" => Some(Event::Key(Key::Del)),
+            "Space" => Some(Event::Char(' ')),
+            _ => None,
+        },
+    }
+}
+
+/// Key a binding is bound to, the one from the config if there is one
+fn binding_key<'a>(binding: &'a Binding, keys: &'a HashMap<String, String>) -> &'a str {
+    keys.get(binding.name)
+        .map(String::as_str)
+        .unwrap_or(binding.key)
+}
+
+/// List bindings one per line, like "a - add an entry"
+fn bindings_help(bindings: &[Binding], keys: &HashMap<String, String>) -> String {
+    bindings
+        .iter()
+        .map(|binding| format!("{} - {}\n", binding_key(binding, keys), binding.help))
+        .collect()
+}
+
+/// Text of every column of an entry as entry view shows it, timestamps in
+/// the format from the config
+fn entry_cells(entry: &Entry, config: &Config) -> Vec<String> {
+    let mut cells = vec![
+        b64::from_u64_padded(entry.key, b64::KEY_WIDTH),
+        entry.location.clone(),
+        entry.quantity.to_string(),
+        config.format_timestamp(entry.created),
+        config.format_timestamp(entry.modified),
+    ];
+
+    cells.extend(entry.fields.iter().map(|field| field.value.clone()));
+
+    cells
+}
+
+/// Whether any column of an entry contains the search, ignoring case. An
+/// empty search matches everything.
+fn entry_matches(entry: &Entry, search: &str, config: &Config) -> bool {
+    let search = search.to_lowercase();
+
+    entry_cells(entry, config)
+        .iter()
+        .any(|cell| cell.to_lowercase().contains(&search))
+}
+
+/// Style of an entry view row, red if the entry is low on stock
+fn quantity_style(quantity: u64, low_stock_threshold: u64) -> Style {
+    match quantity <= low_stock_threshold {
+        true => Color::Dark(BaseColor::Red).into(),
+        false => Style::none(),
+    }
+}
+
+/// Rows of entry view that match the search, styled by quantity. If any
+/// entries are marked, every row starts with a column for the marks.
+fn shown_rows(
+    rows: &[(String, Entry)],
+    search: &str,
+    config: &Config,
+    marked: &[u64],
+) -> Vec<(StyledString, Entry)> {
+    rows.iter()
+        .filter(|(_, entry)| entry_matches(entry, search, config))
+        .map(|(row, entry)| {
+            let style = quantity_style(entry.quantity, config.low_stock_threshold);
+
+            let row = match (marked.is_empty(), marked.contains(&entry.key)) {
+                (true, _) => row.clone(),
+                (false, true) => format!("* {}", row),
+                (false, false) => format!("  {}", row),
+            };
+
+            (StyledString::styled(row, style), entry.clone())
+        })
+        .collect()
+}
+
+/// Whether a value typed into a field of the given type can be stored in it.
+/// Blank values are NULL, except for the builtin fields.
+fn valid_value(field: &str, value: &str, datatype: db::DataType) -> bool {
+    match field {
+        "KEY" => b64::parse_key(value).is_ok(),
+        "LOCATION" => true,
+        "QUANTITY" => value.parse::<u64>().is_ok(),
+        _ if value.is_empty() => true,
+        // Don't read the whole file on every key press, just make sure it's
+        // there
+        _ if datatype == db::DataType::BLOB && !value.starts_with("0x") => {
+            Path::new(value).is_file()
+        }
+        _ => Db::value_from_string(value, datatype).is_ok(),
+    }
+}
+
+/// Text to show in place of an empty list, so a new database or catagory isn't
+/// just a blank screen. None if the list has something in it.
+fn empty_list_message(what: &str, add_key: &str, len: usize, filtered: bool) -> Option<String> {
+    match (len, filtered) {
+        (0, true) => Some(format!("No {} match the search", what)),
+        (0, false) => Some(format!("No {} yet, press '{}' to add one", what, add_key)),
+        _ => None,
+    }
+}
+
 /// Data cache during the TUI session
 struct TuiCache {
     /// The directory for templates
@@ -1822,7 +3348,189 @@ struct TuiCache {
     /// Constraints that affect what is displated in entry view
     pub constraints: Vec<Condition>,
     /// Binding to call when popping out of a dialog
-    pub escape_action: Vec<fn(&mut Cursive) -> Result<LayerType, Box<dyn Error>>>,
+    pub escape_action: Vec<LayerInit>,
     pub selected_catagory: String,
     pub selected_key: u64,
+    /// If set, catagory view lists catagories from Z to A
+    pub catagories_reversed: bool,
+    /// Field entry view is sorted by, if any
+    pub sort: Option<Sort>,
+    /// Keys the config gives bindings in place of their defaults, by binding
+    /// name
+    pub keys: HashMap<String, String>,
+    /// Config the TUI was started with
+    pub config: Config,
+    /// Keys of the entries marked in entry view
+    pub marked: Vec<u64>,
+    /// System clipboard, opened the first time something is copied
+    pub clipboard: Option<arboard::Clipboard>,
+    /// Text entry view rows are narrowed down to, see entry_matches
+    pub search: String,
+    /// Every row loaded into entry view, before the search narrows them
+    pub entry_rows: Vec<(String, Entry)>,
+    /// Long running operation in progress, if any
+    pub task: Option<Task>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_value() {
+        assert!(valid_value("KEY", "3F", db::DataType::INTEGER));
+        assert!(!valid_value("KEY", "3$", db::DataType::INTEGER));
+        assert!(!valid_value("KEY", "", db::DataType::INTEGER));
+        assert!(valid_value("QUANTITY", "10", db::DataType::INTEGER));
+        assert!(!valid_value("QUANTITY", "-1", db::DataType::INTEGER));
+        assert!(!valid_value("QUANTITY", "", db::DataType::INTEGER));
+        assert!(valid_value("LOCATION", "", db::DataType::TEXT));
+
+        // Blank is NULL
+        assert!(valid_value("OHMS", "", db::DataType::REAL));
+        assert!(valid_value("OHMS", "4.7e3", db::DataType::REAL));
+        assert!(!valid_value("OHMS", "4.7k", db::DataType::REAL));
+        assert!(valid_value("PINS", "1e2", db::DataType::INTEGER));
+        assert!(!valid_value("PINS", "1.5", db::DataType::INTEGER));
+        assert!(valid_value("MPN", "anything at all", db::DataType::TEXT));
+        assert!(valid_value("DATASHEET", "0xBEEF", db::DataType::BLOB));
+        assert!(!valid_value("DATASHEET", "0xBEE", db::DataType::BLOB));
+        assert!(!valid_value(
+            "DATASHEET",
+            "/no/such/datasheet.pdf",
+            db::DataType::BLOB
+        ));
+    }
+
+    #[test]
+    fn test_field_labels() {
+        let mut db = Db::_new_test();
+
+        db.add_catagory(Catagory::with_fields(
+            "BOLT",
+            vec![CatagoryField::new("LENGTH", db::DataType::REAL)],
+        ))
+        .unwrap();
+
+        let fields = vec!["KEY".to_owned(), "LENGTH".to_owned()];
+
+        assert_eq!(
+            Tui::field_labels(&db, "BOLT", &fields).unwrap(),
+            vec!["KEY:           ", "LENGTH (REAL): "]
+        );
+    }
+
+    #[test]
+    fn test_quantity_style() {
+        let red: Style = Color::Dark(BaseColor::Red).into();
+
+        assert_eq!(quantity_style(0, 0), red);
+        assert_eq!(quantity_style(1, 0), Style::none());
+        assert_eq!(quantity_style(5, 5), red);
+        assert_eq!(quantity_style(6, 5), Style::none());
+    }
+
+    #[test]
+    fn test_empty_list_message() {
+        assert_eq!(
+            empty_list_message("catagories", "a", 0, false).unwrap(),
+            "No catagories yet, press 'a' to add one"
+        );
+        assert_eq!(
+            empty_list_message("entries", "a", 0, true).unwrap(),
+            "No entries match the search"
+        );
+        assert_eq!(empty_list_message("entries", "a", 3, false), None);
+        assert_eq!(empty_list_message("entries", "a", 3, true), None);
+        assert_eq!(
+            empty_list_message("entries", "n", 0, false).unwrap(),
+            "No entries yet, press 'n' to add one"
+        );
+    }
+
+    #[test]
+    fn test_bindings() {
+        for view_bindings in [Tui::CATAGORY_VIEW_BINDINGS, Tui::ENTRY_VIEW_BINDINGS] {
+            let bindings: Vec<&Binding> = Tui::VIEW_BINDINGS.iter().chain(view_bindings).collect();
+
+            for (i, binding) in bindings.iter().enumerate() {
+                assert!(key_event(binding.key).is_some(), "{}", binding.key);
+                assert!(
+                    bindings[..i].iter().all(|other| other.key != binding.key),
+                    "{} is bound twice",
+                    binding.key
+                );
+            }
+        }
+
+        assert!(bindings_help(Tui::ENTRY_VIEW_BINDINGS, &HashMap::new())
+            .contains("\n/ - search the entries listed\n"));
+        assert_eq!(key_event("Del"), Some(Event::Key(Key::Del)));
+        assert_eq!(key_event("Space"), Some(Event::Char(' ')));
+        assert_eq!(key_event("Delete"), None);
+    }
+
+    #[test]
+    fn test_check_keys() {
+        let config = Config::from_toml("[keys]\nfind = \"/\"\nsearch = \"S\"").unwrap();
+
+        let (keys, warnings) = Tui::check_keys(&config.keys);
+
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(binding_key(&Tui::VIEW_BINDINGS[0], &keys), "/");
+        assert_eq!(binding_key(&Tui::ENTRY_VIEW_BINDINGS[8], &keys), "S");
+        assert!(bindings_help(Tui::VIEW_BINDINGS, &keys).starts_with("/ - find an entry by key\n"));
+
+        // Without moving search out of the way find clashes with it in entry
+        // view, so both keep their defaults
+        let config = Config::from_toml("[keys]\nfind = \"/\"").unwrap();
+
+        let (keys, warnings) = Tui::check_keys(&config.keys);
+
+        assert_eq!(keys, HashMap::new());
+        assert_eq!(
+            warnings,
+            vec!["find and search are both bound to /, using their default keys!"]
+        );
+
+        // Swapping keys is fine
+        let config = Config::from_toml("[keys]\nfilter = \"f\"\nfind = \"F\"").unwrap();
+
+        assert_eq!(Tui::check_keys(&config.keys).1, Vec::<String>::new());
+
+        // Clashes in bindings every view has are only warned about once
+        let config = Config::from_toml("[keys]\nfind = \"p\"").unwrap();
+
+        assert_eq!(Tui::check_keys(&config.keys).1.len(), 1);
+
+        let config = Config::from_toml("[keys]\nfly = \"x\"\ngive = \"Insert\"").unwrap();
+
+        let (keys, warnings) = Tui::check_keys(&config.keys);
+
+        assert_eq!(keys, HashMap::new());
+        assert_eq!(
+            warnings,
+            vec![
+                "There is no key binding named fly!",
+                "Can't bind give to \"Insert\"!"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_matches() {
+        let mut entry = Entry::new("RESISTOR", 10, "Shelf 2", 35, 0, 0);
+        entry.add_field(EntryField::new("MPN", "ERJ-PM8F8204V"));
+
+        let config = Config::default();
+
+        assert!(entry_matches(&entry, "", &config));
+        assert!(entry_matches(&entry, "erj-pm", &config));
+        assert!(entry_matches(&entry, "SHELF", &config));
+        // Keys are matched as they're shown, in base64 padded with zeros
+        assert!(entry_matches(&entry, "A", &config));
+        assert!(entry_matches(&entry, "00000a", &config));
+        assert!(entry_matches(&entry, "35", &config));
+        assert!(!entry_matches(&entry, "shelf 3", &config));
+    }
 }